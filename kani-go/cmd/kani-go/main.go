@@ -0,0 +1,77 @@
+package main
+
+import (
+	"flag"
+	"image/png"
+	"log"
+	"os"
+	"runtime"
+
+	kani "kani-go"
+)
+
+// fix panicked at 'Initializing the event loop outside of the main thread by
+// locking kanirenderer to main thread, which required by winit
+func init() {
+	runtime.LockOSThread()
+}
+
+// using kanirenderer in go
+func main() {
+	log.Println("kanirenderer in go")
+	var path string
+	flag.StringVar(&path, "path", "", "enter file path ")
+	var filet string
+	flag.StringVar(&filet, "type", "opengl", "enter file type")
+	var mode string
+	flag.StringVar(&mode, "mode", "fullscreen", "enter window mode")
+	var hdr bool
+	flag.BoolVar(&hdr, "hdr", false, "use a hdr surface")
+	var out string
+	flag.StringVar(&out, "out", "", "render one frame to this png instead of opening a window")
+	var size int
+	flag.IntVar(&size, "size", 512, "width and height of -out")
+	flag.Parse()
+	if path == "" {
+		log.Panicln("no files path provided, please provide -path=/path/to/yourobj")
+	}
+
+	if out == "" {
+		kani.Run(path, kani.RunOptions{FileType: kani.FileType(filet), WindowMode: mode, HDR: hdr})
+		return
+	}
+
+	viewer, err := kani.NewViewer(kani.Options{Width: size, Height: size, FileType: kani.FileType(filet)})
+	if err != nil {
+		log.Fatalln(err)
+	}
+	defer viewer.Close()
+	if err := viewer.LoadModel(path); err != nil {
+		log.Fatalln(err)
+	}
+	if err := viewer.FrameModel(45, 25); err != nil {
+		log.Fatalln(err)
+	}
+	img, err := viewer.Render()
+	if err != nil {
+		log.Fatalln(err)
+	}
+	file, err := os.Create(out)
+	if err != nil {
+		log.Fatalln(err)
+	}
+	defer file.Close()
+	if err := png.Encode(file, img); err != nil {
+		log.Fatalln(err)
+	}
+	log.Println("saved", out)
+}
+
+//build kanirenderer, cargo build --release in the repository root
+//(kanirenderer_viewer.h there is kept in sync with src/ffi.rs, or regenerate it with cbindgen)
+//cbindgen --config template.toml --crate kanirenderer_viewer --output kanirenderer_viewer.h
+//for other targets copy the library and header to ../../lib
+//
+//then compile with zig c compiler
+// run  >> CC="zig cc -target x86_64-windows-gnu" CXX="zig c++ -target x86_64-windows-gnu" GOOS="windows" GOARCH="amd64" CGO_ENABLED=1 go build ./cmd/kani-go
+//then run kani-go -path=/path/to/your.obj
@@ -0,0 +1,303 @@
+// Package kani wraps the C API of kanirenderer_viewer: an offscreen viewer that loads obj models
+// and renders them into image.RGBA, plus Run for the windowed viewer.
+//
+// build the library first with `cargo build --release` in the repository root, cgo links
+// target/release/libkanirenderer_viewer.so (or a copy in ./lib) and the header next to it.
+package kani
+
+/*
+#cgo CFLAGS: -I${SRCDIR}/lib -I${SRCDIR}/..
+#cgo LDFLAGS: -L${SRCDIR}/lib -L${SRCDIR}/../target/release -lkanirenderer_viewer
+#cgo linux LDFLAGS: -Wl,-rpath,${SRCDIR}/lib -Wl,-rpath,${SRCDIR}/../target/release
+#include "kanirenderer_viewer.h"
+#include <stdlib.h>
+*/
+import "C"
+
+import (
+	"fmt"
+	"image"
+	"runtime"
+	"unsafe"
+)
+
+// ErrorCode is one of the KANI_ERROR_ codes of the C API. CodeDevice means there is no
+// graphics adapter or the device couldn't be created
+type ErrorCode int32
+
+const (
+	CodeNullPointer     ErrorCode = C.KANI_ERROR_NULL_POINTER
+	CodeInvalidArgument ErrorCode = C.KANI_ERROR_INVALID_ARGUMENT
+	CodeDevice          ErrorCode = C.KANI_ERROR_DEVICE
+	CodeLoadFailed      ErrorCode = C.KANI_ERROR_LOAD_FAILED
+	CodeRenderFailed    ErrorCode = C.KANI_ERROR_RENDER_FAILED
+	CodeBufferTooSmall  ErrorCode = C.KANI_ERROR_BUFFER_TOO_SMALL
+	CodePanic           ErrorCode = C.KANI_ERROR_PANIC
+)
+
+// Error is a failed call, Message is what kani_last_error returned for it
+type Error struct {
+	Code    ErrorCode
+	Message string
+}
+
+func (e *Error) Error() string {
+	return fmt.Sprintf("kanirenderer error %d: %s", e.Code, e.Message)
+}
+
+// Is matches on the code only, so errors.Is(err, kani.ErrDevice) works
+func (e *Error) Is(target error) bool {
+	t, ok := target.(*Error)
+	return ok && t.Code == e.Code
+}
+
+var (
+	ErrNullPointer     = &Error{Code: CodeNullPointer}
+	ErrInvalidArgument = &Error{Code: CodeInvalidArgument}
+	ErrDevice          = &Error{Code: CodeDevice}
+	ErrLoadFailed      = &Error{Code: CodeLoadFailed}
+	ErrRenderFailed    = &Error{Code: CodeRenderFailed}
+	ErrBufferTooSmall  = &Error{Code: CodeBufferTooSmall}
+	ErrPanic           = &Error{Code: CodePanic}
+)
+
+// call runs f and turns its code into an error. the message of the last error is kept per
+// thread on the rust side, so the goroutine stays on one thread until it is read
+func call(f func() C.int32_t) error {
+	runtime.LockOSThread()
+	defer runtime.UnlockOSThread()
+	code := f()
+	if code == C.KANI_OK {
+		return nil
+	}
+	return &Error{Code: ErrorCode(code), Message: C.GoString(C.kani_last_error())}
+}
+
+// FileType decides how texture coordinates are read, see the README
+type FileType string
+
+const (
+	FileTypeDefault FileType = "default"
+	FileTypeOpenGL  FileType = "opengl"
+)
+
+// RenderMode is one of the viewer's output modes
+type RenderMode string
+
+const (
+	ModeUnlit        RenderMode = "unlit"
+	ModeLit          RenderMode = "lit"
+	ModeShadow       RenderMode = "shadow"
+	ModeWireframe    RenderMode = "wireframe"
+	ModeDebug        RenderMode = "debug"
+	ModeValidation   RenderMode = "validation"
+	ModeNormals      RenderMode = "normals"
+	ModeNormalMap    RenderMode = "normal-map"
+	ModeUV           RenderMode = "uv"
+	ModeUVChecker    RenderMode = "uv-checker"
+	ModeTexelDensity RenderMode = "texel-density"
+)
+
+type Vec3 [3]float32
+
+// Color is linear rgb
+type Color [3]float32
+
+type DirectionalLight struct {
+	// direction the light shines in, doesn't have to be normalized
+	Direction Vec3
+	Color     Color
+	Intensity float32
+}
+
+type PointLight struct {
+	Position Vec3
+	Color    Color
+	Range    float32
+}
+
+// Pick is the closest mesh under a pixel
+type Pick struct {
+	Model    int
+	Mesh     int
+	Distance float32
+}
+
+// Options for NewViewer, an empty FileType means FileTypeDefault
+type Options struct {
+	Width    int
+	Height   int
+	FileType FileType
+}
+
+// Viewer renders offscreen, it has to be freed with Close
+type Viewer struct {
+	ptr *C.KaniViewer
+}
+
+func cString(s string) (*C.char, func()) {
+	cs := C.CString(s)
+	return cs, func() { C.free(unsafe.Pointer(cs)) }
+}
+
+func NewViewer(opts Options) (*Viewer, error) {
+	if opts.FileType == "" {
+		opts.FileType = FileTypeDefault
+	}
+	if opts.Width <= 0 || opts.Height <= 0 {
+		return nil, &Error{Code: CodeInvalidArgument, Message: fmt.Sprintf("size must be positive, got %dx%d", opts.Width, opts.Height)}
+	}
+	fileType, free := cString(string(opts.FileType))
+	defer free()
+	var ptr *C.KaniViewer
+	err := call(func() C.int32_t {
+		return C.kani_viewer_create(fileType, C.uint32_t(opts.Width), C.uint32_t(opts.Height), &ptr)
+	})
+	if err != nil {
+		return nil, err
+	}
+	return &Viewer{ptr: ptr}, nil
+}
+
+// Close frees the viewer, calling it again does nothing
+func (v *Viewer) Close() {
+	if v.ptr != nil {
+		C.kani_viewer_destroy(v.ptr)
+		v.ptr = nil
+	}
+}
+
+// LoadModel replaces the loaded model and blocks until it's uploaded.
+// a failed load leaves the scene empty
+func (v *Viewer) LoadModel(path string) error {
+	cPath, free := cString(path)
+	defer free()
+	return call(func() C.int32_t { return C.kani_viewer_load_model(v.ptr, cPath) })
+}
+
+func (v *Viewer) UnloadModel() error {
+	return call(func() C.int32_t { return C.kani_viewer_unload_model(v.ptr) })
+}
+
+func (v *Viewer) Resize(width, height int) error {
+	if width <= 0 || height <= 0 {
+		return &Error{Code: CodeInvalidArgument, Message: fmt.Sprintf("size must be positive, got %dx%d", width, height)}
+	}
+	return call(func() C.int32_t { return C.kani_viewer_resize(v.ptr, C.uint32_t(width), C.uint32_t(height)) })
+}
+
+func (v *Viewer) Size() (width, height int, err error) {
+	var w, h C.uint32_t
+	err = call(func() C.int32_t { return C.kani_viewer_size(v.ptr, &w, &h) })
+	return int(w), int(h), err
+}
+
+// SetCamera puts the camera at position looking at target
+func (v *Viewer) SetCamera(position, target Vec3) error {
+	return call(func() C.int32_t {
+		return C.kani_viewer_set_camera(v.ptr,
+			C.float(position[0]), C.float(position[1]), C.float(position[2]),
+			C.float(target[0]), C.float(target[1]), C.float(target[2]))
+	})
+}
+
+// FrameModel points the camera at the model from far enough away that all of it is visible,
+// azimuth around the y axis and elevation in degrees
+func (v *Viewer) FrameModel(azimuth, elevation float32) error {
+	return call(func() C.int32_t { return C.kani_viewer_frame_model(v.ptr, C.float(azimuth), C.float(elevation)) })
+}
+
+func (v *Viewer) SetDirectionalLight(light DirectionalLight) error {
+	return call(func() C.int32_t {
+		return C.kani_viewer_set_directional_light(v.ptr,
+			C.float(light.Direction[0]), C.float(light.Direction[1]), C.float(light.Direction[2]),
+			C.float(light.Color[0]), C.float(light.Color[1]), C.float(light.Color[2]),
+			C.float(light.Intensity))
+	})
+}
+
+func (v *Viewer) SetPointLight(light PointLight) error {
+	return call(func() C.int32_t {
+		return C.kani_viewer_set_point_light(v.ptr,
+			C.float(light.Position[0]), C.float(light.Position[1]), C.float(light.Position[2]),
+			C.float(light.Color[0]), C.float(light.Color[1]), C.float(light.Color[2]),
+			C.float(light.Range))
+	})
+}
+
+func (v *Viewer) SetRenderMode(mode RenderMode) error {
+	cMode, free := cString(string(mode))
+	defer free()
+	return call(func() C.int32_t { return C.kani_viewer_set_render_mode(v.ptr, cMode) })
+}
+
+// Render draws one frame into a new image the size of the viewer
+func (v *Viewer) Render() (*image.RGBA, error) {
+	width, height, err := v.Size()
+	if err != nil {
+		return nil, err
+	}
+	img := image.NewRGBA(image.Rect(0, 0, width, height))
+	if err := v.RenderInto(img); err != nil {
+		return nil, err
+	}
+	return img, nil
+}
+
+// RenderInto draws one frame into img, which has to be exactly the size of the viewer
+func (v *Viewer) RenderInto(img *image.RGBA) error {
+	width, height, err := v.Size()
+	if err != nil {
+		return err
+	}
+	if img.Rect.Dx() != width || img.Rect.Dy() != height || img.Stride != width*4 || len(img.Pix) == 0 {
+		return &Error{Code: CodeInvalidArgument, Message: fmt.Sprintf("image is %v with stride %d, the viewer is %dx%d", img.Rect, img.Stride, width, height)}
+	}
+	return call(func() C.int32_t {
+		return C.kani_viewer_render(v.ptr, (*C.uint8_t)(unsafe.Pointer(&img.Pix[0])), C.size_t(len(img.Pix)))
+	})
+}
+
+// Pick finds the closest mesh under pixel (x, y) of the rendered image, ok is false when there is none
+func (v *Viewer) Pick(x, y float32) (pick Pick, ok bool, err error) {
+	var out C.KaniPick
+	err = call(func() C.int32_t { return C.kani_viewer_pick(v.ptr, C.float(x), C.float(y), &out) })
+	if err != nil || !bool(out.hit) {
+		return Pick{}, false, err
+	}
+	return Pick{Model: int(out.model), Mesh: int(out.mesh), Distance: float32(out.distance)}, true, nil
+}
+
+// RunOptions for Run, empty fields use the viewer's defaults
+type RunOptions struct {
+	FileType FileType
+	// "fullscreen" or "windowed"
+	WindowMode string
+	HDR        bool
+}
+
+// Run opens the interactive viewer and does not return, winit's event loop never hands control
+// back so closing the window exits the whole process without running deferred functions.
+// winit needs the event loop on the main thread, call runtime.LockOSThread from an init function
+// of package main
+func Run(path string, opts RunOptions) {
+	if opts.FileType == "" {
+		opts.FileType = FileTypeDefault
+	}
+	if opts.WindowMode == "" {
+		opts.WindowMode = "windowed"
+	}
+	hdr := "false"
+	if opts.HDR {
+		hdr = "true"
+	}
+	cPath, freePath := cString(path)
+	defer freePath()
+	cFileType, freeFileType := cString(string(opts.FileType))
+	defer freeFileType()
+	cMode, freeMode := cString(opts.WindowMode)
+	defer freeMode()
+	cHDR, freeHDR := cString(hdr)
+	defer freeHDR()
+	C.run_kanirenderer(cPath, cFileType, cMode, cHDR)
+}
@@ -0,0 +1,110 @@
+package kani
+
+import (
+	"errors"
+	"testing"
+)
+
+// links against target/release/libkanirenderer_viewer.so, run `cargo build --release` first.
+// tests that need a gpu are skipped when there is no adapter
+
+const cube = "../res/cube.obj"
+
+func newTestViewer(t *testing.T, width, height int) *Viewer {
+	t.Helper()
+	viewer, err := NewViewer(Options{Width: width, Height: height})
+	if errors.Is(err, ErrDevice) {
+		t.Skipf("no graphics adapter: %v", err)
+	}
+	if err != nil {
+		t.Fatal(err)
+	}
+	t.Cleanup(viewer.Close)
+	return viewer
+}
+
+func TestInvalidOptions(t *testing.T) {
+	if _, err := NewViewer(Options{Width: 64, Height: 64, FileType: "bogus"}); !errors.Is(err, ErrInvalidArgument) {
+		t.Fatalf("unknown file type: got %v, want ErrInvalidArgument", err)
+	}
+	if _, err := NewViewer(Options{Width: 0, Height: 64}); !errors.Is(err, ErrInvalidArgument) {
+		t.Fatalf("zero width: got %v, want ErrInvalidArgument", err)
+	}
+}
+
+func TestErrorMessage(t *testing.T) {
+	_, err := NewViewer(Options{Width: 64, Height: 64, FileType: "bogus"})
+	var kaniErr *Error
+	if !errors.As(err, &kaniErr) || kaniErr.Message == "" {
+		t.Fatalf("got %#v, want an *Error with the message from kani_last_error", err)
+	}
+}
+
+func TestRender(t *testing.T) {
+	viewer := newTestViewer(t, 64, 48)
+	if err := viewer.LoadModel(cube); err != nil {
+		t.Fatal(err)
+	}
+	if err := viewer.FrameModel(45, 25); err != nil {
+		t.Fatal(err)
+	}
+	if err := viewer.SetDirectionalLight(DirectionalLight{Direction: Vec3{0.35, -1, -0.7}, Color: Color{1, 1, 1}, Intensity: 1}); err != nil {
+		t.Fatal(err)
+	}
+	if err := viewer.SetRenderMode(ModeLit); err != nil {
+		t.Fatal(err)
+	}
+	img, err := viewer.Render()
+	if err != nil {
+		t.Fatal(err)
+	}
+	if img.Rect.Dx() != 64 || img.Rect.Dy() != 48 {
+		t.Fatalf("image is %v, want 64x48", img.Rect)
+	}
+
+	//the framed cube covers the centre, the corner is background
+	center, corner := img.RGBAAt(32, 24), img.RGBAAt(0, 0)
+	if center == corner {
+		t.Fatalf("centre and corner are both %v, the cube wasn't drawn", center)
+	}
+	pick, ok, err := viewer.Pick(32, 24)
+	if err != nil || !ok {
+		t.Fatalf("pick at the centre: %v %v %v", pick, ok, err)
+	}
+	if _, ok, _ := viewer.Pick(0, 0); ok {
+		t.Fatal("pick at the corner hit a mesh")
+	}
+}
+
+func TestResize(t *testing.T) {
+	viewer := newTestViewer(t, 32, 32)
+	if err := viewer.Resize(80, 40); err != nil {
+		t.Fatal(err)
+	}
+	if width, height, err := viewer.Size(); err != nil || width != 80 || height != 40 {
+		t.Fatalf("size after resize: %dx%d %v", width, height, err)
+	}
+	img, err := viewer.Render()
+	if err != nil {
+		t.Fatal(err)
+	}
+	if img.Rect.Dx() != 80 || img.Rect.Dy() != 40 {
+		t.Fatalf("image is %v, want 80x40", img.Rect)
+	}
+}
+
+func TestViewerErrors(t *testing.T) {
+	viewer := newTestViewer(t, 32, 32)
+	if err := viewer.LoadModel("does/not/exist.obj"); !errors.Is(err, ErrLoadFailed) {
+		t.Fatalf("missing model: got %v, want ErrLoadFailed", err)
+	}
+	if err := viewer.FrameModel(0, 0); !errors.Is(err, ErrInvalidArgument) {
+		t.Fatalf("framing an empty scene: got %v, want ErrInvalidArgument", err)
+	}
+	if err := viewer.SetRenderMode("bogus"); !errors.Is(err, ErrInvalidArgument) {
+		t.Fatalf("unknown mode: got %v, want ErrInvalidArgument", err)
+	}
+	if err := viewer.SetDirectionalLight(DirectionalLight{Intensity: 1}); !errors.Is(err, ErrInvalidArgument) {
+		t.Fatalf("zero light direction: got %v, want ErrInvalidArgument", err)
+	}
+}
@@ -1,10 +1,159 @@
+// maintained by hand, it must match the extern "C" functions and #[repr(C)] types in src/ffi.rs
+
 #include <stdarg.h>
 #include <stdbool.h>
 #include <stddef.h>
 #include <stdint.h>
 #include <stdlib.h>
 
+#define KANI_ERROR_BUFFER_TOO_SMALL -6
+
+#define KANI_ERROR_DEVICE -3
+
+#define KANI_ERROR_INVALID_ARGUMENT -2
+
+#define KANI_ERROR_LOAD_FAILED -4
+
+#define KANI_ERROR_NULL_POINTER -1
+
+#define KANI_ERROR_PANIC -7
+
+#define KANI_ERROR_RENDER_FAILED -5
+
+#define KANI_OK 0
+
+typedef struct KaniViewer KaniViewer;
+
+typedef struct KaniPick {
+  bool hit;
+  uint32_t model;
+  uint32_t mesh;
+  float distance;
+} KaniPick;
+
+/**
+ * message of the last failed call on this thread, NULL if there was none.
+ * valid until the next failing call on the same thread
+ */
+const char *kani_last_error(void);
+
+/**
+ * file_type is "default" or "opengl" (NULL means "default"), the viewer renders offscreen at width x height.
+ * on success *out_viewer is set to a viewer that must be freed with kani_viewer_destroy
+ */
+int32_t kani_viewer_create(const char *file_type,
+                           uint32_t width,
+                           uint32_t height,
+                           KaniViewer **out_viewer);
+
+/**
+ * draws into the wl_surface surface on the wl_display display, see kani_viewer_create_win32
+ */
+int32_t kani_viewer_create_wayland(void *display,
+                                   void *surface,
+                                   const char *file_type,
+                                   uint32_t width,
+                                   uint32_t height,
+                                   KaniViewer **out_viewer);
+
+/**
+ * draws into the win32 window hwnd (hinstance may be NULL), width x height is its client size.
+ * the window has to outlive the viewer, frames are drawn with kani_viewer_render_frame
+ */
+int32_t kani_viewer_create_win32(void *hwnd,
+                                 void *hinstance,
+                                 const char *file_type,
+                                 uint32_t width,
+                                 uint32_t height,
+                                 KaniViewer **out_viewer);
+
+/**
+ * draws into the x11 window on the xlib Display* display, see kani_viewer_create_win32
+ */
+int32_t kani_viewer_create_xlib(void *display,
+                                uint64_t window,
+                                const char *file_type,
+                                uint32_t width,
+                                uint32_t height,
+                                KaniViewer **out_viewer);
+
+/**
+ * NULL is ignored
+ */
+void kani_viewer_destroy(KaniViewer *viewer);
+
+/**
+ * points the camera at the model from far enough away that all of it is visible,
+ * azimuth around the y axis and elevation in degrees
+ */
+int32_t kani_viewer_frame_model(KaniViewer *viewer, float azimuth, float elevation);
+
+/**
+ * replaces the loaded model, blocks until it's uploaded. a failed load leaves the scene empty
+ */
+int32_t kani_viewer_load_model(KaniViewer *viewer, const char *path);
+
+/**
+ * closest mesh under pixel (x, y) of the rendered image, out_pick->hit is false when there is none
+ */
+int32_t kani_viewer_pick(KaniViewer *viewer, float x, float y, KaniPick *out_pick);
+
+/**
+ * renders one frame into pixels as tightly packed rgba8 rows from the top, len has to be
+ * at least width * height * 4 bytes
+ */
+int32_t kani_viewer_render(KaniViewer *viewer, uint8_t *pixels, size_t len);
+
+/**
+ * draws and presents one frame in the window of a viewer made with kani_viewer_create_win32,
+ * _xlib or _wayland. call kani_viewer_resize when the window changes size
+ */
+int32_t kani_viewer_render_frame(KaniViewer *viewer);
+
+int32_t kani_viewer_resize(KaniViewer *viewer, uint32_t width, uint32_t height);
+
+/**
+ * camera at (x, y, z) looking at (target_x, target_y, target_z)
+ */
+int32_t kani_viewer_set_camera(KaniViewer *viewer,
+                               float x,
+                               float y,
+                               float z,
+                               float target_x,
+                               float target_y,
+                               float target_z);
+
+/**
+ * direction the light shines in, doesn't have to be normalized
+ */
+int32_t kani_viewer_set_directional_light(KaniViewer *viewer,
+                                          float direction_x,
+                                          float direction_y,
+                                          float direction_z,
+                                          float r,
+                                          float g,
+                                          float b,
+                                          float intensity);
+
+int32_t kani_viewer_set_point_light(KaniViewer *viewer,
+                                    float x,
+                                    float y,
+                                    float z,
+                                    float r,
+                                    float g,
+                                    float b,
+                                    float range);
+
+/**
+ * unlit, lit, shadow, wireframe, debug, validation, normals, normal-map, uv, uv-checker or texel-density
+ */
+int32_t kani_viewer_set_render_mode(KaniViewer *viewer, const char *mode);
+
+int32_t kani_viewer_size(KaniViewer *viewer, uint32_t *out_width, uint32_t *out_height);
+
+int32_t kani_viewer_unload_model(KaniViewer *viewer);
 
 void run_kanirenderer(const char *file_path_cstring,
                       const char *file_type_c,
-                      const char *fs_mode_c);
+                      const char *fs_mode_c,
+                      const char *hdr_c);
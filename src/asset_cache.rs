@@ -0,0 +1,509 @@
+use std::fs::File;
+use std::hash::Hasher;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use anyhow::*;
+use memmap2::Mmap;
+use twox_hash::XxHash64;
+use crate::compressed_texture::{self, CompressedImage};
+use crate::{model, resources, texture};
+
+// bump when the file layout or the mesh/texture processing changes, old entries then just stop matching
+const CACHE_VERSION: u32 = 4;
+const MODEL_MAGIC: [u8; 8] = *b"KANIMDL\0";
+const TEXTURE_MAGIC: [u8; 8] = *b"KANITEX\0";
+
+// optional on disk cache of processed meshes and decoded textures, turned on by
+// pointing KANIRENDERER_CACHE_DIR at a directory. entries are named after a hash
+// of the source files and loader options so a changed file simply misses.
+// nothing gets cleaned up, delete the directory to drop old entries
+pub struct AssetCache {
+    dir: PathBuf,
+}
+
+// texture read back from the cache, the levels stay in the mapped file until upload
+pub struct CachedTexture {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    map: Mmap,
+    level_ranges: Vec<Range<usize>>,
+}
+
+impl CachedTexture {
+    pub fn levels(&self) -> Vec<&[u8]> {
+        self.level_ranges.iter().map(|range| &self.map[range.clone()]).collect()
+    }
+
+    pub fn to_compressed(&self) -> CompressedImage {
+        CompressedImage {
+            format: self.format,
+            width: self.width,
+            height: self.height,
+            levels: self.levels().into_iter().map(|level| level.to_vec()).collect(),
+        }
+    }
+}
+
+impl AssetCache {
+    pub fn from_env() -> Option<Self> {
+        let dir = std::env::var_os("KANIRENDERER_CACHE_DIR")?;
+        Some(Self { dir: PathBuf::from(dir) })
+    }
+
+    fn entry_path(&self, hash: u64, extension: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.{}", hash, extension))
+    }
+
+    // parsed obj and its finished meshes, None when there's no up to date entry.
+    // the returned ObjData has no tobj models, the meshes replace them
+    pub fn load_model(&self, file_name: &str, file_type: &str) -> Option<(resources::ObjData, Vec<resources::MeshData>)> {
+        let file_name = obj_file_name(file_name);
+        let source = std::fs::read(file_name).ok()?;
+        let path = self.entry_path(model_hash(&source, file_type), "kmdl");
+        let map = map_file(&path)?;
+        match read_model(&map, file_name) {
+            Result::Ok(Some(cached)) => {
+                println!("loaded {:?} from the asset cache", file_name);
+                Some(cached)
+            }
+            Result::Ok(None) => None,
+            Err(err) => {
+                println!("asset cache entry {:?} is invalid ({:?}), ignoring it", path, err);
+                None
+            }
+        }
+    }
+
+    pub fn store_model(&self, obj: &resources::ObjData, file_type: &str, meshes: &[resources::MeshData]) {
+        let Result::Ok(source) = std::fs::read(obj_file_name(&obj.file_name)) else {
+            return;
+        };
+        let path = self.entry_path(model_hash(&source, file_type), "kmdl");
+        if let Err(err) = self.write_entry(&path, &write_model(obj, meshes)) {
+            println!("could not write asset cache entry {:?} : {:?}", path, err);
+        }
+    }
+
+    // source is the raw file the key points at, the hash of it is the cache key
+    pub fn load_texture(&self, key: &texture::TextureKey, source: &[u8]) -> Option<texture::DecodedImage> {
+        let path = self.entry_path(texture_hash(key, source), "ktex");
+        let map = map_file(&path)?;
+        match read_texture(map) {
+            Result::Ok(cached) => Some(texture::DecodedImage {
+                label: key.path.to_string_lossy().to_string(),
+                data: texture::ImageData::Cached(cached),
+                is_normal_map: key.is_normal_map,
+            }),
+            Err(err) => {
+                println!("asset cache entry {:?} is invalid ({:?}), ignoring it", path, err);
+                None
+            }
+        }
+    }
+
+    pub fn store_texture(&self, key: &texture::TextureKey, source: &[u8], decoded: &texture::DecodedImage) {
+        //what basis turns into depends on the adapter, libktx is quick enough to run again
+        if matches!(decoded.data, texture::ImageData::Basis(_)) {
+            return;
+        }
+        let path = self.entry_path(texture_hash(key, source), "ktex");
+        let bytes = match write_texture(decoded) {
+            Result::Ok(bytes) => bytes,
+            Err(err) => {
+                println!("{} can't be stored in the asset cache : {:?}", decoded.label, err);
+                return;
+            }
+        };
+        if let Err(err) = self.write_entry(&path, &bytes) {
+            println!("could not write asset cache entry {:?} : {:?}", path, err);
+        }
+    }
+
+    // written next to the entry and renamed over it so a reader never maps half a file
+    fn write_entry(&self, path: &Path, bytes: &[u8]) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let temp_path = path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&temp_path, bytes)?;
+        if let Err(err) = std::fs::rename(&temp_path, path) {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+}
+
+// same default as parse_obj
+fn obj_file_name(file_name: &str) -> &str {
+    if file_name.is_empty() { "default_cube.obj" } else { file_name }
+}
+
+fn map_file(path: &Path) -> Option<Mmap> {
+    let file = File::open(path).ok()?;
+    //entries are only ever replaced by a rename, never written in place
+    unsafe { Mmap::map(&file) }.ok()
+}
+
+fn hash_bytes(hasher: &mut XxHash64, bytes: &[u8]) {
+    hasher.write(&(bytes.len() as u64).to_le_bytes());
+    hasher.write(bytes);
+}
+
+fn model_hash(source: &[u8], file_type: &str) -> u64 {
+    let mut hasher = XxHash64::with_seed(0);
+    hasher.write(&CACHE_VERSION.to_le_bytes());
+    hash_bytes(&mut hasher, source);
+    hash_bytes(&mut hasher, file_type.as_bytes());
+    hasher.finish()
+}
+
+fn texture_hash(key: &texture::TextureKey, source: &[u8]) -> u64 {
+    let mut hasher = XxHash64::with_seed(0);
+    hasher.write(&CACHE_VERSION.to_le_bytes());
+    hash_bytes(&mut hasher, source);
+    hasher.write(&[key.is_normal_map as u8, key.opengl as u8]);
+    hasher.finish()
+}
+
+// mtl files are found while parsing the obj, so their hashes live inside the entry.
+// a missing mtl hashes to 0, parse_obj falls back to the built in one for those
+fn file_hash(path: &Path) -> u64 {
+    match std::fs::read(path) {
+        Result::Ok(bytes) => {
+            let mut hasher = XxHash64::with_seed(0);
+            hash_bytes(&mut hasher, &bytes);
+            hasher.finish()
+        }
+        Err(_) => 0,
+    }
+}
+
+// the folder paths in the obj and mtl files are resolved against
+fn model_dir(file_name: &str) -> &Path {
+    Path::new(file_name).parent().unwrap_or(Path::new(""))
+}
+
+// model entry, all little endian:
+// magic, version, mtl count, (path, hash)*, material count, name*,
+// texture key count, (path, is_normal_map, opengl)*, missing texture count, path*, mesh count,
+// (name, material, vertex count, index count, vertices, indices)*
+// a path is a flag byte and a string, 1 when it's relative to the obj's folder
+fn write_model(obj: &resources::ObjData, meshes: &[resources::MeshData]) -> Vec<u8> {
+    let model_dir = model_dir(&obj.file_name);
+    let mut writer = Writer::default();
+    writer.bytes(&MODEL_MAGIC);
+    writer.u32(CACHE_VERSION);
+
+    writer.u32(obj.mtl_paths.len() as u32);
+    for path in &obj.mtl_paths {
+        writer.path(Some(model_dir), path);
+        writer.u64(file_hash(path));
+    }
+    writer.u32(obj.material_names.len() as u32);
+    for name in &obj.material_names {
+        writer.string(name);
+    }
+    writer.u32(obj.texture_keys.len() as u32);
+    for key in &obj.texture_keys {
+        //the fallback isn't a file next to anything
+        writer.path((!key.is_fallback()).then_some(model_dir), &key.path);
+        writer.bytes(&[key.is_normal_map as u8, key.opengl as u8]);
+    }
+    writer.u32(obj.missing_textures.len() as u32);
+    for path in &obj.missing_textures {
+        writer.path(Some(model_dir), path);
+    }
+    writer.u32(meshes.len() as u32);
+    for mesh in meshes {
+        writer.string(&mesh.name);
+        writer.u32(mesh.material as u32);
+        writer.u32(mesh.vertices.len() as u32);
+        writer.u32(mesh.indices.len() as u32);
+        writer.bytes(bytemuck::cast_slice(&mesh.vertices));
+        writer.bytes(bytemuck::cast_slice(&mesh.indices));
+    }
+    writer.data
+}
+
+// Ok(None) when an mtl file changed since the entry was written
+fn read_model(data: &[u8], file_name: &str) -> Result<Option<(resources::ObjData, Vec<resources::MeshData>)>> {
+    let model_dir = model_dir(file_name);
+    let mut reader = Reader { data, position: 0 };
+    if reader.bytes(MODEL_MAGIC.len())? != MODEL_MAGIC || reader.u32()? != CACHE_VERSION {
+        bail!("not a model cache entry");
+    }
+
+    let mut mtl_paths = Vec::new();
+    for _ in 0..reader.u32()? {
+        let path = reader.path(model_dir)?;
+        if file_hash(&path) != reader.u64()? {
+            return Ok(None);
+        }
+        mtl_paths.push(path);
+    }
+    let mut material_names = Vec::new();
+    for _ in 0..reader.u32()? {
+        material_names.push(reader.string()?);
+    }
+    let mut texture_keys = Vec::new();
+    for _ in 0..reader.u32()? {
+        let path = reader.path(model_dir)?;
+        let flags = reader.bytes(2)?;
+        texture_keys.push(texture::TextureKey { path, is_normal_map: flags[0] != 0, opengl: flags[1] != 0 });
+    }
+    let mut missing_textures = Vec::new();
+    for _ in 0..reader.u32()? {
+        missing_textures.push(reader.path(model_dir)?);
+    }
+
+    let vertex_size = std::mem::size_of::<model::ModelVertex>();
+    let mut meshes = Vec::new();
+    for _ in 0..reader.u32()? {
+        let name = reader.string()?;
+        let material = reader.u32()? as usize;
+        let vertex_count = reader.u32()? as usize;
+        let index_count = reader.u32()? as usize;
+        //the file offsets aren't aligned, copy out instead of casting in place
+        let vertices = bytemuck::pod_collect_to_vec(reader.bytes(vertex_count * vertex_size)?);
+        let indices = bytemuck::pod_collect_to_vec(reader.bytes(index_count * 4)?);
+        meshes.push(resources::MeshData { name, vertices, indices, material });
+    }
+
+    let obj = resources::ObjData {
+        file_name: file_name.to_string(),
+        models: Vec::new(),
+        material_names,
+        mtl_paths,
+        texture_keys,
+        missing_textures,
+    };
+    Ok(Some((obj, meshes)))
+}
+
+// texture entry: magic, version, vulkan format, width, height, level count,
+// (offset, size)* and then the level data. regular images are stored as rgba8 with the same
+// mip chain Texture::from_image builds
+fn write_texture(decoded: &texture::DecodedImage) -> Result<Vec<u8>> {
+    let (format, width, height, levels) = match &decoded.data {
+        texture::ImageData::Image(image) => {
+            let rgba = image.to_rgba8();
+            let format = if decoded.is_normal_map {
+                wgpu::TextureFormat::Rgba8Unorm
+            } else {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            };
+            (format, rgba.width(), rgba.height(), texture::mip_chain(&rgba))
+        }
+        texture::ImageData::Compressed(compressed) => {
+            (compressed.format, compressed.width, compressed.height, compressed.levels.clone())
+        }
+        texture::ImageData::Cached(_) => bail!("texture already comes from the cache"),
+        texture::ImageData::Basis(_) => bail!("basis textures are transcoded for the adapter on every load"),
+    };
+    let Some(vk_format) = compressed_texture::vk_format(format) else {
+        bail!("no vulkan format for {:?}", format);
+    };
+
+    let mut writer = Writer::default();
+    writer.bytes(&TEXTURE_MAGIC);
+    writer.u32(CACHE_VERSION);
+    writer.u32(vk_format);
+    writer.u32(width);
+    writer.u32(height);
+    writer.u32(levels.len() as u32);
+    let mut offset = (writer.data.len() + levels.len() * 16) as u64;
+    for level in &levels {
+        writer.u64(offset);
+        writer.u64(level.len() as u64);
+        offset += level.len() as u64;
+    }
+    for level in &levels {
+        writer.bytes(level);
+    }
+    Ok(writer.data)
+}
+
+fn read_texture(map: Mmap) -> Result<CachedTexture> {
+    let mut reader = Reader { data: &map, position: 0 };
+    if reader.bytes(TEXTURE_MAGIC.len())? != TEXTURE_MAGIC || reader.u32()? != CACHE_VERSION {
+        bail!("not a texture cache entry");
+    }
+    let format = compressed_texture::from_vk_format(reader.u32()?)?;
+    let width = reader.u32()?;
+    let height = reader.u32()?;
+    let level_count = reader.u32()?;
+    if level_count == 0 {
+        bail!("texture has no mip levels");
+    }
+
+    let mut level_ranges = Vec::new();
+    for level in 0..level_count {
+        let offset = reader.u64()? as usize;
+        let size = reader.u64()? as usize;
+        let expected = compressed_texture::level_byte_size(format, width, height, level);
+        if size < expected || offset.checked_add(size).map_or(true, |end| end > map.len()) {
+            bail!("mip level {} is out of bounds", level);
+        }
+        level_ranges.push(offset..offset + size);
+    }
+    Ok(CachedTexture { format, width, height, map, level_ranges })
+}
+
+#[derive(Default)]
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes(value.as_bytes());
+    }
+
+    // relative to the obj's folder when the path is inside it, so the entry still fits when the
+    // viewer runs from another directory
+    fn path(&mut self, model_dir: Option<&Path>, path: &Path) {
+        match model_dir.map(|model_dir| path.strip_prefix(model_dir)) {
+            Some(Result::Ok(relative)) => {
+                self.bytes(&[1]);
+                self.string(&relative.to_string_lossy());
+            }
+            _ => {
+                self.bytes(&[0]);
+                self.string(&path.to_string_lossy());
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.position.checked_add(len).filter(|&end| end <= self.data.len());
+        let Some(end) = end else {
+            bail!("unexpected end of file");
+        };
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into()?))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.bytes(len)?.to_vec())?)
+    }
+
+    fn path(&mut self, model_dir: &Path) -> Result<PathBuf> {
+        let relative = self.bytes(1)?[0] != 0;
+        let path = PathBuf::from(self.string()?);
+        Ok(if relative { model_dir.join(path) } else { path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32) -> model::ModelVertex {
+        model::ModelVertex { position: [x, 1.0, 2.0], tex_coords: [0.5, 0.25], normal: [0.0, 0.0, 1.0], tangent: [1.0, 0.0, 0.0], bitangent: [0.0, 1.0, 0.0] }
+    }
+
+    fn model_folder(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("kanirenderer_asset_cache").join(test);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn models_round_trip_from_another_folder() {
+        let dir = model_folder("models");
+        let first = dir.join("first");
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::write(first.join("crate.mtl"), "newmtl wood\nmap_Kd textures/wood.png\n").unwrap();
+        let file_name = first.join("crate.obj").to_string_lossy().to_string();
+        let obj = resources::ObjData {
+            file_name: file_name.clone(),
+            models: Vec::new(),
+            material_names: vec!["wood".to_string()],
+            mtl_paths: vec![first.join("crate.mtl")],
+            texture_keys: vec![
+                texture::TextureKey { path: first.join("textures").join("wood.png"), is_normal_map: false, opengl: true },
+                texture::TextureKey::fallback(true, true),
+            ],
+            missing_textures: vec![first.join("gone.png")],
+        };
+        let meshes = vec![resources::MeshData { name: "crate".to_string(), vertices: vec![vertex(0.0), vertex(1.0), vertex(2.0)], indices: vec![0, 1, 2], material: 0 }];
+        let entry = write_model(&obj, &meshes);
+
+        let (cached, cached_meshes) = read_model(&entry, &file_name).unwrap().unwrap();
+        assert_eq!(cached.mtl_paths, obj.mtl_paths);
+        assert_eq!(cached.material_names, obj.material_names);
+        assert_eq!(cached.texture_keys, obj.texture_keys);
+        assert_eq!(cached.missing_textures, obj.missing_textures);
+        assert_eq!(cached_meshes.len(), 1);
+        assert_eq!(cached_meshes[0].name, "crate");
+        assert_eq!(cached_meshes[0].indices, vec![0, 1, 2]);
+        assert_eq!(bytemuck::cast_slice::<_, u8>(&cached_meshes[0].vertices), bytemuck::cast_slice::<_, u8>(&meshes[0].vertices));
+
+        //the same files somewhere else resolve next to the obj that was loaded
+        let second = dir.join("second");
+        std::fs::rename(&first, &second).unwrap();
+        let moved_name = second.join("crate.obj").to_string_lossy().to_string();
+        let (moved, _) = read_model(&entry, &moved_name).unwrap().unwrap();
+        assert_eq!(moved.mtl_paths, vec![second.join("crate.mtl")]);
+        assert_eq!(moved.texture_keys[0].path, second.join("textures").join("wood.png"));
+        assert!(moved.texture_keys[1].is_fallback());
+        assert_eq!(moved.missing_textures, vec![second.join("gone.png")]);
+
+        //an edited mtl doesn't match anymore
+        std::fs::write(second.join("crate.mtl"), "newmtl stone\n").unwrap();
+        assert!(read_model(&entry, &moved_name).unwrap().is_none());
+    }
+
+    #[test]
+    fn images_are_stored_with_their_mip_chain() {
+        let dir = model_folder("textures");
+        let image = image::RgbaImage::from_fn(5, 3, |x, y| image::Rgba([x as u8 * 50, y as u8 * 100, 7, 255]));
+        let decoded = texture::DecodedImage {
+            label: "test".to_string(),
+            data: texture::ImageData::Image(image::DynamicImage::ImageRgba8(image.clone())),
+            is_normal_map: true,
+        };
+        let path = dir.join("entry.ktex");
+        std::fs::write(&path, write_texture(&decoded).unwrap()).unwrap();
+
+        let cached = read_texture(map_file(&path).unwrap()).unwrap();
+        assert_eq!((cached.format, cached.width, cached.height), (wgpu::TextureFormat::Rgba8Unorm, 5, 3));
+        let levels = cached.levels();
+        assert_eq!(levels.iter().map(|level| level.len()).collect::<Vec<_>>(), vec![5 * 3 * 4, 2 * 4, 4]);
+        assert_eq!(levels[0], image.as_raw().as_slice());
+        assert_eq!(levels, texture::mip_chain(&image).iter().map(|level| level.as_slice()).collect::<Vec<_>>());
+        //the first pixel of level 1 averages the top left 2x2 block
+        assert_eq!(&levels[1][..4], &[25, 50, 7, 255]);
+    }
+}
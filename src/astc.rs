@@ -0,0 +1,590 @@
+// software decoder for ldr astc blocks, used when the adapter has no astc support or the
+// texture size isn't a multiple of the block size. follows the decoding steps of the khronos
+// data format spec, blocks that are invalid or use hdr endpoints decode to magenta
+
+const ERROR_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+pub fn can_decompress(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::Astc { channel: wgpu::AstcChannel::Unorm | wgpu::AstcChannel::UnormSrgb, .. }
+    )
+}
+
+// decode one block into row major rgba8 pixels
+pub fn decode_block(data: &[u8], block_width: usize, block_height: usize, srgb: bool, out: &mut [u8]) {
+    let block = u128::from_le_bytes(data[0..16].try_into().unwrap());
+    match decode_texels(block, block_width, block_height, srgb) {
+        Some(texels) => {
+            for (texel, color) in texels.iter().enumerate() {
+                out[texel * 4..texel * 4 + 4].copy_from_slice(color);
+            }
+        }
+        None => {
+            for texel in out.chunks_exact_mut(4).take(block_width * block_height) {
+                texel.copy_from_slice(&ERROR_COLOR);
+            }
+        }
+    }
+}
+
+fn bits(block: u128, start: usize, count: usize) -> u32 {
+    if count == 0 || start >= 128 {
+        return 0;
+    }
+    ((block >> start) & ((1u128 << count) - 1)) as u32
+}
+
+// repeat the bits of value until it's `to` bits wide
+fn replicate(value: u32, count: u32, to: u32) -> u32 {
+    if count == 0 {
+        return 0;
+    }
+    let mut result = 0;
+    let mut filled = 0;
+    while filled < to {
+        result = (result << count) | value;
+        filled += count;
+    }
+    result >> (filled - to)
+}
+
+struct BlockMode {
+    weights_wide: usize,
+    weights_high: usize,
+    weight_levels: u32,
+    dual_plane: bool,
+}
+
+// the quantization levels in order, weights use the first 12
+const LEVELS: [u32; 21] = [2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32, 40, 48, 64, 80, 96, 128, 160, 192, 256];
+
+// (trits or quints, bits) of an integer sequence with this many levels, 3 is trits and 5 quints
+fn ise_layout(levels: u32) -> (u32, u32) {
+    match levels {
+        3 => (3, 0),
+        5 => (5, 0),
+        6 => (3, 1),
+        10 => (5, 1),
+        12 => (3, 2),
+        20 => (5, 2),
+        24 => (3, 3),
+        40 => (5, 3),
+        48 => (3, 4),
+        80 => (5, 4),
+        96 => (3, 5),
+        160 => (5, 5),
+        192 => (3, 6),
+        _ => (1, levels.trailing_zeros()),
+    }
+}
+
+fn ise_size(count: usize, levels: u32) -> usize {
+    let (kind, bit_count) = ise_layout(levels);
+    count * bit_count as usize
+        + match kind {
+            3 => (8 * count + 4) / 5,
+            5 => (7 * count + 2) / 3,
+            _ => 0,
+        }
+}
+
+fn block_mode(mode: u32) -> Option<BlockMode> {
+    let bit = |n: u32| (mode >> n) & 1;
+    let a = ((mode >> 5) & 3) as usize;
+    let mut high_precision = bit(9) == 1;
+    let mut dual_plane = bit(10) == 1;
+    let mut range = bit(4);
+    let (weights_wide, weights_high);
+    if mode & 3 != 0 {
+        range |= (mode & 3) << 1;
+        let b = ((mode >> 7) & 3) as usize;
+        match (mode >> 2) & 3 {
+            0 => (weights_wide, weights_high) = (b + 4, a + 2),
+            1 => (weights_wide, weights_high) = (b + 8, a + 2),
+            2 => (weights_wide, weights_high) = (a + 2, b + 8),
+            _ if bit(8) == 1 => (weights_wide, weights_high) = ((b & 1) + 2, a + 2),
+            _ => (weights_wide, weights_high) = (a + 2, (b & 1) + 6),
+        }
+    } else {
+        range |= ((mode >> 2) & 3) << 1;
+        if (mode >> 2) & 3 == 0 {
+            return None;
+        }
+        let b = ((mode >> 9) & 3) as usize;
+        match (mode >> 7) & 3 {
+            0 => (weights_wide, weights_high) = (12, a + 2),
+            1 => (weights_wide, weights_high) = (a + 2, 12),
+            2 => {
+                (weights_wide, weights_high) = (a + 6, b + 6);
+                high_precision = false;
+                dual_plane = false;
+            }
+            _ => match a {
+                0 => (weights_wide, weights_high) = (6, 10),
+                1 => (weights_wide, weights_high) = (10, 6),
+                _ => return None,
+            },
+        }
+    }
+
+    let weight_levels = LEVELS[(range - 2 + 6 * high_precision as u32) as usize];
+    let weight_count = weights_wide * weights_high * (1 + dual_plane as usize);
+    let weight_bits = ise_size(weight_count, weight_levels);
+    if weight_count > 64 || !(24..=96).contains(&weight_bits) {
+        return None;
+    }
+    Some(BlockMode { weights_wide, weights_high, weight_levels, dual_plane })
+}
+
+fn decode_trits(t: u32) -> [u32; 5] {
+    let bit = |value: u32, n: u32| (value >> n) & 1;
+    let (c, t4, t3);
+    if (t >> 2) & 7 == 7 {
+        c = (((t >> 5) & 7) << 2) | (t & 3);
+        (t4, t3) = (2, 2);
+    } else {
+        c = t & 0x1F;
+        if (t >> 5) & 3 == 3 {
+            (t4, t3) = (2, bit(t, 7));
+        } else {
+            (t4, t3) = (bit(t, 7), (t >> 5) & 3);
+        }
+    }
+    let (t2, t1, t0);
+    if c & 3 == 3 {
+        (t2, t1, t0) = (2, bit(c, 4), (bit(c, 3) << 1) | (bit(c, 2) & !bit(c, 3) & 1));
+    } else if (c >> 2) & 3 == 3 {
+        (t2, t1, t0) = (2, 2, c & 3);
+    } else {
+        (t2, t1, t0) = (bit(c, 4), (c >> 2) & 3, (bit(c, 1) << 1) | (bit(c, 0) & !bit(c, 1) & 1));
+    }
+    [t0, t1, t2, t3, t4]
+}
+
+fn decode_quints(q: u32) -> [u32; 3] {
+    let bit = |n: u32| (q >> n) & 1;
+    if (q >> 1) & 3 == 3 && (q >> 5) & 3 == 0 {
+        let q2 = (bit(0) << 2) | ((bit(4) & !bit(0) & 1) << 1) | (bit(3) & !bit(0) & 1);
+        return [4, 4, q2];
+    }
+    let (q2, c) = if (q >> 1) & 3 == 3 {
+        (4, (((q >> 3) & 3) << 3) | ((!(q >> 5) & 3) << 1) | bit(0))
+    } else {
+        ((q >> 5) & 3, q & 0x1F)
+    };
+    if c & 7 == 5 {
+        [(c >> 3) & 3, 4, q2]
+    } else {
+        [c & 7, (c >> 3) & 3, q2]
+    }
+}
+
+// read `count` integers of an integer sequence encoding starting at bit `start`
+fn decode_ise(block: u128, start: usize, count: usize, levels: u32) -> Vec<u32> {
+    let (kind, bit_count) = ise_layout(levels);
+    let bit_count = bit_count as usize;
+    let mut values = Vec::with_capacity(count);
+    let mut position = start;
+    let mut read = |size: usize| {
+        let value = bits(block, position, size);
+        position += size;
+        value
+    };
+
+    //trit and quint packs interleave their packed bits with the plain bits of each value
+    let (group, packed_sizes): (usize, &[usize]) = match kind {
+        3 => (5, &[2, 2, 1, 2, 1]),
+        5 => (3, &[3, 2, 2]),
+        _ => (1, &[0]),
+    };
+    while values.len() < count {
+        let in_group = group.min(count - values.len());
+        let mut low = [0u32; 5];
+        let mut packed = 0;
+        let mut shift = 0;
+        for (i, size) in packed_sizes.iter().enumerate().take(in_group) {
+            low[i] = read(bit_count);
+            packed |= read(*size) << shift;
+            shift += size;
+        }
+        let high = match kind {
+            3 => decode_trits(packed).to_vec(),
+            5 => decode_quints(packed).to_vec(),
+            _ => vec![0],
+        };
+        for i in 0..in_group {
+            values.push((high[i] << bit_count) | low[i]);
+        }
+    }
+    values
+}
+
+// spread the packed bits of a trit or quint value back over the 0..255 range
+fn unquantize_color(value: u32, levels: u32) -> i32 {
+    let (kind, bit_count) = ise_layout(levels);
+    if kind == 1 {
+        return replicate(value, bit_count, 8) as i32;
+    }
+    let high = value >> bit_count;
+    let low = value & ((1 << bit_count) - 1);
+    let a = if low & 1 == 1 { 0x1FF } else { 0 };
+    let rest = low >> 1;
+    let (b, c) = match (kind, bit_count) {
+        (3, 1) => (0, 204),
+        (5, 1) => (0, 113),
+        (3, 2) => (rest * 0x116, 93),
+        (5, 2) => (rest * 0x10C, 54),
+        (3, 3) => ((rest << 7) | (rest << 2) | rest, 44),
+        (5, 3) => ((rest << 7) | (rest << 1) | (rest >> 1), 26),
+        (3, 4) => ((rest << 6) | rest, 22),
+        (5, 4) => ((rest << 6) | (rest >> 1), 13),
+        (3, 5) => ((rest << 5) | (rest >> 2), 11),
+        (5, 5) => ((rest << 5) | (rest >> 3), 6),
+        _ => ((rest << 4) | (rest >> 4), 5),
+    };
+    let t = (high * c + b) ^ a;
+    ((a & 0x80) | (t >> 2)) as i32
+}
+
+// weights end up in 0..=64
+fn unquantize_weight(value: u32, levels: u32) -> u32 {
+    let (kind, bit_count) = ise_layout(levels);
+    let weight = if kind == 1 {
+        replicate(value, bit_count, 6)
+    } else if bit_count == 0 {
+        if kind == 3 { [0, 32, 63][value as usize] } else { [0, 16, 32, 47, 63][value as usize] }
+    } else {
+        let high = value >> bit_count;
+        let low = value & ((1 << bit_count) - 1);
+        let a = if low & 1 == 1 { 0x7F } else { 0 };
+        let rest = low >> 1;
+        let (b, c) = match (kind, bit_count) {
+            (3, 1) => (0, 50),
+            (5, 1) => (0, 28),
+            (3, 2) => (rest * 0x45, 23),
+            (5, 2) => (rest * 0x42, 13),
+            _ => ((rest << 5) | rest, 11),
+        };
+        let t = (high * c + b) ^ a;
+        (a & 0x20) | (t >> 2)
+    };
+    if weight > 32 { weight + 1 } else { weight }
+}
+
+// moves one bit from the base into the offset and turns the offset into a signed value
+fn bit_transfer_signed(offset: &mut i32, base: &mut i32) {
+    *base >>= 1;
+    *base |= *offset & 0x80;
+    *offset >>= 1;
+    *offset &= 0x3F;
+    if *offset & 0x20 != 0 {
+        *offset -= 0x40;
+    }
+}
+
+fn blue_contract(r: i32, g: i32, b: i32, a: i32) -> [i32; 4] {
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+fn clamp_color(color: [i32; 4]) -> [i32; 4] {
+    color.map(|c| c.clamp(0, 255))
+}
+
+// the two endpoint colors of a partition, None for the hdr modes
+fn decode_endpoints(mode: u32, v: &[i32]) -> Option<([i32; 4], [i32; 4])> {
+    let endpoints = match mode {
+        0 => ([v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]),
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xC0);
+            let l1 = (l0 + (v[1] & 0x3F)).min(255);
+            ([l0, l0, l0, 255], [l1, l1, l1, 255])
+        }
+        4 => ([v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]),
+        5 => {
+            let (mut v0, mut v1, mut v2, mut v3) = (v[0], v[1], v[2], v[3]);
+            bit_transfer_signed(&mut v1, &mut v0);
+            bit_transfer_signed(&mut v3, &mut v2);
+            let e1 = v0 + v1;
+            (clamp_color([v0, v0, v0, v2]), clamp_color([e1, e1, e1, v2 + v3]))
+        }
+        6 => (
+            [(v[0] * v[3]) >> 8, (v[1] * v[3]) >> 8, (v[2] * v[3]) >> 8, 255],
+            [v[0], v[1], v[2], 255],
+        ),
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 { (v[6], v[7]) } else { (255, 255) };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                ([v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1])
+            } else {
+                (blue_contract(v[1], v[3], v[5], a1), blue_contract(v[0], v[2], v[4], a0))
+            }
+        }
+        9 | 13 => {
+            let mut v: Vec<i32> = v[..if mode == 13 { 8 } else { 6 }].to_vec();
+            if mode == 9 {
+                v.extend([255, 0]);
+            }
+            for pair in v.chunks_exact_mut(2).take(if mode == 13 { 4 } else { 3 }) {
+                let (base, offset) = pair.split_at_mut(1);
+                bit_transfer_signed(&mut offset[0], &mut base[0]);
+            }
+            let (e0, e1) = if v[1] + v[3] + v[5] >= 0 {
+                ([v[0], v[2], v[4], v[6]], [v[0] + v[1], v[2] + v[3], v[4] + v[5], v[6] + v[7]])
+            } else {
+                (
+                    blue_contract(v[0] + v[1], v[2] + v[3], v[4] + v[5], v[6] + v[7]),
+                    blue_contract(v[0], v[2], v[4], v[6]),
+                )
+            };
+            (clamp_color(e0), clamp_color(e1))
+        }
+        10 => (
+            [(v[0] * v[3]) >> 8, (v[1] * v[3]) >> 8, (v[2] * v[3]) >> 8, v[4]],
+            [v[0], v[1], v[2], v[5]],
+        ),
+        _ => return None,
+    };
+    Some(endpoints)
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, small_block: bool) -> usize {
+    let (x, y) = if small_block { (x << 1, y << 1) } else { (x, y) };
+    let seed = seed + (partitions - 1) * 1024;
+    let rnum = hash52(seed);
+    let mut seeds = [0u32; 8];
+    for (i, s) in seeds.iter_mut().enumerate() {
+        let value = (rnum >> (4 * i)) & 0xF;
+        *s = value * value;
+    }
+    let (sh1, sh2) = if seed & 1 == 1 {
+        (if seed & 2 != 0 { 4 } else { 5 }, if partitions == 3 { 6 } else { 5 })
+    } else {
+        (if partitions == 3 { 6 } else { 5 }, if seed & 2 != 0 { 4 } else { 5 })
+    };
+    let shifts = [sh1, sh2, sh1, sh2, sh1, sh2, sh1, sh2];
+    for (s, shift) in seeds.iter_mut().zip(shifts) {
+        *s >>= shift;
+    }
+
+    //the z seeds only matter for 3d textures
+    let a = (seeds[0] * x + seeds[1] * y + (rnum >> 14)) & 0x3F;
+    let b = (seeds[2] * x + seeds[3] * y + (rnum >> 10)) & 0x3F;
+    let c = if partitions >= 3 { (seeds[4] * x + seeds[5] * y + (rnum >> 6)) & 0x3F } else { 0 };
+    let d = if partitions >= 4 { (seeds[6] * x + seeds[7] * y + (rnum >> 2)) & 0x3F } else { 0 };
+
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+// bilinear infill of the weight grid to one weight per texel
+fn infill_weights(grid: &[u32], weights_wide: usize, weights_high: usize, block_width: usize, block_height: usize) -> Vec<u32> {
+    let ds = (1024 + block_width / 2) / (block_width - 1);
+    let dt = (1024 + block_height / 2) / (block_height - 1);
+    let weight = |index: usize| grid.get(index).copied().unwrap_or(0);
+    let mut texels = Vec::with_capacity(block_width * block_height);
+    for t in 0..block_height {
+        for s in 0..block_width {
+            let gs = ((ds * s) * (weights_wide - 1) + 32) >> 6;
+            let gt = ((dt * t) * (weights_high - 1) + 32) >> 6;
+            let (js, fs) = (gs >> 4, (gs & 0xF) as u32);
+            let (jt, ft) = (gt >> 4, (gt & 0xF) as u32);
+            let w11 = (fs * ft + 8) >> 4;
+            let w10 = ft - w11;
+            let w01 = fs - w11;
+            let w00 = 16 + w11 - fs - ft;
+            let v0 = js + jt * weights_wide;
+            texels.push(
+                (weight(v0) * w00 + weight(v0 + 1) * w01 + weight(v0 + weights_wide) * w10 + weight(v0 + weights_wide + 1) * w11 + 8) >> 4,
+            );
+        }
+    }
+    texels
+}
+
+fn decode_void_extent(block: u128, texels: usize) -> Option<Vec<[u8; 4]>> {
+    //hdr void extents are errors in ldr mode
+    if bits(block, 9, 1) == 1 {
+        return None;
+    }
+    let (min_s, max_s) = (bits(block, 12, 13), bits(block, 25, 13));
+    let (min_t, max_t) = (bits(block, 38, 13), bits(block, 51, 13));
+    let all_ones = min_s == 0x1FFF && max_s == 0x1FFF && min_t == 0x1FFF && max_t == 0x1FFF;
+    if !all_ones && (min_s >= max_s || min_t >= max_t) {
+        return None;
+    }
+    let color = [0, 1, 2, 3].map(|channel| (bits(block, 64 + 16 * channel, 16) >> 8) as u8);
+    Some(vec![color; texels])
+}
+
+fn decode_texels(block: u128, block_width: usize, block_height: usize, srgb: bool) -> Option<Vec<[u8; 4]>> {
+    let texel_count = block_width * block_height;
+    if bits(block, 0, 9) == 0x1FC {
+        return decode_void_extent(block, texel_count);
+    }
+    let mode = block_mode(bits(block, 0, 11))?;
+    if mode.weights_wide > block_width || mode.weights_high > block_height {
+        return None;
+    }
+    let partitions = bits(block, 11, 2) + 1;
+    if partitions == 4 && mode.dual_plane {
+        return None;
+    }
+
+    let weight_count = mode.weights_wide * mode.weights_high * (1 + mode.dual_plane as usize);
+    let mut below_weights = 128 - ise_size(weight_count, mode.weight_levels);
+    let mut endpoint_modes = [0u32; 4];
+    let mut partition_seed = 0;
+    let color_start = if partitions == 1 {
+        endpoint_modes[0] = bits(block, 13, 4);
+        17
+    } else {
+        partition_seed = bits(block, 13, 10);
+        let field = bits(block, 23, 6);
+        if field & 3 == 0 {
+            endpoint_modes = [field >> 2; 4];
+        } else {
+            //the rest of the mode bits sit just below the weights
+            let extra = 3 * partitions as usize - 4;
+            below_weights -= extra;
+            let encoded = field | (bits(block, below_weights, extra) << 6);
+            let base_class = (field & 3) - 1;
+            for (i, mode) in endpoint_modes.iter_mut().enumerate().take(partitions as usize) {
+                let class = (encoded >> (2 + i)) & 1;
+                let low = (encoded >> (2 + partitions as usize + 2 * i)) & 3;
+                *mode = ((base_class + class) << 2) | low;
+            }
+        }
+        29
+    };
+    let mut second_plane_channel = None;
+    if mode.dual_plane {
+        below_weights -= 2;
+        second_plane_channel = Some(bits(block, below_weights, 2) as usize);
+    }
+
+    let modes = &endpoint_modes[..partitions as usize];
+    let value_count: usize = modes.iter().map(|mode| ((mode >> 2) as usize + 1) * 2).sum();
+    if value_count > 18 || below_weights <= color_start {
+        return None;
+    }
+    let color_bits = below_weights - color_start;
+    let color_levels = *LEVELS.iter().rev().find(|&&levels| ise_size(value_count, levels) <= color_bits)?;
+    if color_levels < 6 {
+        return None;
+    }
+    let values: Vec<i32> = decode_ise(block, color_start, value_count, color_levels)
+        .into_iter()
+        .map(|value| unquantize_color(value, color_levels))
+        .collect();
+    let mut endpoints = Vec::with_capacity(partitions as usize);
+    let mut offset = 0;
+    for &mode in modes {
+        endpoints.push(decode_endpoints(mode, &values[offset..])?);
+        offset += ((mode >> 2) as usize + 1) * 2;
+    }
+
+    //weights are stored bit reversed from the top of the block
+    let weights: Vec<u32> = decode_ise(block.reverse_bits(), 0, weight_count, mode.weight_levels)
+        .into_iter()
+        .map(|value| unquantize_weight(value, mode.weight_levels))
+        .collect();
+    let planes = 1 + mode.dual_plane as usize;
+    let plane_weights: Vec<Vec<u32>> = (0..planes)
+        .map(|plane| {
+            let grid: Vec<u32> = weights.iter().skip(plane).step_by(planes).copied().collect();
+            infill_weights(&grid, mode.weights_wide, mode.weights_high, block_width, block_height)
+        })
+        .collect();
+
+    let mut texels = Vec::with_capacity(texel_count);
+    for y in 0..block_height {
+        for x in 0..block_width {
+            let texel = y * block_width + x;
+            let partition = if partitions > 1 {
+                select_partition(partition_seed, x as u32, y as u32, partitions, texel_count < 31)
+            } else {
+                0
+            };
+            let (e0, e1) = endpoints[partition];
+            let mut color = [0u8; 4];
+            for channel in 0..4 {
+                let plane = if second_plane_channel == Some(channel) { 1 } else { 0 };
+                let weight = plane_weights[plane][texel] as i32;
+                let (c0, c1) = if srgb {
+                    ((e0[channel] << 8) | 0x80, (e1[channel] << 8) | 0x80)
+                } else {
+                    ((e0[channel] << 8) | e0[channel], (e1[channel] << 8) | e1[channel])
+                };
+                color[channel] = (((c0 * (64 - weight) + c1 * weight + 32) >> 6) >> 8) as u8;
+            }
+            texels.push(color);
+        }
+    }
+    Some(texels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(data: &[u8], srgb: bool) -> Vec<[u8; 4]> {
+        let mut out = [0u8; 64];
+        decode_block(data, 4, 4, srgb, &mut out);
+        out.chunks_exact(4).map(|pixel| pixel.try_into().unwrap()).collect()
+    }
+
+    #[test]
+    fn void_extent() {
+        //unorm16 color ffff, 8000, 0000, ffff
+        let mut data = [0xFC, 0xFD, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x80, 0x00, 0x00, 0xFF, 0xFF];
+        assert!(decode(&data, false).iter().all(|texel| *texel == [255, 128, 0, 255]));
+
+        //the hdr flag is an error in ldr mode
+        data[1] |= 0x02;
+        assert!(decode(&data, false).iter().all(|texel| *texel == ERROR_COLOR));
+    }
+
+    #[test]
+    fn single_partition_rgb() {
+        //4x4 grid of 2 bit weights, rgb direct endpoints (10, 20, 30) and (200, 100, 50),
+        //texel 0 has weight 3 and texel 2 weight 1, the rest 0
+        let data = [0x42, 0x00, 0x15, 0x90, 0x29, 0xC8, 0x3C, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC8];
+        let block = decode(&data, false);
+        assert_eq!(block[0], [200, 100, 50, 255]);
+        assert_eq!(block[1], [10, 20, 30, 255]);
+        assert_eq!(block[2], [72, 46, 36, 255]);
+        assert_eq!(block[15], [10, 20, 30, 255]);
+
+        //srgb endpoints are expanded with 0x80 instead of replicated
+        let block = decode(&data, true);
+        assert_eq!(block[0], [200, 100, 50, 255]);
+        assert_eq!(block[1], [10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn reserved_block_mode_is_an_error() {
+        assert!(decode(&[0u8; 16], false).iter().all(|texel| *texel == ERROR_COLOR));
+    }
+}
@@ -0,0 +1,237 @@
+// basis universal ktx2 textures (etc1s and uastc) are transcoded with libktx from KTX-Software.
+// the library is loaded when the first basis texture shows up so the viewer runs fine without it,
+// KANIRENDERER_LIBKTX can point at it when it isn't installed where the system looks for libraries
+
+use anyhow::*;
+use std::ffi::{c_char, c_void, CStr};
+use std::sync::OnceLock;
+use crate::compressed_texture::{self, CompressedImage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Etc1s,
+    Uastc,
+}
+
+// the file as it is, transcoded once the adapter's features are known
+pub struct BasisImage {
+    pub bytes: Vec<u8>,
+    pub codec: Codec,
+    pub has_alpha: bool,
+    pub width: u32,
+    pub height: u32,
+    pub level_count: u32,
+}
+
+impl BasisImage {
+    // None for ktx2 files with a regular vulkan format and for anything that isn't ktx2
+    pub fn from_bytes(bytes: &[u8]) -> Result<Option<Self>> {
+        let Result::Ok(reader) = ktx2::Reader::new(bytes) else {
+            return Ok(None);
+        };
+        let header = reader.header();
+        if header.format.is_some() {
+            return Ok(None);
+        }
+        let Some(descriptor) = reader.data_format_descriptors().find(|descriptor| descriptor.header == ktx2::DataFormatDescriptorHeader::BASIC) else {
+            bail!("ktx2 without a vulkan format needs a basic data format descriptor");
+        };
+        let basic = ktx2::BasicDataFormatDescriptor::parse(descriptor.data).map_err(|err| anyhow!("invalid ktx2 data format descriptor: {:?}", err))?;
+        //etc1s keeps alpha in a second slice (channel AAA), uastc says RGBA or RRRG in its only sample
+        let channels = basic.sample_information().map(|sample| sample.channel_type).collect::<Vec<_>>();
+        let (codec, has_alpha) = match basic.color_model {
+            Some(ktx2::ColorModel::ETC1S) => (Codec::Etc1s, channels.contains(&15)),
+            Some(ktx2::ColorModel::UASTC) => (Codec::Uastc, channels.iter().any(|channel| *channel == 3 || *channel == 5)),
+            model => bail!("ktx2 without a vulkan format has to be basis universal, the color model is {:?}", model),
+        };
+        if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+            bail!("ktx2 3d, array and cubemap textures are not supported");
+        }
+        Ok(Some(Self {
+            bytes: bytes.to_vec(),
+            codec,
+            has_alpha,
+            width: header.pixel_width,
+            height: header.pixel_height.max(1),
+            level_count: header.level_count.max(1),
+        }))
+    }
+
+    // into the block format the adapter samples that loses the least, see target_format
+    pub fn transcode(&self, features: wgpu::Features) -> Result<CompressedImage> {
+        let (target, format) = target_format(self.codec, self.has_alpha, self.width, self.height, features);
+        let libktx = libktx()?;
+        let texture = KtxTexture::from_memory(libktx, &self.bytes)?;
+        libktx.check(unsafe { (libktx.transcode_basis)(texture.0, target, 0) })?;
+
+        let data = unsafe {
+            let data = (libktx.get_data)(texture.0);
+            if data.is_null() {
+                bail!("libktx returned no image data");
+            }
+            std::slice::from_raw_parts(data, (libktx.get_data_size)(texture.0))
+        };
+        let mut levels = Vec::with_capacity(self.level_count as usize);
+        for level in 0..self.level_count {
+            let mut offset = 0;
+            libktx.check(unsafe { (libktx.get_image_offset)(texture.0, level, 0, 0, &mut offset) })?;
+            let size = compressed_texture::level_byte_size(format, self.width, self.height, level);
+            match data.get(offset..offset + size) {
+                Some(level) => levels.push(level.to_vec()),
+                None => bail!("transcoded mip level {} is outside the image data", level),
+            }
+        }
+        Ok(CompressedImage { format, width: self.width, height: self.height, levels })
+    }
+}
+
+const KTX_TTF_ETC1_RGB: u32 = 0;
+const KTX_TTF_ETC2_RGBA: u32 = 1;
+const KTX_TTF_BC7_RGBA: u32 = 6;
+const KTX_TTF_ASTC_4X4_RGBA: u32 = 10;
+const KTX_TTF_RGBA32: u32 = 13;
+
+// uastc is a subset of astc 4x4 and goes to bc7 almost as well, etc1s is etc1 underneath so etc2 is
+// lossless for it. rgba8 when the adapter samples none of them or the size isn't a multiple of 4
+fn target_format(codec: Codec, has_alpha: bool, width: u32, height: u32, features: wgpu::Features) -> (u32, wgpu::TextureFormat) {
+    use wgpu::TextureFormat::*;
+    let astc = (KTX_TTF_ASTC_4X4_RGBA, Astc { block: wgpu::AstcBlock::B4x4, channel: wgpu::AstcChannel::Unorm });
+    let bc7 = (KTX_TTF_BC7_RGBA, Bc7RgbaUnorm);
+    let etc = if has_alpha { (KTX_TTF_ETC2_RGBA, Etc2Rgba8Unorm) } else { (KTX_TTF_ETC1_RGB, Etc2Rgb8Unorm) };
+    let candidates = match codec {
+        Codec::Uastc => [astc, bc7, etc],
+        Codec::Etc1s => [etc, bc7, astc],
+    };
+    candidates
+        .into_iter()
+        .find(|(_, format)| compressed_texture::is_format_supported(*format, width, height, features))
+        .unwrap_or((KTX_TTF_RGBA32, Rgba8Unorm))
+}
+
+const KTX_TEXTURE_CREATE_LOAD_IMAGE_DATA_BIT: u32 = 1;
+
+// the handful of functions we need from ktx.h, every ktxTexture2 is passed as an opaque pointer
+struct Libktx {
+    _library: libloading::Library,
+    create_from_memory: unsafe extern "system" fn(*const u8, usize, u32, *mut *mut c_void) -> i32,
+    destroy: unsafe extern "system" fn(*mut c_void),
+    transcode_basis: unsafe extern "system" fn(*mut c_void, u32, u32) -> i32,
+    get_image_offset: unsafe extern "system" fn(*mut c_void, u32, u32, u32, *mut usize) -> i32,
+    get_data: unsafe extern "system" fn(*mut c_void) -> *const u8,
+    get_data_size: unsafe extern "system" fn(*mut c_void) -> usize,
+    error_string: unsafe extern "system" fn(i32) -> *const c_char,
+}
+
+impl Libktx {
+    fn load() -> Result<Self> {
+        let names = match std::env::var("KANIRENDERER_LIBKTX") {
+            Result::Ok(path) => vec![path],
+            Err(_) if cfg!(windows) => vec!["ktx.dll".to_string()],
+            Err(_) if cfg!(target_os = "macos") => vec!["libktx.dylib".to_string(), "libktx.4.dylib".to_string()],
+            Err(_) => vec!["libktx.so.4".to_string(), "libktx.so".to_string()],
+        };
+        let library = names
+            .iter()
+            .find_map(|name| unsafe { libloading::Library::new(name) }.ok())
+            .ok_or_else(|| anyhow!("basis universal textures need libktx from KTX-Software, tried {}, set KANIRENDERER_LIBKTX to its path", names.join(", ")))?;
+        unsafe {
+            Ok(Self {
+                create_from_memory: *library.get(b"ktxTexture2_CreateFromMemory\0")?,
+                destroy: *library.get(b"ktxTexture2_Destroy\0")?,
+                transcode_basis: *library.get(b"ktxTexture2_TranscodeBasis\0")?,
+                get_image_offset: *library.get(b"ktxTexture2_GetImageOffset\0")?,
+                get_data: *library.get(b"ktxTexture_GetData\0")?,
+                get_data_size: *library.get(b"ktxTexture_GetDataSize\0")?,
+                error_string: *library.get(b"ktxErrorString\0")?,
+                _library: library,
+            })
+        }
+    }
+
+    fn check(&self, code: i32) -> Result<()> {
+        if code == 0 {
+            return Ok(());
+        }
+        let message = unsafe { CStr::from_ptr((self.error_string)(code)) };
+        Err(anyhow!("libktx: {}", message.to_string_lossy()))
+    }
+}
+
+// loaded once, a missing library is reported for every basis texture
+fn libktx() -> Result<&'static Libktx> {
+    static LIBKTX: OnceLock<std::result::Result<Libktx, String>> = OnceLock::new();
+    LIBKTX.get_or_init(|| Libktx::load().map_err(|err| format!("{:#}", err))).as_ref().map_err(|err| anyhow!("{}", err))
+}
+
+struct KtxTexture<'a>(*mut c_void, &'a Libktx);
+
+impl<'a> KtxTexture<'a> {
+    fn from_memory(libktx: &'a Libktx, bytes: &[u8]) -> Result<Self> {
+        let mut texture = std::ptr::null_mut();
+        libktx.check(unsafe { (libktx.create_from_memory)(bytes.as_ptr(), bytes.len(), KTX_TEXTURE_CREATE_LOAD_IMAGE_DATA_BIT, &mut texture) })?;
+        Ok(Self(texture, libktx))
+    }
+}
+
+impl Drop for KtxTexture<'_> {
+    fn drop(&mut self) {
+        unsafe { (self.1.destroy)(self.0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a ktx2 header, one level index entry and a basic descriptor with the given samples
+    fn ktx2_file(color_model: u32, channels: &[u32]) -> Vec<u8> {
+        let dfd_block_size = 24 + 16 * channels.len() as u32;
+        let dfd_offset = 80 + 24;
+        let data_offset = dfd_offset + 4 + dfd_block_size;
+        let mut bytes = vec![0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+        let scheme = if color_model == 163 { 1 } else { 0 };
+        for value in [0, 1, 8, 8, 0, 0, 1, 1, scheme, dfd_offset, 4 + dfd_block_size, 0, 0] {
+            bytes.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        bytes.extend_from_slice(&[0; 16]);
+        for value in [data_offset as u64, 16, 16] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(4 + dfd_block_size).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(2 | (dfd_block_size << 16)).to_le_bytes());
+        bytes.extend_from_slice(&(color_model | (1 << 8) | (2 << 16)).to_le_bytes());
+        bytes.extend_from_slice(&[3, 3, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0]);
+        for channel in channels {
+            bytes.extend_from_slice(&((127 << 16) | (channel << 24)).to_le_bytes());
+            bytes.extend_from_slice(&[0; 12]);
+        }
+        bytes.extend_from_slice(&[0; 16]);
+        bytes
+    }
+
+    #[test]
+    fn codec_and_alpha_from_the_descriptor() {
+        let etc1s = BasisImage::from_bytes(&ktx2_file(163, &[0, 15])).unwrap().unwrap();
+        assert_eq!((etc1s.codec, etc1s.has_alpha, etc1s.width, etc1s.height), (Codec::Etc1s, true, 8, 8));
+        let uastc = BasisImage::from_bytes(&ktx2_file(166, &[0])).unwrap().unwrap();
+        assert_eq!((uastc.codec, uastc.has_alpha), (Codec::Uastc, false));
+        assert!(BasisImage::from_bytes(&ktx2_file(166, &[3])).unwrap().unwrap().has_alpha);
+        assert!(BasisImage::from_bytes(&ktx2_file(1, &[0])).is_err());
+        assert!(BasisImage::from_bytes(b"DDS ").unwrap().is_none());
+    }
+
+    #[test]
+    fn transcode_targets_follow_the_features() {
+        use wgpu::TextureFormat::*;
+        let astc = Astc { block: wgpu::AstcBlock::B4x4, channel: wgpu::AstcChannel::Unorm };
+        let all = wgpu::Features::TEXTURE_COMPRESSION_BC | wgpu::Features::TEXTURE_COMPRESSION_ETC2 | wgpu::Features::TEXTURE_COMPRESSION_ASTC;
+        assert_eq!(target_format(Codec::Uastc, true, 8, 8, all).1, astc);
+        assert_eq!(target_format(Codec::Etc1s, false, 8, 8, all).1, Etc2Rgb8Unorm);
+        assert_eq!(target_format(Codec::Etc1s, true, 8, 8, all).1, Etc2Rgba8Unorm);
+        assert_eq!(target_format(Codec::Uastc, true, 8, 8, wgpu::Features::TEXTURE_COMPRESSION_BC).1, Bc7RgbaUnorm);
+        assert_eq!(target_format(Codec::Uastc, true, 8, 8, wgpu::Features::empty()), (KTX_TTF_RGBA32, Rgba8Unorm));
+        //the adapter can't sample block formats that don't cover the image exactly
+        assert_eq!(target_format(Codec::Uastc, true, 6, 8, all).1, Rgba8Unorm);
+    }
+}
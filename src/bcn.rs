@@ -0,0 +1,742 @@
+use anyhow::*;
+
+// software decoders for bc1-bc7 blocks, used when the adapter can't sample
+// block compressed textures directly
+
+type Block = [[u8; 4]; 16];
+
+pub fn can_decompress(format: wgpu::TextureFormat) -> bool {
+    use wgpu::TextureFormat::*;
+    matches!(
+        format,
+        Bc1RgbaUnorm | Bc1RgbaUnormSrgb
+            | Bc2RgbaUnorm | Bc2RgbaUnormSrgb
+            | Bc3RgbaUnorm | Bc3RgbaUnormSrgb
+            | Bc4RUnorm | Bc4RSnorm
+            | Bc5RgUnorm | Bc5RgSnorm
+            | Bc6hRgbUfloat | Bc6hRgbFloat
+            | Bc7RgbaUnorm | Bc7RgbaUnormSrgb
+    )
+}
+
+// decode one 4x4 block into row major pixels, rgba8 for everything but bc6h which
+// comes out as rgba16float. the snorm formats keep their sign and come out as rgba8 snorm
+pub fn decode_block(format: wgpu::TextureFormat, data: &[u8], out: &mut [u8]) {
+    use wgpu::TextureFormat::*;
+    let block = match format {
+        Bc1RgbaUnorm | Bc1RgbaUnormSrgb => decode_bc1(data, false),
+        Bc2RgbaUnorm | Bc2RgbaUnormSrgb => decode_bc2(data),
+        Bc3RgbaUnorm | Bc3RgbaUnormSrgb => decode_bc3(data),
+        Bc4RUnorm => decode_bc4(data),
+        Bc4RSnorm => decode_bc4_snorm(data),
+        Bc5RgUnorm => decode_bc5(data),
+        Bc5RgSnorm => decode_bc5_snorm(data),
+        Bc6hRgbUfloat | Bc6hRgbFloat => {
+            for (pixel, color) in decode_bc6h(data, format == Bc6hRgbFloat).iter().enumerate() {
+                for (channel, half) in color.iter().enumerate() {
+                    let offset = pixel * 8 + channel * 2;
+                    out[offset..offset + 2].copy_from_slice(&half.to_le_bytes());
+                }
+            }
+            return;
+        }
+        _ => decode_bc7(data),
+    };
+    for (pixel, color) in block.iter().enumerate() {
+        out[pixel * 4..pixel * 4 + 4].copy_from_slice(color);
+    }
+}
+
+fn rgb565(color: u16) -> [u8; 3] {
+    let r = ((color >> 11) & 31) as u8;
+    let g = ((color >> 5) & 63) as u8;
+    let b = (color & 31) as u8;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+fn decode_bc1(data: &[u8], force_four_colors: bool) -> Block {
+    let c0 = u16::from_le_bytes([data[0], data[1]]);
+    let c1 = u16::from_le_bytes([data[2], data[3]]);
+    let e0 = rgb565(c0);
+    let e1 = rgb565(c1);
+
+    let mut palette = [[0u8; 4]; 4];
+    palette[0] = [e0[0], e0[1], e0[2], 255];
+    palette[1] = [e1[0], e1[1], e1[2], 255];
+    for c in 0..3 {
+        let (a, b) = (e0[c] as u32, e1[c] as u32);
+        if c0 > c1 || force_four_colors {
+            palette[2][c] = ((2 * a + b) / 3) as u8;
+            palette[3][c] = ((a + 2 * b) / 3) as u8;
+        } else {
+            palette[2][c] = ((a + b) / 2) as u8;
+            palette[3][c] = 0;
+        }
+    }
+    palette[2][3] = 255;
+    palette[3][3] = if c0 > c1 || force_four_colors { 255 } else { 0 };
+
+    let indices = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let mut block = [[0u8; 4]; 16];
+    for (i, pixel) in block.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+    block
+}
+
+fn decode_bc2(data: &[u8]) -> Block {
+    let mut block = decode_bc1(&data[8..16], true);
+    let alpha = u64::from_le_bytes(data[0..8].try_into().unwrap());
+    for (i, pixel) in block.iter_mut().enumerate() {
+        pixel[3] = ((alpha >> (4 * i)) & 15) as u8 * 17;
+    }
+    block
+}
+
+fn decode_bc3(data: &[u8]) -> Block {
+    let mut block = decode_bc1(&data[8..16], true);
+    let alpha = decode_bc4_channel(&data[0..8]);
+    for (pixel, a) in block.iter_mut().zip(alpha) {
+        pixel[3] = a;
+    }
+    block
+}
+
+fn decode_bc4_channel(data: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (data[0] as u32, data[1] as u32);
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a0 + i as u32 * a1 + 3) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a0 + i as u32 * a1 + 2) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&data[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    let mut values = [0u8; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[((indices >> (3 * i)) & 7) as usize];
+    }
+    values
+}
+
+// rounds halves away from zero like the unsigned decoder does for positive values
+fn round_div(value: i32, divisor: i32) -> i32 {
+    (value + value.signum() * (divisor / 2)) / divisor
+}
+
+// same as decode_bc4_channel with signed endpoints, -128 is treated as -127
+fn decode_bc4_snorm_channel(data: &[u8]) -> [i8; 16] {
+    let a0 = (data[0] as i8).max(-127) as i32;
+    let a1 = (data[1] as i8).max(-127) as i32;
+    let mut palette = [0i32; 8];
+    palette[0] = a0;
+    palette[1] = a1;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = round_div((7 - i as i32) * a0 + i as i32 * a1, 7);
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = round_div((5 - i as i32) * a0 + i as i32 * a1, 5);
+        }
+        palette[6] = -127;
+        palette[7] = 127;
+    }
+
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&data[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    let mut values = [0i8; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[((indices >> (3 * i)) & 7) as usize] as i8;
+    }
+    values
+}
+
+fn decode_bc4(data: &[u8]) -> Block {
+    let red = decode_bc4_channel(data);
+    let mut block = [[0u8; 4]; 16];
+    for (pixel, r) in block.iter_mut().zip(red) {
+        *pixel = [r, 0, 0, 255];
+    }
+    block
+}
+
+fn decode_bc5(data: &[u8]) -> Block {
+    let red = decode_bc4_channel(&data[0..8]);
+    let green = decode_bc4_channel(&data[8..16]);
+    let mut block = [[0u8; 4]; 16];
+    for (i, pixel) in block.iter_mut().enumerate() {
+        *pixel = [red[i], green[i], 0, 255];
+    }
+    block
+}
+
+fn decode_bc4_snorm(data: &[u8]) -> Block {
+    let red = decode_bc4_snorm_channel(data);
+    let mut block = [[0u8; 4]; 16];
+    for (pixel, r) in block.iter_mut().zip(red) {
+        *pixel = [r as u8, 0, 0, 127];
+    }
+    block
+}
+
+fn decode_bc5_snorm(data: &[u8]) -> Block {
+    let red = decode_bc4_snorm_channel(&data[0..8]);
+    let green = decode_bc4_snorm_channel(&data[8..16]);
+    let mut block = [[0u8; 4]; 16];
+    for (i, pixel) in block.iter_mut().enumerate() {
+        *pixel = [red[i] as u8, green[i] as u8, 0, 127];
+    }
+    block
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, count: usize) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            let bit = (self.data[(self.position + i) / 8] >> ((self.position + i) % 8)) & 1;
+            value |= (bit as u32) << i;
+        }
+        self.position += count;
+        value
+    }
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    index_selection_bits: usize,
+    color_bits: usize,
+    alpha_bits: usize,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: usize,
+    secondary_index_bits: usize,
+}
+
+const fn bc7_mode(subsets: usize, partition_bits: usize, rotation_bits: usize, index_selection_bits: usize, color_bits: usize, alpha_bits: usize, endpoint_pbits: bool, shared_pbits: bool, index_bits: usize, secondary_index_bits: usize) -> Bc7Mode {
+    Bc7Mode { subsets, partition_bits, rotation_bits, index_selection_bits, color_bits, alpha_bits, endpoint_pbits, shared_pbits, index_bits, secondary_index_bits }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, 4, 0, 0, 4, 0, true, false, 3, 0),
+    bc7_mode(2, 6, 0, 0, 6, 0, false, true, 3, 0),
+    bc7_mode(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+    bc7_mode(2, 6, 0, 0, 7, 0, true, false, 2, 0),
+    bc7_mode(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+    bc7_mode(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+    bc7_mode(1, 0, 0, 0, 7, 7, true, false, 4, 0),
+    bc7_mode(2, 6, 0, 0, 5, 5, true, false, 2, 0),
+];
+
+// bit i is the subset of pixel i
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+// bits 2i..2i+1 are the subset of pixel i
+const BC7_PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+const BC7_ANCHORS_2: [usize; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+const BC7_ANCHORS_3_SECOND: [usize; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+    3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+    8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+    3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+];
+
+const BC7_ANCHORS_3_THIRD: [usize; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+    15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+    15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn bc7_interpolate(e0: u8, e1: u8, index: u32, index_bits: usize) -> u8 {
+    let weight = match index_bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        _ => BC7_WEIGHTS_4[index as usize],
+    };
+    (((64 - weight) * e0 as u32 + weight * e1 as u32 + 32) >> 6) as u8
+}
+
+fn bc7_expand(value: u32, bits: usize) -> u8 {
+    let value = value << (8 - bits);
+    (value | (value >> bits)) as u8
+}
+
+fn decode_bc7(data: &[u8]) -> Block {
+    let mut reader = BitReader { data, position: 0 };
+
+    let mut mode_index = 0;
+    while mode_index < 8 && reader.read(1) == 0 {
+        mode_index += 1;
+    }
+    if mode_index == 8 {
+        //reserved mode, decoders output transparent black
+        return [[0u8; 4]; 16];
+    }
+    let mode = &BC7_MODES[mode_index];
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    //endpoints[subset * 2 + n][channel]
+    let mut endpoints = [[0u32; 4]; 6];
+    let endpoint_count = mode.subsets * 2;
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(mode.color_bits);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        endpoint[3] = if mode.alpha_bits > 0 { reader.read(mode.alpha_bits) } else { 255 };
+    }
+
+    let mut color_bits = mode.color_bits;
+    let mut alpha_bits = mode.alpha_bits;
+    if mode.endpoint_pbits || mode.shared_pbits {
+        let mut pbits = [0u32; 6];
+        if mode.endpoint_pbits {
+            for pbit in pbits.iter_mut().take(endpoint_count) {
+                *pbit = reader.read(1);
+            }
+        } else {
+            for subset in 0..mode.subsets {
+                let pbit = reader.read(1);
+                pbits[subset * 2] = pbit;
+                pbits[subset * 2 + 1] = pbit;
+            }
+        }
+        for (endpoint, pbit) in endpoints.iter_mut().zip(pbits).take(endpoint_count) {
+            for channel in 0..3 {
+                endpoint[channel] = (endpoint[channel] << 1) | pbit;
+            }
+            if mode.alpha_bits > 0 {
+                endpoint[3] = (endpoint[3] << 1) | pbit;
+            }
+        }
+        color_bits += 1;
+        if mode.alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    let mut colors = [[0u8; 4]; 6];
+    for (color, endpoint) in colors.iter_mut().zip(endpoints).take(endpoint_count) {
+        for channel in 0..3 {
+            color[channel] = bc7_expand(endpoint[channel], color_bits);
+        }
+        color[3] = if alpha_bits > 0 { bc7_expand(endpoint[3], alpha_bits) } else { 255 };
+    }
+
+    let subset_of = |pixel: usize| -> usize {
+        match mode.subsets {
+            2 => ((BC7_PARTITIONS_2[partition] >> pixel) & 1) as usize,
+            3 => ((BC7_PARTITIONS_3[partition] >> (2 * pixel)) & 3) as usize,
+            _ => 0,
+        }
+    };
+    let is_anchor = |pixel: usize| -> bool {
+        pixel == 0
+            || (mode.subsets == 2 && pixel == BC7_ANCHORS_2[partition])
+            || (mode.subsets == 3 && (pixel == BC7_ANCHORS_3_SECOND[partition] || pixel == BC7_ANCHORS_3_THIRD[partition]))
+    };
+
+    let mut indices = [0u32; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        let bits = if is_anchor(pixel) { mode.index_bits - 1 } else { mode.index_bits };
+        *index = reader.read(bits);
+    }
+    let mut secondary_indices = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (pixel, index) in secondary_indices.iter_mut().enumerate() {
+            let bits = if pixel == 0 { mode.secondary_index_bits - 1 } else { mode.secondary_index_bits };
+            *index = reader.read(bits);
+        }
+    }
+
+    let mut block = [[0u8; 4]; 16];
+    for (pixel, out) in block.iter_mut().enumerate() {
+        let subset = subset_of(pixel);
+        let e0 = colors[subset * 2];
+        let e1 = colors[subset * 2 + 1];
+
+        let (color_index, color_index_bits, alpha_index, alpha_index_bits) = if mode.secondary_index_bits == 0 {
+            (indices[pixel], mode.index_bits, indices[pixel], mode.index_bits)
+        } else if index_selection == 0 {
+            (indices[pixel], mode.index_bits, secondary_indices[pixel], mode.secondary_index_bits)
+        } else {
+            (secondary_indices[pixel], mode.secondary_index_bits, indices[pixel], mode.index_bits)
+        };
+
+        for channel in 0..3 {
+            out[channel] = bc7_interpolate(e0[channel], e1[channel], color_index, color_index_bits);
+        }
+        out[3] = bc7_interpolate(e0[3], e1[3], alpha_index, alpha_index_bits);
+
+        match rotation {
+            1 => out.swap(0, 3),
+            2 => out.swap(1, 3),
+            3 => out.swap(2, 3),
+            _ => {}
+        }
+    }
+    block
+}
+
+// one run of header bits, (endpoint, channel, last bit, first bit) read from the first bit
+// towards the last so [9:0] is (.., 9, 0) and the reversed [10:11] is (.., 10, 11), the
+// same notation as the bc6h mode tables in the d3d docs
+type Bc6hBits = (usize, usize, u32, u32);
+
+struct Bc6hMode {
+    mode: u32,
+    header: &'static [Bc6hBits],
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    transformed: bool,
+    regions: usize,
+}
+
+const R: usize = 0;
+const G: usize = 1;
+const B: usize = 2;
+
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { mode: 0x00, endpoint_bits: 10, delta_bits: [5, 5, 5], transformed: true, regions: 2, header: &[
+        (2, G, 4, 4), (2, B, 4, 4), (3, B, 4, 4), (0, R, 9, 0), (0, G, 9, 0), (0, B, 9, 0), (1, R, 4, 0), (3, G, 4, 4),
+        (2, G, 3, 0), (1, G, 4, 0), (3, B, 0, 0), (3, G, 3, 0), (1, B, 4, 0), (3, B, 1, 1), (2, B, 3, 0), (2, R, 4, 0),
+        (3, B, 2, 2), (3, R, 4, 0), (3, B, 3, 3),
+    ] },
+    Bc6hMode { mode: 0x01, endpoint_bits: 7, delta_bits: [6, 6, 6], transformed: true, regions: 2, header: &[
+        (2, G, 5, 5), (3, G, 4, 4), (3, G, 5, 5), (0, R, 6, 0), (3, B, 0, 0), (3, B, 1, 1), (2, B, 4, 4), (0, G, 6, 0),
+        (2, B, 5, 5), (3, B, 2, 2), (2, G, 4, 4), (0, B, 6, 0), (3, B, 3, 3), (3, B, 5, 5), (3, B, 4, 4), (1, R, 5, 0),
+        (2, G, 3, 0), (1, G, 5, 0), (3, G, 3, 0), (1, B, 5, 0), (2, B, 3, 0), (2, R, 5, 0), (3, R, 5, 0),
+    ] },
+    Bc6hMode { mode: 0x02, endpoint_bits: 11, delta_bits: [5, 4, 4], transformed: true, regions: 2, header: &[
+        (0, R, 9, 0), (0, G, 9, 0), (0, B, 9, 0), (1, R, 4, 0), (0, R, 10, 10), (2, G, 3, 0), (1, G, 3, 0), (0, G, 10, 10),
+        (3, B, 0, 0), (3, G, 3, 0), (1, B, 3, 0), (0, B, 10, 10), (3, B, 1, 1), (2, B, 3, 0), (2, R, 4, 0), (3, B, 2, 2),
+        (3, R, 4, 0), (3, B, 3, 3),
+    ] },
+    Bc6hMode { mode: 0x06, endpoint_bits: 11, delta_bits: [4, 5, 4], transformed: true, regions: 2, header: &[
+        (0, R, 9, 0), (0, G, 9, 0), (0, B, 9, 0), (1, R, 3, 0), (0, R, 10, 10), (3, G, 4, 4), (2, G, 3, 0), (1, G, 4, 0),
+        (0, G, 10, 10), (3, G, 3, 0), (1, B, 3, 0), (0, B, 10, 10), (3, B, 1, 1), (2, B, 3, 0), (2, R, 3, 0), (3, B, 0, 0),
+        (3, B, 2, 2), (3, R, 3, 0), (2, G, 4, 4), (3, B, 3, 3),
+    ] },
+    Bc6hMode { mode: 0x0A, endpoint_bits: 11, delta_bits: [4, 4, 5], transformed: true, regions: 2, header: &[
+        (0, R, 9, 0), (0, G, 9, 0), (0, B, 9, 0), (1, R, 3, 0), (0, R, 10, 10), (2, B, 4, 4), (2, G, 3, 0), (1, G, 3, 0),
+        (0, G, 10, 10), (3, B, 0, 0), (3, G, 3, 0), (1, B, 4, 0), (0, B, 10, 10), (2, B, 3, 0), (2, R, 3, 0), (3, B, 1, 1),
+        (3, B, 2, 2), (3, R, 3, 0), (3, B, 4, 4), (3, B, 3, 3),
+    ] },
+    Bc6hMode { mode: 0x0E, endpoint_bits: 9, delta_bits: [5, 5, 5], transformed: true, regions: 2, header: &[
+        (0, R, 8, 0), (2, B, 4, 4), (0, G, 8, 0), (2, G, 4, 4), (0, B, 8, 0), (3, B, 4, 4), (1, R, 4, 0), (3, G, 4, 4),
+        (2, G, 3, 0), (1, G, 4, 0), (3, B, 0, 0), (3, G, 3, 0), (1, B, 4, 0), (3, B, 1, 1), (2, B, 3, 0), (2, R, 4, 0),
+        (3, B, 2, 2), (3, R, 4, 0), (3, B, 3, 3),
+    ] },
+    Bc6hMode { mode: 0x12, endpoint_bits: 8, delta_bits: [6, 5, 5], transformed: true, regions: 2, header: &[
+        (0, R, 7, 0), (3, G, 4, 4), (2, B, 4, 4), (0, G, 7, 0), (3, B, 2, 2), (2, G, 4, 4), (0, B, 7, 0), (3, B, 3, 3),
+        (3, B, 4, 4), (1, R, 5, 0), (2, G, 3, 0), (1, G, 4, 0), (3, B, 0, 0), (3, G, 3, 0), (1, B, 4, 0), (3, B, 1, 1),
+        (2, B, 3, 0), (2, R, 5, 0), (3, R, 5, 0),
+    ] },
+    Bc6hMode { mode: 0x16, endpoint_bits: 8, delta_bits: [5, 6, 5], transformed: true, regions: 2, header: &[
+        (0, R, 7, 0), (3, B, 0, 0), (2, B, 4, 4), (0, G, 7, 0), (2, G, 5, 5), (2, G, 4, 4), (0, B, 7, 0), (3, G, 5, 5),
+        (3, B, 4, 4), (1, R, 4, 0), (3, G, 4, 4), (2, G, 3, 0), (1, G, 5, 0), (3, G, 3, 0), (1, B, 4, 0), (3, B, 1, 1),
+        (2, B, 3, 0), (2, R, 4, 0), (3, B, 2, 2), (3, R, 4, 0), (3, B, 3, 3),
+    ] },
+    Bc6hMode { mode: 0x1A, endpoint_bits: 8, delta_bits: [5, 5, 6], transformed: true, regions: 2, header: &[
+        (0, R, 7, 0), (3, B, 1, 1), (2, B, 4, 4), (0, G, 7, 0), (2, B, 5, 5), (2, G, 4, 4), (0, B, 7, 0), (3, B, 5, 5),
+        (3, B, 4, 4), (1, R, 4, 0), (3, G, 4, 4), (2, G, 3, 0), (1, G, 4, 0), (3, B, 0, 0), (3, G, 3, 0), (1, B, 5, 0),
+        (2, B, 3, 0), (2, R, 4, 0), (3, B, 2, 2), (3, R, 4, 0), (3, B, 3, 3),
+    ] },
+    Bc6hMode { mode: 0x1E, endpoint_bits: 6, delta_bits: [6, 6, 6], transformed: false, regions: 2, header: &[
+        (0, R, 5, 0), (3, G, 4, 4), (3, B, 0, 0), (3, B, 1, 1), (2, B, 4, 4), (0, G, 5, 0), (2, G, 5, 5), (2, B, 5, 5),
+        (3, B, 2, 2), (2, G, 4, 4), (0, B, 5, 0), (3, G, 5, 5), (3, B, 3, 3), (3, B, 5, 5), (3, B, 4, 4), (1, R, 5, 0),
+        (2, G, 3, 0), (1, G, 5, 0), (3, G, 3, 0), (1, B, 5, 0), (2, B, 3, 0), (2, R, 5, 0), (3, R, 5, 0),
+    ] },
+    Bc6hMode { mode: 0x03, endpoint_bits: 10, delta_bits: [10, 10, 10], transformed: false, regions: 1, header: &[
+        (0, R, 9, 0), (0, G, 9, 0), (0, B, 9, 0), (1, R, 9, 0), (1, G, 9, 0), (1, B, 9, 0),
+    ] },
+    Bc6hMode { mode: 0x07, endpoint_bits: 11, delta_bits: [9, 9, 9], transformed: true, regions: 1, header: &[
+        (0, R, 9, 0), (0, G, 9, 0), (0, B, 9, 0), (1, R, 8, 0), (0, R, 10, 10), (1, G, 8, 0), (0, G, 10, 10), (1, B, 8, 0),
+        (0, B, 10, 10),
+    ] },
+    Bc6hMode { mode: 0x0B, endpoint_bits: 12, delta_bits: [8, 8, 8], transformed: true, regions: 1, header: &[
+        (0, R, 9, 0), (0, G, 9, 0), (0, B, 9, 0), (1, R, 7, 0), (0, R, 10, 11), (1, G, 7, 0), (0, G, 10, 11), (1, B, 7, 0),
+        (0, B, 10, 11),
+    ] },
+    Bc6hMode { mode: 0x0F, endpoint_bits: 16, delta_bits: [4, 4, 4], transformed: true, regions: 1, header: &[
+        (0, R, 9, 0), (0, G, 9, 0), (0, B, 9, 0), (1, R, 3, 0), (0, R, 10, 15), (1, G, 3, 0), (0, G, 10, 15), (1, B, 3, 0),
+        (0, B, 10, 15),
+    ] },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+fn bc6h_unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 || value == 0 {
+            value
+        } else if value == (1 << bits) - 1 {
+            0xFFFF
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else {
+        if bits >= 16 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 { -unquantized } else { unquantized }
+    }
+}
+
+// scale the interpolated value into the half float range, negative values get the sign bit
+fn bc6h_finish(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        0x8000 | (((-value) * 31) >> 5) as u16
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+// rgb half floats per pixel plus an alpha of 1.0
+fn decode_bc6h(data: &[u8], signed: bool) -> [[u16; 4]; 16] {
+    const ONE: u16 = 0x3C00;
+    let mut reader = BitReader { data, position: 0 };
+
+    let mut mode_bits = reader.read(2);
+    if mode_bits > 1 {
+        mode_bits |= reader.read(3) << 2;
+    }
+    let mode = match BC6H_MODES.iter().find(|mode| mode.mode == mode_bits) {
+        Some(mode) => mode,
+        //reserved modes decode to black
+        None => return [[0, 0, 0, ONE]; 16],
+    };
+
+    let mut endpoints = [[0i32; 3]; 4];
+    for &(endpoint, channel, last, first) in mode.header {
+        if first <= last {
+            for bit in first..=last {
+                endpoints[endpoint][channel] |= (reader.read(1) as i32) << bit;
+            }
+        } else {
+            for bit in (last..=first).rev() {
+                endpoints[endpoint][channel] |= (reader.read(1) as i32) << bit;
+            }
+        }
+    }
+    let partition = if mode.regions == 2 { reader.read(5) as usize } else { 0 };
+
+    let endpoint_count = mode.regions * 2;
+    let mask = (1 << mode.endpoint_bits) - 1;
+    for channel in 0..3 {
+        if signed {
+            endpoints[0][channel] = sign_extend(endpoints[0][channel], mode.endpoint_bits);
+        }
+        let base = endpoints[0][channel];
+        for endpoint in endpoints.iter_mut().take(endpoint_count).skip(1) {
+            if mode.transformed {
+                let delta = sign_extend(endpoint[channel], mode.delta_bits[channel]);
+                endpoint[channel] = (base + delta) & mask;
+                if signed {
+                    endpoint[channel] = sign_extend(endpoint[channel], mode.endpoint_bits);
+                }
+            } else if signed {
+                endpoint[channel] = sign_extend(endpoint[channel], mode.endpoint_bits);
+            }
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint.iter_mut() {
+            *value = bc6h_unquantize(*value, mode.endpoint_bits, signed);
+        }
+    }
+
+    let index_bits = if mode.regions == 2 { 3 } else { 4 };
+    let mut block = [[0, 0, 0, ONE]; 16];
+    for (pixel, out) in block.iter_mut().enumerate() {
+        let anchor = pixel == 0 || (mode.regions == 2 && pixel == BC7_ANCHORS_2[partition]);
+        let index = reader.read(if anchor { index_bits - 1 } else { index_bits });
+        let weight = if index_bits == 3 { BC7_WEIGHTS_3[index as usize] } else { BC7_WEIGHTS_4[index as usize] } as i32;
+        let subset = if mode.regions == 2 { ((BC7_PARTITIONS_2[partition] >> pixel) & 1) as usize } else { 0 };
+        let e0 = endpoints[subset * 2];
+        let e1 = endpoints[subset * 2 + 1];
+        for channel in 0..3 {
+            let value = (e0[channel] * (64 - weight) + e1[channel] * weight + 32) >> 6;
+            out[channel] = bc6h_finish(value, signed);
+        }
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //red and blue 565 endpoints, pixels 0-3 use indices 0-3 and the rest index 0
+    const RED: [u8; 2] = [0x00, 0xF8];
+    const BLUE: [u8; 2] = [0x1F, 0x00];
+    const INDICES_0123: [u8; 4] = [0xE4, 0x00, 0x00, 0x00];
+
+    fn bc1_block(c0: [u8; 2], c1: [u8; 2]) -> Vec<u8> {
+        [c0, c1].concat().into_iter().chain(INDICES_0123).collect()
+    }
+
+    #[test]
+    fn bc1_four_colors() {
+        let block = decode_bc1(&bc1_block(RED, BLUE), false);
+        assert_eq!(block[0], [255, 0, 0, 255]);
+        assert_eq!(block[1], [0, 0, 255, 255]);
+        assert_eq!(block[2], [170, 0, 85, 255]);
+        assert_eq!(block[3], [85, 0, 170, 255]);
+        assert_eq!(block[15], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc1_three_colors_and_transparent() {
+        let block = decode_bc1(&bc1_block(BLUE, RED), false);
+        assert_eq!(block[0], [0, 0, 255, 255]);
+        assert_eq!(block[1], [255, 0, 0, 255]);
+        assert_eq!(block[2], [127, 0, 127, 255]);
+        assert_eq!(block[3], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bc2_explicit_alpha() {
+        let data: Vec<u8> = [0x8F, 0, 0, 0, 0, 0, 0, 0].into_iter().chain(bc1_block(BLUE, RED)).collect();
+        let block = decode_bc2(&data);
+        //bc2 colors always use the four color palette
+        assert_eq!(block[0], [0, 0, 255, 255]);
+        assert_eq!(block[1], [255, 0, 0, 136]);
+        assert_eq!(block[2], [85, 0, 170, 0]);
+        assert_eq!(block[3], [170, 0, 85, 0]);
+    }
+
+    //pixels 0-7 use indices 0-7
+    const BC4_INDICES: [u8; 6] = [0x88, 0xC6, 0xFA, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn bc3_and_bc4_interpolated_values() {
+        let alpha: Vec<u8> = [255, 0].into_iter().chain(BC4_INDICES).collect();
+        let expected = [255, 0, 219, 182, 146, 109, 73, 36];
+        let block = decode_bc4(&alpha);
+        for (pixel, value) in expected.iter().enumerate() {
+            assert_eq!(block[pixel], [*value, 0, 0, 255]);
+        }
+        let data: Vec<u8> = alpha.into_iter().chain(bc1_block(RED, BLUE)).collect();
+        let block = decode_bc3(&data);
+        assert_eq!(block[2], [170, 0, 85, 219]);
+        assert_eq!(block[7], [255, 0, 0, 36]);
+    }
+
+    #[test]
+    fn bc5_six_value_mode() {
+        let red: Vec<u8> = [255, 0].into_iter().chain(BC4_INDICES).collect();
+        let green: Vec<u8> = [0, 255].into_iter().chain(BC4_INDICES).collect();
+        let block = decode_bc5(&[red, green].concat());
+        let expected_green = [0, 255, 51, 102, 153, 204, 0, 255];
+        for (pixel, green) in expected_green.iter().enumerate() {
+            assert_eq!(block[pixel][1], *green);
+        }
+        assert_eq!(block[2], [219, 51, 0, 255]);
+    }
+
+    #[test]
+    fn bc4_snorm_keeps_the_sign() {
+        //-128 is read as -127
+        let data: Vec<u8> = [0x7F, 0x80].into_iter().chain(BC4_INDICES).collect();
+        let block = decode_bc4_snorm(&data);
+        let expected: [i8; 8] = [127, -127, 91, 54, 18, -18, -54, -91];
+        for (pixel, value) in expected.iter().enumerate() {
+            assert_eq!(block[pixel], [*value as u8, 0, 0, 127]);
+        }
+    }
+
+    #[test]
+    fn bc7_mode_6() {
+        //endpoints 0 and 7f with p bits 0 and 1, pixel 1 uses index 15 and pixel 2 index 8
+        let data = [0x40, 0xC0, 0x1F, 0xF0, 0x07, 0xFC, 0x01, 0x7F, 0xF1, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let block = decode_bc7(&data);
+        assert_eq!(block[0], [0, 0, 0, 0]);
+        assert_eq!(block[1], [255, 255, 255, 255]);
+        assert_eq!(block[2], [135, 135, 135, 135]);
+        assert_eq!(block[3], [0, 0, 0, 0]);
+
+        let reserved = decode_bc7(&[0u8; 16]);
+        assert!(reserved.iter().all(|pixel| *pixel == [0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn bc6h_mode_11() {
+        //10 bit endpoints 0 and 1023 with the same indices as the bc7 block
+        let data = [0x03, 0x00, 0x00, 0x00, 0xF8, 0xFF, 0xFF, 0xFF, 0xF1, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let block = decode_bc6h(&data, false);
+        assert_eq!(block[0], [0, 0, 0, 0x3C00]);
+        //65504, the biggest half float
+        assert_eq!(block[1], [0x7BFF, 0x7BFF, 0x7BFF, 0x3C00]);
+        assert_eq!(block[2], [0x41DF, 0x41DF, 0x41DF, 0x3C00]);
+
+        //signed endpoints are sign extended, 1023 is -1 which unquantizes to -96 and scales to -93
+        let block = decode_bc6h(&data, true);
+        assert_eq!(block[1], [0x8000 | 93, 0x8000 | 93, 0x8000 | 93, 0x3C00]);
+    }
+}
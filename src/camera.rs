@@ -39,19 +39,36 @@ impl Camera {
     }
 
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
-        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
-
         Matrix4::look_to_rh(
             self.position,
-            Vector3::new(
-                cos_pitch * cos_yaw,
-                sin_pitch,
-                cos_pitch * sin_yaw
-            ).normalize(),
+            self.direction(),
             Vector3::unit_y(),
         )
     }
+
+    // unit vector the camera looks along
+    pub fn direction(&self) -> Vector3<f32> {
+        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
+        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+
+        Vector3::new(
+            cos_pitch * cos_yaw,
+            sin_pitch,
+            cos_pitch * sin_yaw
+        ).normalize()
+    }
+
+    // point the camera at target from position, pitch stays inside the range the controller allows
+    pub fn look_at(&mut self, position: Point3<f32>, target: Point3<f32>) {
+        let direction = target - position;
+        self.position = position;
+        if direction.magnitude2() == 0.0 {
+            return;
+        }
+        let horizontal = (direction.x * direction.x + direction.z * direction.z).sqrt();
+        self.yaw = Rad(direction.z.atan2(direction.x));
+        self.pitch = Rad(direction.y.atan2(horizontal).clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
+    }
 }
 
 pub struct Projection {
@@ -85,6 +102,18 @@ impl Projection {
         //OPENGL_TO_WGPU_MATRIX * 
         perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
+
+    pub fn fovy(&self) -> Rad<f32> {
+        self.fovy
+    }
+
+    pub fn znear(&self) -> f32 {
+        self.znear
+    }
+
+    pub fn zfar(&self) -> f32 {
+        self.zfar
+    }
 }
 
 #[derive(Debug)]
@@ -218,4 +247,12 @@ impl CameraUniform {
         self.view_position = camera.position.to_homogeneous().into();
         self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
     }
+
+    // same camera with an extra transform applied in clip space, used to render one tile of a bigger image
+    pub fn with_clip_transform(&self, clip: Matrix4<f32>) -> Self {
+        Self {
+            view_position: self.view_position,
+            view_proj: (clip * Matrix4::from(self.view_proj)).into(),
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, bail};
+use image::RgbaImage;
+
+// a texture copied into a mappable buffer, recorded into the frame's encoder and read back after submit
+pub struct PendingCapture {
+    buffer: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+}
+
+impl PendingCapture {
+    pub fn new(device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) -> Self {
+        let format = texture.format();
+        let (width, height) = (texture.width(), texture.height());
+        let bytes_per_pixel = format.block_size(None).unwrap_or(4);
+        let padded_bytes_per_row = crate::align_up(width * bytes_per_pixel, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        Self {
+            buffer,
+            format,
+            width,
+            height,
+            padded_bytes_per_row,
+        }
+    }
+
+    // blocks until the gpu is done with the copy
+    pub fn read(self, device: &wgpu::Device) -> anyhow::Result<RgbaImage> {
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+
+        let data = slice.get_mapped_range();
+        let mut image = RgbaImage::new(self.width, self.height);
+        for (y, row) in data.chunks_exact(self.padded_bytes_per_row as usize).enumerate() {
+            for x in 0..self.width {
+                let pixel = match self.format {
+                    wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {
+                        let p = &row[x as usize * 4..x as usize * 4 + 4];
+                        [p[0], p[1], p[2], p[3]]
+                    }
+                    wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+                        let p = &row[x as usize * 4..x as usize * 4 + 4];
+                        [p[2], p[1], p[0], p[3]]
+                    }
+                    //hdr surface, linear values that get encoded to srgb like the display would
+                    wgpu::TextureFormat::Rgba16Float => {
+                        let p: &[u16] = bytemuck::cast_slice(&row[x as usize * 8..x as usize * 8 + 8]);
+                        [
+                            linear_to_srgb(half_to_f32(p[0])),
+                            linear_to_srgb(half_to_f32(p[1])),
+                            linear_to_srgb(half_to_f32(p[2])),
+                            (half_to_f32(p[3]).clamp(0.0, 1.0) * 255.0).round() as u8,
+                        ]
+                    }
+                    format => bail!("can't save {:?} textures", format),
+                };
+                image.put_pixel(x, y as u32, image::Rgba(pixel));
+            }
+        }
+        drop(data);
+        self.buffer.unmap();
+        Ok(image)
+    }
+}
+
+// png encoding takes a while for big captures, don't hold up the frame for it
+pub fn save_in_background(image: RgbaImage, path: PathBuf) {
+    std::thread::spawn(move || match image.save(&path) {
+        Ok(()) => println!("saved {:?} ({}x{})", path, image.width(), image.height()),
+        Err(err) => eprintln!("could not save {:?} : {}", path, err),
+    });
+}
+
+// prefix-YYYYMMDD-HHMMSS-mmm.png in dir, utc so names sort the same on every machine
+pub fn timestamped_path(dir: &Path, prefix: &str) -> PathBuf {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs() as i64;
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    dir.join(format!(
+        "{}-{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}.png",
+        prefix,
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        since_epoch.subsec_millis(),
+    ))
+}
+
+// days since 1970-01-01 to year, month, day (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn half_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        31 => if mantissa == 0.0 { sign * f32::INFINITY } else { f32::NAN },
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value.is_nan() { 0.0 } else { value.clamp(0.0, 1.0) };
+    let encoded = if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 };
+    (encoded * 255.0).round() as u8
+}
+
+// scale and offset clip space so the part of the frame from (x, y) with this size fills the target,
+// pixel coordinates of a frame_width x frame_height image with y going down
+pub fn tile_clip_transform(x: u32, y: u32, width: u32, height: u32, frame_width: u32, frame_height: u32) -> cgmath::Matrix4<f32> {
+    let left = x as f32 / frame_width as f32 * 2.0 - 1.0;
+    let right = (x + width) as f32 / frame_width as f32 * 2.0 - 1.0;
+    let top = 1.0 - y as f32 / frame_height as f32 * 2.0;
+    let bottom = 1.0 - (y + height) as f32 / frame_height as f32 * 2.0;
+    let scale_x = 2.0 / (right - left);
+    let scale_y = 2.0 / (top - bottom);
+    cgmath::Matrix4::new(
+        scale_x, 0.0, 0.0, 0.0,
+        0.0, scale_y, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        -(right + left) / (right - left), -(top + bottom) / (top - bottom), 0.0, 1.0,
+    )
+}
+
+pub fn poster_scale() -> anyhow::Result<u32> {
+    match std::env::var("KANIRENDERER_POSTER_SCALE") {
+        Ok(value) => match value.parse::<u32>() {
+            Ok(scale) if scale >= 1 => Ok(scale),
+            _ => Err(anyhow!("KANIRENDERER_POSTER_SCALE must be a whole number of at least 1, got {:?}", value)),
+        },
+        Err(_) => Ok(4),
+    }
+}
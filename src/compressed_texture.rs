@@ -0,0 +1,328 @@
+use anyhow::*;
+use std::io::Read;
+use crate::{astc, bcn, etc};
+
+// gpu ready texture data from a ktx2 or dds container, levels[0] is the full size image
+pub struct CompressedImage {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+const KTX2_MAGIC: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+const DDS_MAGIC: [u8; 4] = *b"DDS ";
+
+pub fn is_compressed_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(&KTX2_MAGIC) || bytes.starts_with(&DDS_MAGIC)
+}
+
+impl CompressedImage {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let image = if bytes.starts_with(&KTX2_MAGIC) {
+            parse_ktx2(bytes)?
+        } else if bytes.starts_with(&DDS_MAGIC) {
+            parse_dds(bytes)?
+        } else {
+            bail!("not a ktx2 or dds file");
+        };
+        image.validate()?;
+        Ok(image)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.levels.is_empty() {
+            bail!("texture has no mip levels");
+        }
+        for (level, data) in self.levels.iter().enumerate() {
+            let expected = level_byte_size(self.format, self.width, self.height, level as u32);
+            if data.len() < expected {
+                bail!("mip level {} is {} bytes, expected {}", level, data.len(), expected);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn mip_extent(&self, level: u32) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: (self.width >> level).max(1),
+            height: (self.height >> level).max(1),
+            depth_or_array_layers: 1,
+        }
+    }
+
+    // the adapter can sample this format directly
+    pub fn is_supported(&self, features: wgpu::Features) -> bool {
+        is_format_supported(self.format, self.width, self.height, features)
+    }
+
+    // decode on the cpu when the adapter has no support for the format, into rgba8 or
+    // rgba16float for bc6h
+    pub fn decompress(&self) -> Result<Self> {
+        let format = match decompressed_format(self.format) {
+            Some(format) => format,
+            None => bail!("{:?} is not supported by this adapter and can't be decoded on the cpu", self.format),
+        };
+        let mut levels = Vec::with_capacity(self.levels.len());
+        for (level, data) in self.levels.iter().enumerate() {
+            let extent = self.mip_extent(level as u32);
+            levels.push(decompress_level(self.format, extent.width, extent.height, data)?);
+        }
+        Ok(Self { format, width: self.width, height: self.height, levels })
+    }
+}
+
+// what decompress turns a format into, None when there's no cpu decoder for it
+pub fn decompressed_format(format: wgpu::TextureFormat) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat::*;
+    if !(bcn::can_decompress(format) || etc::can_decompress(format) || astc::can_decompress(format)) {
+        return None;
+    }
+    let decompressed = match format {
+        Bc6hRgbUfloat | Bc6hRgbFloat => Rgba16Float,
+        Bc4RSnorm | Bc5RgSnorm | EacR11Snorm | EacRg11Snorm => Rgba8Snorm,
+        _ if format.is_srgb() => Rgba8UnormSrgb,
+        _ => Rgba8Unorm,
+    };
+    Some(decompressed)
+}
+
+// decode a single mip level to tightly packed pixels of the decompressed format
+fn decompress_level(format: wgpu::TextureFormat, width: u32, height: u32, data: &[u8]) -> Result<Vec<u8>> {
+    let (block_width, block_height) = format.block_dimensions();
+    let (block_width, block_height) = (block_width as usize, block_height as usize);
+    let block_bytes = format.block_size(None).unwrap_or(16) as usize;
+    let pixel_bytes = match decompressed_format(format) {
+        Some(wgpu::TextureFormat::Rgba16Float) => 8,
+        Some(_) => 4,
+        None => bail!("no software decoder for {:?}", format),
+    };
+    let (width, height) = (width as usize, height as usize);
+
+    let blocks_wide = (width + block_width - 1) / block_width;
+    let blocks_high = (height + block_height - 1) / block_height;
+    if data.len() < blocks_wide * blocks_high * block_bytes {
+        bail!("{:?} level is {} bytes, expected {}", format, data.len(), blocks_wide * blocks_high * block_bytes);
+    }
+
+    let mut pixels = vec![0u8; width * height * pixel_bytes];
+    let mut block = vec![0u8; block_width * block_height * pixel_bytes];
+    for by in 0..blocks_high {
+        for bx in 0..blocks_wide {
+            let offset = (by * blocks_wide + bx) * block_bytes;
+            let block_data = &data[offset..offset + block_bytes];
+            match format {
+                wgpu::TextureFormat::Astc { channel, .. } => {
+                    let srgb = channel == wgpu::AstcChannel::UnormSrgb;
+                    astc::decode_block(block_data, block_width, block_height, srgb, &mut block);
+                }
+                _ if etc::can_decompress(format) => etc::decode_block(format, block_data, &mut block),
+                _ => bcn::decode_block(format, block_data, &mut block),
+            }
+
+            //copy the rows that are inside the image, edge blocks can hang over
+            let x = bx * block_width;
+            let row_pixels = block_width.min(width - x);
+            for py in 0..block_height.min(height - by * block_height) {
+                let y = by * block_height + py;
+                let dst = (y * width + x) * pixel_bytes;
+                let src = py * block_width * pixel_bytes;
+                pixels[dst..dst + row_pixels * pixel_bytes].copy_from_slice(&block[src..src + row_pixels * pixel_bytes]);
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+// wgpu also wants the base level of a compressed texture to be a multiple of the block size
+pub fn is_format_supported(format: wgpu::TextureFormat, width: u32, height: u32, features: wgpu::Features) -> bool {
+    let (block_width, block_height) = format.block_dimensions();
+    features.contains(format.required_features())
+        && (!format.is_compressed() || (width % block_width == 0 && height % block_height == 0))
+}
+
+pub fn level_byte_size(format: wgpu::TextureFormat, width: u32, height: u32, level: u32) -> usize {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_size(None).unwrap_or(4);
+    let width = (width >> level).max(1);
+    let height = (height >> level).max(1);
+    let blocks_wide = (width + block_width - 1) / block_width;
+    let blocks_high = (height + block_height - 1) / block_height;
+    (blocks_wide * blocks_high * block_size) as usize
+}
+
+fn parse_ktx2(bytes: &[u8]) -> Result<CompressedImage> {
+    let reader = ktx2::Reader::new(bytes).map_err(|err| anyhow!("invalid ktx2 file: {:?}", err))?;
+    let header = reader.header();
+
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+        bail!("ktx2 3d, array and cubemap textures are not supported");
+    }
+    let format = match header.format {
+        Some(format) => ktx2_format(format)?,
+        //basis universal, DecodedImage keeps those as they are until the adapter is known
+        None => bail!("basis universal ktx2 has to be transcoded, see basis::BasisImage"),
+    };
+
+    let mut levels = Vec::new();
+    for level in reader.levels() {
+        let data = match header.supercompression_scheme {
+            None => level.to_vec(),
+            Some(ktx2::SupercompressionScheme::Zstandard) => {
+                let mut decoder = ruzstd::StreamingDecoder::new(level)
+                    .map_err(|err| anyhow!("invalid zstd level: {:?}", err))?;
+                let mut data = Vec::new();
+                decoder.read_to_end(&mut data)?;
+                data
+            }
+            Some(scheme) => bail!("ktx2 supercompression {:?} is not supported", scheme),
+        };
+        levels.push(data);
+    }
+
+    Ok(CompressedImage {
+        format,
+        width: header.pixel_width,
+        height: header.pixel_height.max(1),
+        levels,
+    })
+}
+
+fn ktx2_format(format: ktx2::Format) -> Result<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::TextureFormat as W;
+    let mapped = match format {
+        K::R8G8B8A8_UNORM => W::Rgba8Unorm,
+        K::R8G8B8A8_SRGB => W::Rgba8UnormSrgb,
+        K::R8G8B8A8_SNORM => W::Rgba8Snorm,
+        K::R16G16B16A16_SFLOAT => W::Rgba16Float,
+        K::B8G8R8A8_UNORM => W::Bgra8Unorm,
+        K::B8G8R8A8_SRGB => W::Bgra8UnormSrgb,
+        K::BC1_RGB_UNORM_BLOCK | K::BC1_RGBA_UNORM_BLOCK => W::Bc1RgbaUnorm,
+        K::BC1_RGB_SRGB_BLOCK | K::BC1_RGBA_SRGB_BLOCK => W::Bc1RgbaUnormSrgb,
+        K::BC2_UNORM_BLOCK => W::Bc2RgbaUnorm,
+        K::BC2_SRGB_BLOCK => W::Bc2RgbaUnormSrgb,
+        K::BC3_UNORM_BLOCK => W::Bc3RgbaUnorm,
+        K::BC3_SRGB_BLOCK => W::Bc3RgbaUnormSrgb,
+        K::BC4_UNORM_BLOCK => W::Bc4RUnorm,
+        K::BC4_SNORM_BLOCK => W::Bc4RSnorm,
+        K::BC5_UNORM_BLOCK => W::Bc5RgUnorm,
+        K::BC5_SNORM_BLOCK => W::Bc5RgSnorm,
+        K::BC6H_UFLOAT_BLOCK => W::Bc6hRgbUfloat,
+        K::BC6H_SFLOAT_BLOCK => W::Bc6hRgbFloat,
+        K::BC7_UNORM_BLOCK => W::Bc7RgbaUnorm,
+        K::BC7_SRGB_BLOCK => W::Bc7RgbaUnormSrgb,
+        K::ETC2_R8G8B8_UNORM_BLOCK => W::Etc2Rgb8Unorm,
+        K::ETC2_R8G8B8_SRGB_BLOCK => W::Etc2Rgb8UnormSrgb,
+        K::ETC2_R8G8B8A1_UNORM_BLOCK => W::Etc2Rgb8A1Unorm,
+        K::ETC2_R8G8B8A1_SRGB_BLOCK => W::Etc2Rgb8A1UnormSrgb,
+        K::ETC2_R8G8B8A8_UNORM_BLOCK => W::Etc2Rgba8Unorm,
+        K::ETC2_R8G8B8A8_SRGB_BLOCK => W::Etc2Rgba8UnormSrgb,
+        K::EAC_R11_UNORM_BLOCK => W::EacR11Unorm,
+        K::EAC_R11_SNORM_BLOCK => W::EacR11Snorm,
+        K::EAC_R11G11_UNORM_BLOCK => W::EacRg11Unorm,
+        K::EAC_R11G11_SNORM_BLOCK => W::EacRg11Snorm,
+        _ => {
+            //astc formats come in unorm/srgb pairs in the same block order as wgpu
+            let value = format.0.get();
+            if (157..=184).contains(&value) {
+                use wgpu::AstcBlock::*;
+                let blocks = [B4x4, B5x4, B5x5, B6x5, B6x6, B8x5, B8x6, B8x8, B10x5, B10x6, B10x8, B10x10, B12x10, B12x12];
+                let offset = (value - 157) as usize;
+                let channel = if offset % 2 == 0 { wgpu::AstcChannel::Unorm } else { wgpu::AstcChannel::UnormSrgb };
+                W::Astc { block: blocks[offset / 2], channel }
+            } else {
+                bail!("ktx2 format {:?} is not supported", format);
+            }
+        }
+    };
+    Ok(mapped)
+}
+
+// vulkan format number as used by ktx2, every format a container can give us has one
+pub fn vk_format(format: wgpu::TextureFormat) -> Option<u32> {
+    (1..=184).find(|&value| {
+        ktx2::Format::new(value).and_then(|k| ktx2_format(k).ok()) == Some(format)
+    })
+}
+
+pub fn from_vk_format(value: u32) -> Result<wgpu::TextureFormat> {
+    match ktx2::Format::new(value) {
+        Some(format) => ktx2_format(format),
+        None => bail!("invalid vulkan format {}", value),
+    }
+}
+
+fn parse_dds(bytes: &[u8]) -> Result<CompressedImage> {
+    let dds = ddsfile::Dds::read(bytes).map_err(|err| anyhow!("invalid dds file: {:?}", err))?;
+
+    if dds.get_depth() > 1 || dds.get_num_array_layers() > 1 {
+        bail!("dds volume, array and cubemap textures are not supported");
+    }
+    let format = dds_format(&dds)?;
+    let width = dds.get_width();
+    let height = dds.get_height();
+    let data = dds.get_data(0).map_err(|err| anyhow!("invalid dds data: {:?}", err))?;
+
+    //mips are stored one after another, biggest first
+    let mut levels = Vec::new();
+    let mut offset = 0;
+    for level in 0..dds.get_num_mipmap_levels().max(1) {
+        let size = level_byte_size(format, width, height, level);
+        if offset + size > data.len() {
+            break;
+        }
+        levels.push(data[offset..offset + size].to_vec());
+        offset += size;
+    }
+
+    Ok(CompressedImage { format, width, height, levels })
+}
+
+fn dds_format(dds: &ddsfile::Dds) -> Result<wgpu::TextureFormat> {
+    use ddsfile::{D3DFormat, DxgiFormat};
+    use wgpu::TextureFormat as W;
+
+    if let Some(format) = dds.get_dxgi_format() {
+        let mapped = match format {
+            DxgiFormat::R8G8B8A8_UNorm => W::Rgba8Unorm,
+            DxgiFormat::R8G8B8A8_UNorm_sRGB => W::Rgba8UnormSrgb,
+            DxgiFormat::B8G8R8A8_UNorm => W::Bgra8Unorm,
+            DxgiFormat::B8G8R8A8_UNorm_sRGB => W::Bgra8UnormSrgb,
+            DxgiFormat::BC1_Typeless | DxgiFormat::BC1_UNorm => W::Bc1RgbaUnorm,
+            DxgiFormat::BC1_UNorm_sRGB => W::Bc1RgbaUnormSrgb,
+            DxgiFormat::BC2_Typeless | DxgiFormat::BC2_UNorm => W::Bc2RgbaUnorm,
+            DxgiFormat::BC2_UNorm_sRGB => W::Bc2RgbaUnormSrgb,
+            DxgiFormat::BC3_Typeless | DxgiFormat::BC3_UNorm => W::Bc3RgbaUnorm,
+            DxgiFormat::BC3_UNorm_sRGB => W::Bc3RgbaUnormSrgb,
+            DxgiFormat::BC4_Typeless | DxgiFormat::BC4_UNorm => W::Bc4RUnorm,
+            DxgiFormat::BC4_SNorm => W::Bc4RSnorm,
+            DxgiFormat::BC5_Typeless | DxgiFormat::BC5_UNorm => W::Bc5RgUnorm,
+            DxgiFormat::BC5_SNorm => W::Bc5RgSnorm,
+            DxgiFormat::BC6H_Typeless | DxgiFormat::BC6H_UF16 => W::Bc6hRgbUfloat,
+            DxgiFormat::BC6H_SF16 => W::Bc6hRgbFloat,
+            DxgiFormat::BC7_Typeless | DxgiFormat::BC7_UNorm => W::Bc7RgbaUnorm,
+            DxgiFormat::BC7_UNorm_sRGB => W::Bc7RgbaUnormSrgb,
+            other => bail!("dds format {:?} is not supported", other),
+        };
+        return Ok(mapped);
+    }
+
+    //older files without the dx10 header
+    if let Some(fourcc) = &dds.header.spf.fourcc {
+        match fourcc.0 {
+            ddsfile::FourCC::ATI1 | ddsfile::FourCC::BC4_UNORM => return Ok(W::Bc4RUnorm),
+            ddsfile::FourCC::ATI2 => return Ok(W::Bc5RgUnorm),
+            _ => {}
+        }
+    }
+    match dds.get_d3d_format() {
+        Some(D3DFormat::DXT1) => Ok(W::Bc1RgbaUnorm),
+        Some(D3DFormat::DXT2) | Some(D3DFormat::DXT3) => Ok(W::Bc2RgbaUnorm),
+        Some(D3DFormat::DXT4) | Some(D3DFormat::DXT5) => Ok(W::Bc3RgbaUnorm),
+        Some(D3DFormat::A8B8G8R8) => Ok(W::Rgba8Unorm),
+        Some(D3DFormat::A8R8G8B8) => Ok(W::Bgra8Unorm),
+        other => bail!("dds format {:?} is not supported", other),
+    }
+}
+
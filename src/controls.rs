@@ -0,0 +1,171 @@
+use std::path::Path;
+use anyhow::anyhow;
+use cgmath::{EuclideanSpace, Point3};
+use crate::{loader, picking, Bounds, PickHit, RenderOutputMode, State};
+
+// how much room is left around the bounding sphere when framing a model
+const FRAMING_MARGIN: f32 = 1.05;
+
+// scene controls for code driving the renderer instead of a user, shared by
+// HeadlessRenderer, EmbeddedViewer and the c api
+impl State {
+    pub(crate) fn check_file_type(file_type: &str) -> anyhow::Result<()> {
+        if file_type != "default" && file_type != "opengl" {
+            return Err(anyhow!("unknown file type {:?}, use \"default\" or \"opengl\"", file_type));
+        }
+        Ok(())
+    }
+
+    // replaces whatever was loaded before and blocks until the model and its textures are uploaded.
+    // a model that fails to load leaves the scene empty
+    pub(crate) fn load_model_blocking(&mut self, file_path: &Path) -> anyhow::Result<()> {
+        let result = self.scoped(|state| {
+            state.start_loading(file_path);
+            state.wait_for_model()
+        });
+        if result.is_err() {
+            self.clear_models();
+        }
+        result
+    }
+
+    // loads file_path again into the model at index the way a hot reload does, its entities stay
+    pub(crate) fn reload_model_blocking(&mut self, index: usize, file_path: &Path) -> anyhow::Result<()> {
+        if index >= self.models.len() {
+            return Err(anyhow!("there is no model {}, {} are loaded", index, self.models.len()));
+        }
+        self.model_loader = Some(loader::ModelLoader::spawn(file_path.to_string_lossy().to_string(), self.file_type.clone(), Some(index), &self.texture_cache, self.device.features()));
+        self.model_load_error = None;
+        self.scoped(|state| state.wait_for_model())
+    }
+
+    // replaces whatever was loaded before, the model shows up over the next frames like in the viewer
+    pub(crate) fn start_loading(&mut self, file_path: &Path) {
+        //a load cut short by an earlier error would make open_model ignore this one
+        self.model_loader = None;
+        self.model_load_error = None;
+        self.open_model(file_path, false);
+    }
+
+    // drops every loaded model and the textures only they used
+    pub(crate) fn unload_models(&mut self) {
+        self.model_loader = None;
+        self.clear_models();
+        self.texture_cache.remove_unused();
+    }
+
+    pub(crate) fn set_mode_by_name(&mut self, name: &str) -> anyhow::Result<()> {
+        self.render_output_mode = RenderOutputMode::from_name(name).ok_or_else(|| {
+            let names = RenderOutputMode::ALL.iter().map(|mode| mode.name()).collect::<Vec<_>>();
+            anyhow!("unknown render mode {:?}, use one of {}", name, names.join(", "))
+        })?;
+        Ok(())
+    }
+
+    // world space bounds of every drawn instance of every loaded mesh
+    pub(crate) fn scene_bounds(&self) -> Option<Bounds> {
+        let points = self.models.iter().flat_map(|model| {
+            model.meshes.iter().enumerate().flat_map(move |(index, mesh)| {
+                model.mesh_transforms(index).into_iter().flat_map(move |world| {
+                    mesh.positions.iter().map(move |position| {
+                        let point = world * Point3::from(*position).to_homogeneous();
+                        [point.x, point.y, point.z]
+                    })
+                })
+            })
+        });
+        Bounds::from_points(points)
+    }
+
+    // camera position looking at the bounds centre from far enough away that the whole scene
+    // fits the frame, azimuth around the y axis and elevation above the centre in degrees
+    pub(crate) fn orbit_pose(&self, azimuth: f32, elevation: f32) -> anyhow::Result<([f32; 3], [f32; 3])> {
+        let bounds = self.scene_bounds().ok_or(anyhow!("the model has no vertices to frame"))?;
+        let center = bounds.center();
+        let size = bounds.size();
+        let radius = (size[0] * size[0] + size[1] * size[1] + size[2] * size[2]).sqrt() * 0.5;
+
+        //the bounding sphere has to fit the narrower of the two fields of view
+        let half_fovy = self.projection.fovy().0 * 0.5;
+        let half_fovx = (half_fovy.tan() * self.config.width as f32 / self.config.height as f32).atan();
+        let distance = (radius / half_fovy.min(half_fovx).sin() * FRAMING_MARGIN).max(0.5);
+
+        let (azimuth, elevation) = (azimuth.to_radians(), elevation.to_radians());
+        let position = [
+            center[0] + distance * elevation.cos() * azimuth.cos(),
+            center[1] + distance * elevation.sin(),
+            center[2] + distance * elevation.cos() * azimuth.sin(),
+        ];
+        Ok((position, center))
+    }
+
+    pub(crate) fn look_at(&mut self, position: [f32; 3], target: [f32; 3]) {
+        self.camera.look_at(Point3::from(position), Point3::from(target));
+    }
+
+    // direction the light shines in, it doesn't have to be normalized
+    pub(crate) fn set_directional_light(&mut self, direction: [f32; 3], color: [f32; 3], intensity: f32) {
+        self.directional_light.light_direction = direction;
+        self.directional_light.color = color;
+        self.directional_light.intensity = intensity;
+    }
+
+    pub(crate) fn set_point_light(&mut self, position: [f32; 3], color: [f32; 3], range: f32) {
+        self.movable_light.position = Point3::from(position);
+        self.movable_light_controller.set_light(color.into(), range);
+    }
+
+    // closest mesh under a pixel, x and y from the top left
+    pub(crate) fn pick_at(&self, x: f32, y: f32) -> Option<PickHit> {
+        let ray = picking::ray_from_cursor(&self.camera, &self.projection, x, y, self.config.width, self.config.height)?;
+        picking::pick(&self.models, &ray)
+    }
+
+    pub(crate) fn resize_checked(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        if width == 0 || height == 0 {
+            return Err(anyhow!("size must not be zero, got {}x{}", width, height));
+        }
+        self.scoped(|state| {
+            state.resize(winit::dpi::PhysicalSize::new(width, height));
+            Ok(())
+        })
+    }
+
+    // one frame into an image, the window (if there is one) isn't touched
+    pub(crate) fn render_image(&mut self) -> anyhow::Result<image::RgbaImage> {
+        self.scoped(|state| {
+            //writes the camera and light uniforms
+            state.update(instant::Duration::ZERO);
+            state.render_offscreen()
+        })
+    }
+
+    // one frame into the window surface. a lost or outdated surface is configured again and
+    // the frame skipped, the next one draws normally
+    pub(crate) fn render_frame(&mut self, dt: instant::Duration) -> anyhow::Result<()> {
+        if self.surface.is_none() {
+            return Err(anyhow!("this viewer has no window, render into an image instead"));
+        }
+        self.scoped(|state| {
+            state.update(dt);
+            match state.render() {
+                Ok(()) | Err(wgpu::SurfaceError::Timeout) => Ok(()),
+                Err(wgpu::SurfaceError::Lost) | Err(wgpu::SurfaceError::Outdated) => {
+                    state.configure_surface();
+                    Ok(())
+                }
+                Err(err) => Err(anyhow!("could not draw to the window : {}", err)),
+            }
+        })
+    }
+
+    // wgpu validation errors come back as Err instead of going to the default handler, which panics
+    pub(crate) fn scoped<T>(&mut self, f: impl FnOnce(&mut State) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let result = f(self);
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(err) => Err(anyhow!("{}", err)),
+            None => result,
+        }
+    }
+}
@@ -7,16 +7,26 @@ struct VertexOutput {
     @location(0) uv: vec2<f32>,
 };
 
+//shared with debug_view.wgsl
+struct DebugViewParams {
+    //offset in xy and size in zw of the quad in clip space
+    rect: vec4<f32>,
+    near: f32,
+    far: f32,
+    //linear depth range mapped to black..white
+    range_min: f32,
+    range_max: f32,
+    mode: u32,
+};
+@group(1) @binding(0)
+var<uniform> params: DebugViewParams;
+
 @vertex
 fn vs_main(input: VertexInput) -> VertexOutput {
     var out: VertexOutput;
 
-    // Define quad size and position in clip space
-    let quad_size = vec2<f32>(0.4, 0.4); // Size (e.g., 20% of screen width/height)
-    let quad_offset = vec2<f32>(0.5, 0.5); // Offset to top-right (adjust as needed)
-    
-    // Scale and translate the quad
-    let scaled_pos = input.position * quad_size + quad_offset;
+    // Scale and translate the quad, a corner of the screen or all of it
+    let scaled_pos = input.position * params.rect.zw + params.rect.xy;
 
     out.position = vec4<f32>(scaled_pos, 0.0, 1.0);
     // Flip the v-coordinate to correct upside-down texture
@@ -26,24 +36,30 @@ fn vs_main(input: VertexInput) -> VertexOutput {
 
 //fragment
 @group(0) @binding(0)
-var t_depth: texture_depth_2d;
+var t_depth: texture_2d<f32>;
 @group(0) @binding(1)
 var s_depth: sampler;
 
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    let depth = textureSample(t_depth, s_depth, input.uv);
+    let depth = textureSample(t_depth, s_depth, input.uv).r;
+
+    // Add a black border if UV is near the edge, only when drawn in a corner
+    let border_width = 0.01;
+    if (params.rect.z < 1.0 && (input.uv.x < border_width || input.uv.x > (1.0 - border_width) || input.uv.y < border_width || input.uv.y > (1.0 - border_width))) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
 
-    let near = 0.1;
-    let far = 10000.0;
+    //the shadow map is an orthographic projection, its depth is already linear
+    if (params.mode == 1u) {
+        return vec4<f32>(depth, depth, depth, 1.0);
+    }
+
+    let near = params.near;
+    let far = params.far;
 
     let linear_depth = near * far / (far - depth * ( far - near ));
-    let normalized_depth = linear_depth / far;
+    let normalized_depth = clamp((linear_depth - params.range_min) / (params.range_max - params.range_min), 0.0, 1.0);
 
-    // Add a white border if UV is near the edge
-    let border_width = 0.01;
-    if (input.uv.x < border_width || input.uv.x > (1.0 - border_width) || input.uv.y < border_width || input.uv.y > (1.0 - border_width)) {
-        return vec4<f32>(0.0, 0.0, 0.0, 1.0); // White border
-    }
     return vec4<f32>(normalized_depth, normalized_depth, normalized_depth, 1.0);
-}
\ No newline at end of file
+}
@@ -0,0 +1,172 @@
+// debug shading modes, same inputs as the unlit shader with one fragment entry point per mode
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+struct Params {
+    //texels per world unit that shows up green in the texel density view
+    target_density: f32,
+    //checker cells per uv unit
+    checker_scale: f32,
+};
+@group(2) @binding(0)
+var<uniform> params: Params;
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+};
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    out.tex_coords = model.tex_coords;
+    out.world_normal = normal_matrix * model.normal;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var s_normal: sampler;
+
+//world space normal mapped from -1..1 to 0..1
+@fragment
+fn fs_world_normal(in: VertexOutput) -> @location(0) vec4<f32> {
+    let len = length(in.world_normal);
+    if len == 0.0 {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    return vec4<f32>(in.world_normal / len * 0.5 + 0.5, 1.0);
+}
+
+//raw tangent space sample, a flat normal map shows up as (0.5, 0.5, 1.0)
+@fragment
+fn fs_normal_map(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSample(t_normal, s_normal, in.tex_coords).rgb, 1.0);
+}
+
+//diffuse texture without lighting or tone mapping, for the albedo debug view
+@fragment
+fn fs_albedo(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSample(t_diffuse, s_diffuse, in.tex_coords).rgb, 1.0);
+}
+
+//blended additively with the depth test off, red ends up holding how many times each pixel was shaded
+@fragment
+fn fs_overdraw(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+}
+
+//u in red, v in green, blue marks coordinates outside 0..1 that wrap
+@fragment
+fn fs_uv(in: VertexOutput) -> @location(0) vec4<f32> {
+    let outside = any(in.tex_coords < vec2<f32>(0.0)) || any(in.tex_coords > vec2<f32>(1.0));
+    return vec4<f32>(fract(in.tex_coords), select(0.0, 1.0, outside), 1.0);
+}
+
+//procedural checkerboard in place of the diffuse texture, tinted by the position inside
+//the 0..1 tile so mirrored or rotated islands stand out
+@fragment
+fn fs_checker(in: VertexOutput) -> @location(0) vec4<f32> {
+    let cell = vec2<i32>(floor(in.tex_coords * params.checker_scale));
+    let shade = select(0.85, 0.3, ((cell.x + cell.y) & 1) == 1);
+    let tint = mix(vec3<f32>(1.0), vec3<f32>(fract(in.tex_coords), 1.0), 0.4);
+    return vec4<f32>(shade * tint, 1.0);
+}
+
+fn density_color(ratio: f32) -> vec3<f32> {
+    //a quarter of the target or less is blue, on target green, four times or more red
+    let t = clamp(log2(max(ratio, 1e-6)) * 0.5, -1.0, 1.0);
+    if t < 0.0 {
+        return mix(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.2, 1.0), -t);
+    }
+    return mix(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), t);
+}
+
+//texels of the diffuse texture per world unit compared to params.target_density.
+//uv and world position are both linear over a triangle, so the ratio of their screen
+//derivatives is the ratio of the triangle's uv area to its world area
+@fragment
+fn fs_texel_density(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+    let uv_x = dpdx(in.tex_coords) * size;
+    let uv_y = dpdy(in.tex_coords) * size;
+    let world_x = dpdx(in.world_position);
+    let world_y = dpdy(in.world_position);
+
+    //world derivatives that are parallel mean the triangle is seen edge on, nothing to measure
+    let g = mat2x2<f32>(dot(world_x, world_x), dot(world_x, world_y), dot(world_x, world_y), dot(world_y, world_y));
+    let det_g = determinant(g);
+    if det_g <= 0.0 {
+        return vec4<f32>(0.5, 0.5, 0.5, 1.0);
+    }
+    let g_inv = mat2x2<f32>(g[1][1], -g[0][1], -g[1][0], g[0][0]) * (1.0 / det_g);
+
+    //texels squared per world unit squared along the two principal directions
+    let uv = mat2x2<f32>(uv_x, uv_y);
+    let m = uv * g_inv * transpose(uv);
+    let half_trace = (m[0][0] + m[1][1]) * 0.5;
+    let spread = sqrt(max(half_trace * half_trace - determinant(m), 0.0));
+    let stretch_max = sqrt(half_trace + spread);
+    let stretch_min = sqrt(max(half_trace - spread, 0.0));
+
+    let density = sqrt(stretch_max * stretch_min);
+    var color = density_color(density / params.target_density);
+
+    //uvs stretched more than 2:1 in one direction get dark stripes
+    if stretch_max > 2.0 * stretch_min {
+        let stripe = fract((in.clip_position.x + in.clip_position.y) / 12.0);
+        if stripe < 0.5 {
+            color = color * 0.35;
+        }
+    }
+    return vec4<f32>(color, 1.0);
+}
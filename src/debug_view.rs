@@ -0,0 +1,233 @@
+use wgpu::util::DeviceExt;
+use crate::model;
+use crate::model::Vertex;
+
+// debug views that aren't an existing texture (normals, albedo, lighting, overdraw, shadow cascades)
+// render the scene again into this target, the debug pass then draws it in a corner or over the whole screen
+pub const TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// clip space offset and size of the quad
+const CORNER_RECT: [f32; 4] = [0.5, 0.5, 0.4, 0.4];
+const FULLSCREEN_RECT: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugViewParams {
+    pub rect: [f32; 4],
+    pub near: f32,
+    pub far: f32,
+    pub range_min: f32,
+    pub range_max: f32,
+    pub mode: u32,
+    _padding: [u32; 3],
+}
+
+pub struct DebugViewTarget {
+    pub params: DebugViewParams,
+    pub fullscreen: bool,
+    pub params_bind_group_layout: wgpu::BindGroupLayout,
+    pub params_bind_group: wgpu::BindGroup,
+    pub view_bind_group_layout: wgpu::BindGroupLayout,
+    pub view_bind_group: wgpu::BindGroup,
+    pub color_view: wgpu::TextureView,
+    pub depth_view: wgpu::TextureView,
+    params_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+}
+
+impl DebugViewTarget {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, near: f32, far: f32) -> Self {
+        let params = DebugViewParams {
+            rect: CORNER_RECT,
+            near,
+            far,
+            range_min: near,
+            range_max: far,
+            mode: 0,
+            _padding: [0; 3],
+        };
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Debug View Params Buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let params_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("debug_view_params_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Debug View Params Bind Group"),
+            layout: &params_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+        });
+
+        let view_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("debug_view_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+            ],
+        });
+        //overdraw counts must not be blended between texels
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Debug View Sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let (color_view, depth_view) = create_targets(device, width, height);
+        let view_bind_group = create_view_bind_group(device, &view_bind_group_layout, &color_view, &sampler);
+
+        Self {
+            params,
+            fullscreen: false,
+            params_bind_group_layout,
+            params_bind_group,
+            view_bind_group_layout,
+            view_bind_group,
+            color_view,
+            depth_view,
+            params_buffer,
+            sampler,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let (color_view, depth_view) = create_targets(device, width, height);
+        self.view_bind_group = create_view_bind_group(device, &self.view_bind_group_layout, &color_view, &self.sampler);
+        self.color_view = color_view;
+        self.depth_view = depth_view;
+    }
+
+    pub fn toggle_fullscreen(&mut self, queue: &wgpu::Queue) {
+        self.fullscreen = !self.fullscreen;
+        self.params.rect = if self.fullscreen { FULLSCREEN_RECT } else { CORNER_RECT };
+        self.write_params(queue);
+    }
+
+    pub fn write_params(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[self.params]));
+    }
+}
+
+fn create_targets(device: &wgpu::Device, width: u32, height: u32) -> (wgpu::TextureView, wgpu::TextureView) {
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let color = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Debug View Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: TARGET_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let depth = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Debug View Depth Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Depth32Float,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    (
+        color.create_view(&wgpu::TextureViewDescriptor::default()),
+        depth.create_view(&wgpu::TextureViewDescriptor::default()),
+    )
+}
+
+fn create_view_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, view: &wgpu::TextureView, sampler: &wgpu::Sampler) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Debug View Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+
+// counts every fragment the scene shades: additive blending and no depth test,
+// back faces are still culled like in the regular pipelines
+pub fn create_overdraw_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, shader: wgpu::ShaderModuleDescriptor) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(shader);
+    let additive = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Overdraw Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[model::ModelVertex::desc(), model::InstanceRaw::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_overdraw",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: TARGET_FORMAT,
+                blend: Some(wgpu::BlendState { color: additive, alpha: additive }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        //the pass has a depth attachment for the other views, this pipeline ignores it
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,66 @@
+// draws the offscreen debug view target, the scene rendered with one of the debug fragment shaders
+
+struct VertexInput{
+    @location(0) position: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+//shared with debug_depth.wgsl
+struct DebugViewParams {
+    rect: vec4<f32>,
+    near: f32,
+    far: f32,
+    range_min: f32,
+    range_max: f32,
+    mode: u32,
+};
+@group(1) @binding(0)
+var<uniform> params: DebugViewParams;
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let scaled_pos = input.position * params.rect.zw + params.rect.xy;
+    out.position = vec4<f32>(scaled_pos, 0.0, 1.0);
+    out.uv = vec2<f32>(input.position.x * 0.5 + 0.5, 1.0 - (input.position.y * 0.5 + 0.5));
+    return out;
+}
+
+@group(0) @binding(0)
+var t_view: texture_2d<f32>;
+@group(0) @binding(1)
+var s_view: sampler;
+
+//1 layer dark blue, 2 green, 4 yellow, 8 or more red
+fn overdraw_color(count: f32) -> vec3<f32> {
+    if count < 0.5 {
+        return vec3<f32>(0.0);
+    }
+    let t = clamp(log2(count) / 3.0, 0.0, 1.0) * 3.0;
+    if t < 1.0 {
+        return mix(vec3<f32>(0.0, 0.1, 0.5), vec3<f32>(0.0, 0.9, 0.0), t);
+    }
+    if t < 2.0 {
+        return mix(vec3<f32>(0.0, 0.9, 0.0), vec3<f32>(1.0, 1.0, 0.0), t - 1.0);
+    }
+    return mix(vec3<f32>(1.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), t - 2.0);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_view, s_view, input.uv);
+
+    let border_width = 0.01;
+    if (params.rect.z < 1.0 && (input.uv.x < border_width || input.uv.x > (1.0 - border_width) || input.uv.y < border_width || input.uv.y > (1.0 - border_width))) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    if (params.mode == 3u) {
+        return vec4<f32>(overdraw_color(color.r), 1.0);
+    }
+    return vec4<f32>(color.rgb, 1.0);
+}
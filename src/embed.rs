@@ -0,0 +1,190 @@
+use std::path::Path;
+use image::RgbaImage;
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle};
+use winit::event::WindowEvent;
+use crate::{Attachment, Bounds, Entity, PickHit, State, Transform};
+
+// the window and display a surface is created for. built from anything with raw handles
+// (a winit window, sdl, glfw...) or filled in by hand from the handles a host hands over
+#[derive(Clone, Copy)]
+pub struct RawWindow {
+    pub window: RawWindowHandle,
+    pub display: RawDisplayHandle,
+}
+
+impl RawWindow {
+    pub fn of<W: HasRawWindowHandle + HasRawDisplayHandle>(window: &W) -> Self {
+        Self {
+            window: window.raw_window_handle(),
+            display: window.raw_display_handle(),
+        }
+    }
+}
+
+unsafe impl HasRawWindowHandle for RawWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.window
+    }
+}
+
+unsafe impl HasRawDisplayHandle for RawWindow {
+    fn raw_display_handle(&self) -> RawDisplayHandle {
+        self.display
+    }
+}
+
+// the viewer inside a window and event loop the host owns. the host forwards window events
+// and calls render_frame whenever it wants a frame, nothing here blocks or takes over the thread
+pub struct EmbeddedViewer {
+    state: State,
+    last_frame: instant::Instant,
+}
+
+impl EmbeddedViewer {
+    // width and height are the window's inner size in pixels, call resize when it changes.
+    //
+    // safety: the window behind the handles has to stay open until the viewer is dropped
+    pub unsafe fn new(window: RawWindow, width: u32, height: u32, file_type: &str, use_hdr: bool) -> anyhow::Result<Self> {
+        State::check_file_type(file_type)?;
+        if width == 0 || height == 0 {
+            return Err(anyhow::anyhow!("window size must not be zero, got {}x{}", width, height));
+        }
+        let state = pollster::block_on(State::new_embedded(window, width, height, file_type.to_string(), use_hdr))?;
+        Ok(Self {
+            state,
+            last_frame: instant::Instant::now(),
+        })
+    }
+
+    // replaces the scene, loads in the background with the progress bar like the viewer does
+    pub fn load_model(&mut self, file_path: &Path) {
+        self.state.start_loading(file_path);
+    }
+
+    // blocks until the model and its textures are uploaded
+    pub fn load_model_blocking(&mut self, file_path: &Path) -> anyhow::Result<()> {
+        self.state.load_model_blocking(file_path)
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.state.model_loader.is_some()
+    }
+
+    // why the last background load failed, cleared once taken
+    pub fn take_load_error(&mut self) -> Option<String> {
+        self.state.model_load_error.take()
+    }
+
+    pub fn unload_model(&mut self) {
+        self.state.unload_models();
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        self.state.resize_checked(width, height)
+    }
+
+    // camera keys, mouse buttons, scroll and the other viewer shortcuts. also handles Resized and
+    // dropped models or textures, returns true when the viewer used the event
+    pub fn handle_window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::DroppedFile(file_path) => {
+                self.state.open_dropped_file(file_path);
+                true
+            }
+            WindowEvent::Resized(size) => {
+                self.state.resize(*size);
+                true
+            }
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                self.state.resize(**new_inner_size);
+                true
+            }
+            _ => self.state.input(event),
+        }
+    }
+
+    // DeviceEvent::MouseMotion, turns the camera while the right mouse button is held
+    pub fn handle_mouse_motion(&mut self, delta_x: f64, delta_y: f64) {
+        if self.state.left_mouse_pressed {
+            self.state.mouse_x = delta_x as u32;
+            self.state.mouse_y = delta_y as u32;
+        }
+        self.state.camera_controller.process_mouse(delta_x, delta_y);
+    }
+
+    pub fn set_mode(&mut self, name: &str) -> anyhow::Result<()> {
+        self.state.set_mode_by_name(name)
+    }
+
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.state.scene_bounds()
+    }
+
+    pub fn look_at(&mut self, position: [f32; 3], target: [f32; 3]) {
+        self.state.look_at(position, target);
+    }
+
+    // points the camera at the scene from far enough away that all of it is visible
+    pub fn frame_model(&mut self, azimuth: f32, elevation: f32) -> anyhow::Result<()> {
+        let (position, target) = self.state.orbit_pose(azimuth, elevation)?;
+        self.state.look_at(position, target);
+        Ok(())
+    }
+
+    // the scene graph, each newly loaded model starts out with a model entity at the origin and a child per mesh
+    pub fn entities(&self) -> &[Entity] {
+        &self.state.entities
+    }
+
+    pub fn spawn_entity(&mut self, model: usize, transform: Transform) -> anyhow::Result<u32> {
+        self.state.spawn_entity(model, transform)
+    }
+
+    pub fn set_entity_transform(&mut self, id: u32, transform: Transform) -> anyhow::Result<()> {
+        self.state.set_entity_transform(id, transform)
+    }
+
+    // an entity below parent (or at the top of the tree), a model brings a child per mesh along
+    pub fn add_entity(&mut self, parent: Option<u32>, attachment: Attachment, transform: Transform) -> anyhow::Result<u32> {
+        self.state.add_entity(parent, attachment, transform)
+    }
+
+    // the transform is kept and is relative to the new parent from then on
+    pub fn set_entity_parent(&mut self, id: u32, parent: Option<u32>) -> anyhow::Result<()> {
+        self.state.set_entity_parent(id, parent)
+    }
+
+    // hides or shows the entity with everything below it
+    pub fn set_entity_visible(&mut self, id: u32, visible: bool) -> anyhow::Result<()> {
+        self.state.set_entity_visible(id, visible)
+    }
+
+    // removes everything below it too
+    pub fn remove_entity(&mut self, id: u32) -> anyhow::Result<()> {
+        self.state.remove_entity(id)
+    }
+
+    pub fn set_directional_light(&mut self, direction: [f32; 3], color: [f32; 3], intensity: f32) {
+        self.state.set_directional_light(direction, color, intensity);
+    }
+
+    pub fn set_point_light(&mut self, position: [f32; 3], color: [f32; 3], range: f32) {
+        self.state.set_point_light(position, color, range);
+    }
+
+    pub fn pick(&self, x: f32, y: f32) -> Option<PickHit> {
+        self.state.pick_at(x, y)
+    }
+
+    // draws and presents one frame, camera movement uses the time since the previous one
+    pub fn render_frame(&mut self) -> anyhow::Result<()> {
+        let dt = self.last_frame.elapsed();
+        self.last_frame = instant::Instant::now();
+        self.state.render_frame(dt)
+    }
+
+    // the current view as an image, the window isn't touched
+    pub fn render_image(&mut self) -> anyhow::Result<RgbaImage> {
+        self.state.render_image()
+    }
+}
@@ -1,23 +1,438 @@
+// the scene graph. every entity is a node with a transform relative to its parent, its children and
+// optionally something attached to it. only mesh entities get drawn: their world matrices become the
+// instances of that mesh, so a model is uploaded once however many times it's placed. a model entity
+// gets a child per mesh of its model (the g/o groups of an obj) so they can be moved or hidden on
+// their own. a newly loaded model starts out with one model entity at the origin
+use std::collections::{HashMap, HashSet};
+use cgmath::{Deg, Euler, EuclideanSpace, Matrix, Matrix3, Matrix4, One, Point3, Quaternion, SquareMatrix, Vector3};
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+use crate::{model, State};
+
 pub struct Entity {
-    pub model: model::Model,
-    pub position: Point3<f32>,
-    pub rotation: Point3<f32>,
-    pub scale: Point3<f32>,
+    pub id: u32,
+    pub name: String,
+    pub parent: Option<u32>,
+    pub children: Vec<u32>,
+    pub attachment: Attachment,
+    // a hidden entity hides everything below it too
+    pub visible: bool,
+    // relative to the parent
+    pub position: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+    // as of the last sync, the parent's world matrix times the local one and whether it and
+    // every entity above it are visible
+    pub world: Matrix4<f32>,
+    pub world_visible: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Attachment {
+    // an empty entity for grouping others
+    None,
+    // a placed model, its meshes hang below it
+    Model { model: usize },
+    // one mesh of a model, the only kind that gets drawn
+    Mesh { model: usize, mesh: usize },
+    // moves the point light to the entity's world position
+    PointLight,
+}
+
+impl Attachment {
+    pub fn model(&self) -> Option<usize> {
+        match *self {
+            Attachment::Model { model } | Attachment::Mesh { model, .. } => Some(model),
+            Attachment::None | Attachment::PointLight => None,
+        }
+    }
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct EntityUniform {
+// position, rotation as euler angles in degrees (x, then y, then z) and scale,
+// how the controls and the remote server describe an entity
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Transform {
     pub position: [f32; 3],
-    pub _padding: u32,
     pub rotation: [f32; 3],
-    pub _padding: u32,
     pub scale: [f32; 3],
-    pub _padding: u32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self { position: [0.0; 3], rotation: [0.0; 3], scale: [1.0; 3] }
+    }
 }
 
 impl Entity {
-    pub fn new<V: Into<Point3<f32>>, R: Into<Point3<f32>,>>(){
+    pub fn new(id: u32, name: String, attachment: Attachment, transform: Transform) -> Self {
+        let mut entity = Self {
+            id,
+            name,
+            parent: None,
+            children: Vec::new(),
+            attachment,
+            visible: true,
+            position: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            world: Matrix4::identity(),
+            world_visible: true,
+        };
+        entity.set_transform(transform);
+        entity.world = entity.local_matrix();
+        entity
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        let [x, y, z] = transform.rotation;
+        self.position = transform.position.into();
+        self.rotation = Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z)));
+        self.scale = transform.scale.into();
+    }
+
+    pub fn transform(&self) -> Transform {
+        let euler = Euler::from(self.rotation);
+        Transform {
+            position: self.position.into(),
+            rotation: [Deg::from(euler.x).0, Deg::from(euler.y).0, Deg::from(euler.z).0],
+            scale: self.scale.into(),
+        }
+    }
+
+    pub fn local_matrix(&self) -> Matrix4<f32> {
+        model::Instance { position: self.position, rotation: self.rotation, scale: self.scale }.to_raw().model.into()
+    }
+}
+
+impl State {
+    pub(crate) fn entity(&self, id: u32) -> anyhow::Result<&Entity> {
+        self.entities.iter().find(|entity| entity.id == id).ok_or_else(|| anyhow::anyhow!("no entity {}", id))
+    }
+
+    pub(crate) fn entity_mut(&mut self, id: u32) -> anyhow::Result<&mut Entity> {
+        self.entities.iter_mut().find(|entity| entity.id == id).ok_or_else(|| anyhow::anyhow!("no entity {}", id))
+    }
+
+    // places model (an index into the loaded models) once more at the top of the tree
+    pub(crate) fn spawn_entity(&mut self, model: usize, transform: Transform) -> anyhow::Result<u32> {
+        self.add_entity(None, Attachment::Model { model }, transform)
+    }
+
+    // a model attachment brings a child per mesh along
+    pub(crate) fn add_entity(&mut self, parent: Option<u32>, attachment: Attachment, transform: Transform) -> anyhow::Result<u32> {
+        let name = match attachment {
+            Attachment::None => "group".to_string(),
+            Attachment::Model { model } => {
+                if model >= self.models.len() {
+                    return Err(anyhow::anyhow!("there is no model {}, {} are loaded", model, self.models.len()));
+                }
+                format!("model {}", model)
+            }
+            Attachment::Mesh { model, mesh } => {
+                let meshes = &self.models.get(model).ok_or_else(|| anyhow::anyhow!("there is no model {}, {} are loaded", model, self.models.len()))?.meshes;
+                meshes.get(mesh).ok_or_else(|| anyhow::anyhow!("model {} has no mesh {}, it has {}", model, mesh, meshes.len()))?.name.clone()
+            }
+            Attachment::PointLight => "point light".to_string(),
+        };
+        if let Some(parent) = parent {
+            self.entity(parent)?;
+        }
+        let id = self.push_entity(parent, Entity::new(0, name, attachment, transform));
+        if let Attachment::Model { .. } = attachment {
+            self.attach_meshes(id);
+        }
+        self.entities_dirty = true;
+        self.sync_entities();
+        Ok(id)
+    }
+
+    fn push_entity(&mut self, parent: Option<u32>, mut entity: Entity) -> u32 {
+        let id = self.next_entity_id;
+        self.next_entity_id += 1;
+        entity.id = id;
+        entity.parent = parent;
+        if let Some(parent) = parent.and_then(|parent| self.entities.iter_mut().find(|entity| entity.id == parent)) {
+            parent.children.push(id);
+        }
+        self.entities.push(entity);
+        id
+    }
+
+    pub(crate) fn set_entity_transform(&mut self, id: u32, transform: Transform) -> anyhow::Result<()> {
+        self.entity_mut(id)?.set_transform(transform);
+        self.entities_dirty = true;
+        self.sync_entities();
+        Ok(())
+    }
+
+    pub(crate) fn set_entity_visible(&mut self, id: u32, visible: bool) -> anyhow::Result<()> {
+        self.entity_mut(id)?.visible = visible;
+        self.entities_dirty = true;
+        self.sync_entities();
+        Ok(())
+    }
 
+    // moves an entity with everything below it under another one, or to the top with None.
+    // the local transform is kept, so it's now relative to the new parent
+    pub(crate) fn set_entity_parent(&mut self, id: u32, parent: Option<u32>) -> anyhow::Result<()> {
+        self.entity(id)?;
+        if let Some(parent) = parent {
+            self.entity(parent)?;
+            if parent == id || self.descendants(id).contains(&parent) {
+                return Err(anyhow::anyhow!("entity {} is below {}, it can't become its parent", parent, id));
+            }
+        }
+        self.detach(id);
+        if let Some(parent) = parent {
+            self.entity_mut(parent)?.children.push(id);
+        }
+        self.entity_mut(id)?.parent = parent;
+        self.entities_dirty = true;
+        self.sync_entities();
+        Ok(())
     }
-}
\ No newline at end of file
+
+    // removes the entity with everything below it, the models stay loaded even when nothing places them anymore
+    pub(crate) fn remove_entity(&mut self, id: u32) -> anyhow::Result<()> {
+        self.entity(id)?;
+        self.remove_subtree(id);
+        self.entities_dirty = true;
+        self.sync_entities();
+        Ok(())
+    }
+
+    fn remove_subtree(&mut self, id: u32) {
+        self.detach(id);
+        let mut removed = self.descendants(id).into_iter().collect::<HashSet<_>>();
+        removed.insert(id);
+        self.entities.retain(|entity| !removed.contains(&entity.id));
+    }
+
+    fn detach(&mut self, id: u32) {
+        let parent = self.entities.iter().find(|entity| entity.id == id).and_then(|entity| entity.parent);
+        if let Some(parent) = parent.and_then(|parent| self.entities.iter_mut().find(|entity| entity.id == parent)) {
+            parent.children.retain(|child| *child != id);
+        }
+    }
+
+    fn descendants(&self, id: u32) -> Vec<u32> {
+        let mut found = Vec::new();
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            if let Some(entity) = self.entities.iter().find(|entity| entity.id == id) {
+                found.extend(&entity.children);
+                pending.extend(&entity.children);
+            }
+        }
+        found
+    }
+
+    // gives a model entity a child for every mesh of its model that nothing below it draws yet
+    fn attach_meshes(&mut self, id: u32) {
+        let Ok(Attachment::Model { model }) = self.entity(id).map(|entity| entity.attachment) else {
+            return;
+        };
+        let present = self
+            .descendants(id)
+            .into_iter()
+            .filter_map(|child| match self.entity(child).map(|entity| entity.attachment) {
+                Ok(Attachment::Mesh { model: child_model, mesh }) if child_model == model => Some(mesh),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        let missing = self.models[model]
+            .meshes
+            .iter()
+            .enumerate()
+            .filter(|(mesh, _)| !present.contains(mesh))
+            .map(|(mesh, data)| (mesh, data.name.clone()))
+            .collect::<Vec<_>>();
+        for (mesh, name) in missing {
+            self.push_entity(Some(id), Entity::new(0, name, Attachment::Mesh { model, mesh }, Transform::default()));
+        }
+    }
+
+    // after a model finished loading or was reloaded its groups may have changed, mesh entities
+    // whose mesh is gone are dropped and new meshes get an entity below every model entity
+    pub(crate) fn attach_mesh_entities(&mut self) {
+        let stale = self
+            .entities
+            .iter()
+            .filter(|entity| match entity.attachment {
+                Attachment::Mesh { model, mesh } => self.models.get(model).map_or(true, |model| mesh >= model.meshes.len()),
+                _ => false,
+            })
+            .map(|entity| entity.id)
+            .collect::<Vec<_>>();
+        for id in stale {
+            self.remove_subtree(id);
+        }
+        for entity in &mut self.entities {
+            if let Attachment::Mesh { model, mesh } = entity.attachment {
+                entity.name = self.models[model].meshes[mesh].name.clone();
+            }
+        }
+        let model_entities = self
+            .entities
+            .iter()
+            .filter(|entity| matches!(entity.attachment, Attachment::Model { .. }))
+            .map(|entity| entity.id)
+            .collect::<Vec<_>>();
+        for id in model_entities {
+            self.attach_meshes(id);
+        }
+        self.entities_dirty = true;
+    }
+
+    // a mesh was taken out of a model, the meshes after it moved down one index
+    pub(crate) fn mesh_removed(&mut self, model: usize, mesh: usize) {
+        let removed = self.entities.iter().filter(|entity| entity.attachment == Attachment::Mesh { model, mesh }).map(|entity| entity.id).collect::<Vec<_>>();
+        for id in removed {
+            self.remove_subtree(id);
+        }
+        for entity in &mut self.entities {
+            if let Attachment::Mesh { model: entity_model, mesh: entity_mesh } = &mut entity.attachment {
+                if *entity_model == model && *entity_mesh > mesh {
+                    *entity_mesh -= 1;
+                }
+            }
+        }
+        self.entities_dirty = true;
+        self.sync_entities();
+    }
+
+    // mesh entity drawn as the given instance of a mesh, for turning pick hits into entities
+    pub(crate) fn entity_at(&self, model: usize, mesh: usize, instance: usize) -> Option<u32> {
+        let attachment = Attachment::Mesh { model, mesh };
+        self.entities.iter().filter(|entity| entity.world_visible && entity.attachment == attachment).nth(instance).map(|entity| entity.id)
+    }
+
+    // drops every model and everything placing them
+    pub(crate) fn clear_models(&mut self) {
+        self.models.clear();
+        self.entities.clear();
+        self.entity_model_count = 0;
+    }
+
+    // places models that showed up since the last call, then when anything changed propagates the
+    // world matrices down the tree and rewrites the instance buffers. runs at the start of every update
+    pub(crate) fn sync_entities(&mut self) {
+        let model_count = self.models.len();
+        if model_count < self.entity_model_count {
+            let stale = self.entities.iter().filter(|entity| entity.attachment.model().is_some_and(|model| model >= model_count)).map(|entity| entity.id).collect::<Vec<_>>();
+            for id in stale {
+                self.remove_subtree(id);
+            }
+            self.entity_model_count = model_count;
+            self.entities_dirty = true;
+        }
+        for model in self.entity_model_count..model_count {
+            let id = self.push_entity(None, Entity::new(0, format!("model {}", model), Attachment::Model { model }, Transform::default()));
+            self.attach_meshes(id);
+            self.entities_dirty = true;
+        }
+        self.entity_model_count = model_count;
+        if !self.entities_dirty {
+            return;
+        }
+        self.entities_dirty = false;
+
+        self.update_world_matrices();
+        for entity in &self.entities {
+            if entity.world_visible && entity.attachment == Attachment::PointLight {
+                self.movable_light.position = Point3::from_vec(entity.world.w.truncate());
+            }
+        }
+
+        //visible mesh entities in tree order, grouped by the mesh they draw
+        let mut mesh_instances: HashMap<(usize, usize), Vec<model::InstanceRaw>> = HashMap::new();
+        for entity in &self.entities {
+            if let (true, Attachment::Mesh { model, mesh }) = (entity.world_visible, entity.attachment) {
+                mesh_instances.entry((model, mesh)).or_default().push(instance_raw(entity.world));
+            }
+        }
+        for (index, model) in self.models.iter_mut().enumerate() {
+            let mut instance_data = Vec::new();
+            let mut ranges = Vec::new();
+            for mesh in 0..model.meshes.len() {
+                let start = instance_data.len() as u32;
+                instance_data.extend(mesh_instances.remove(&(index, mesh)).unwrap_or_default());
+                ranges.push(start..instance_data.len() as u32);
+            }
+            let bytes: &[u8] = bytemuck::cast_slice(&instance_data);
+            //the buffer only grows, an empty slice can't be bound so the old one stays for zero instances
+            if bytes.len() as u64 > model.instance_buffer.size() {
+                model.instance_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Instance Buffer"),
+                    contents: bytes,
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                });
+            } else if !bytes.is_empty() {
+                self.queue.write_buffer(&model.instance_buffer, 0, bytes);
+            }
+            model.instance_num = instance_data.len() as i32;
+            model.instance_data = instance_data;
+            model.mesh_instances = ranges;
+        }
+    }
+
+    // walks down from every entity without a parent
+    fn update_world_matrices(&mut self) {
+        let index_of = self.entities.iter().enumerate().map(|(index, entity)| (entity.id, index)).collect::<HashMap<_, _>>();
+        let mut pending = self
+            .entities
+            .iter()
+            .enumerate()
+            .filter(|(_, entity)| entity.parent.is_none())
+            .map(|(index, _)| (index, Matrix4::identity(), true))
+            .collect::<Vec<_>>();
+        while let Some((index, parent_world, parent_visible)) = pending.pop() {
+            let entity = &mut self.entities[index];
+            entity.world = parent_world * entity.local_matrix();
+            entity.world_visible = parent_visible && entity.visible;
+            let (world, visible) = (entity.world, entity.world_visible);
+            pending.extend(entity.children.iter().filter_map(|child| index_of.get(child)).map(|&child| (child, world, visible)));
+        }
+    }
+}
+
+// entities for the instances models were created with, a model entity per instance with its meshes below it
+pub(crate) fn entities_from_instances(models: &[model::Model]) -> Vec<Entity> {
+    let mut entities: Vec<Entity> = Vec::new();
+    for (index, model) in models.iter().enumerate() {
+        for instance in &model.instances {
+            let id = entities.len() as u32;
+            let mut entity = Entity::new(id, format!("model {}", index), Attachment::Model { model: index }, Transform::default());
+            entity.position = instance.position;
+            entity.rotation = instance.rotation;
+            entity.scale = instance.scale;
+            entity.world = entity.local_matrix();
+            let world = entity.world;
+            entities.push(entity);
+            for (mesh, data) in model.meshes.iter().enumerate() {
+                let child_id = entities.len() as u32;
+                let mut child = Entity::new(child_id, data.name.clone(), Attachment::Mesh { model: index, mesh }, Transform::default());
+                child.parent = Some(id);
+                child.world = world;
+                entities[id as usize].children.push(child_id);
+                entities.push(child);
+            }
+        }
+    }
+    entities
+}
+
+pub(crate) fn instance_raw(world: Matrix4<f32>) -> model::InstanceRaw {
+    let linear = Matrix3::from_cols(world.x.truncate(), world.y.truncate(), world.z.truncate());
+    //a zero scale has no inverse, nothing of it is visible anyway
+    let normal = linear.invert().map(|inverse| inverse.transpose()).unwrap_or(linear);
+    model::InstanceRaw { model: world.into(), normal: normal.into(), _padding: 0 }
+}
+
+// the normal matrix is the inverse transpose of rotation * scale, which for a pure rotation
+// is the rotation itself, so only the scale gets inverted
+pub(crate) fn normal_matrix(rotation: Quaternion<f32>, scale: Vector3<f32>) -> Matrix3<f32> {
+    let inverse = |value: f32| if value != 0.0 { 1.0 / value } else { 0.0 };
+    Matrix3::from(rotation) * Matrix3::from_diagonal(Vector3::new(inverse(scale.x), inverse(scale.y), inverse(scale.z)))
+}
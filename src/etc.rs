@@ -0,0 +1,305 @@
+// software decoders for etc2 and eac blocks, used when the adapter has no etc2 support
+// or the texture size isn't a multiple of the block size
+
+type Block = [[u8; 4]; 16];
+
+pub fn can_decompress(format: wgpu::TextureFormat) -> bool {
+    use wgpu::TextureFormat::*;
+    matches!(
+        format,
+        Etc2Rgb8Unorm | Etc2Rgb8UnormSrgb
+            | Etc2Rgb8A1Unorm | Etc2Rgb8A1UnormSrgb
+            | Etc2Rgba8Unorm | Etc2Rgba8UnormSrgb
+            | EacR11Unorm | EacR11Snorm
+            | EacRg11Unorm | EacRg11Snorm
+    )
+}
+
+// decode one 4x4 block into row major rgba8 pixels, the eac snorm formats come out as rgba8 snorm
+pub fn decode_block(format: wgpu::TextureFormat, data: &[u8], out: &mut [u8]) {
+    use wgpu::TextureFormat::*;
+    let block = match format {
+        Etc2Rgb8Unorm | Etc2Rgb8UnormSrgb => decode_etc2_rgb(data, false),
+        Etc2Rgb8A1Unorm | Etc2Rgb8A1UnormSrgb => decode_etc2_rgb(data, true),
+        Etc2Rgba8Unorm | Etc2Rgba8UnormSrgb => {
+            let mut block = decode_etc2_rgb(&data[8..16], false);
+            for (pixel, alpha) in block.iter_mut().zip(decode_eac_alpha(&data[0..8])) {
+                pixel[3] = alpha;
+            }
+            block
+        }
+        EacR11Unorm | EacRg11Unorm => {
+            let red = decode_eac_r11(&data[0..8]);
+            let green = if format == EacRg11Unorm { decode_eac_r11(&data[8..16]) } else { [0; 16] };
+            let mut block = [[0u8; 4]; 16];
+            for (i, pixel) in block.iter_mut().enumerate() {
+                *pixel = [red[i], green[i], 0, 255];
+            }
+            block
+        }
+        _ => {
+            let red = decode_eac_r11_snorm(&data[0..8]);
+            let green = if format == EacRg11Snorm { decode_eac_r11_snorm(&data[8..16]) } else { [0; 16] };
+            let mut block = [[0u8; 4]; 16];
+            for (i, pixel) in block.iter_mut().enumerate() {
+                *pixel = [red[i] as u8, green[i] as u8, 0, 127];
+            }
+            block
+        }
+    };
+    for (pixel, color) in block.iter().enumerate() {
+        out[pixel * 4..pixel * 4 + 4].copy_from_slice(color);
+    }
+}
+
+const ETC1_MODIFIERS: [[i32; 2]; 8] = [[2, 8], [5, 17], [9, 29], [13, 42], [18, 60], [24, 80], [33, 106], [47, 183]];
+const ETC2_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+// bits count from the most significant bit of the big endian block, 63 is the first bit
+fn bits(block: u64, high: u32, low: u32) -> i32 {
+    ((block >> low) & ((1 << (high - low + 1)) - 1)) as i32
+}
+
+fn extend4(value: i32) -> i32 {
+    (value << 4) | value
+}
+
+fn extend5(value: i32) -> i32 {
+    (value << 3) | (value >> 2)
+}
+
+fn add(color: [i32; 3], amount: i32) -> [u8; 4] {
+    [
+        (color[0] + amount).clamp(0, 255) as u8,
+        (color[1] + amount).clamp(0, 255) as u8,
+        (color[2] + amount).clamp(0, 255) as u8,
+        255,
+    ]
+}
+
+// the 2 bit index of a pixel, pixels are numbered down the columns
+fn pixel_index(block: u64, x: usize, y: usize) -> usize {
+    let pixel = x * 4 + y;
+    ((((block >> (pixel + 16)) & 1) << 1) | ((block >> pixel) & 1)) as usize
+}
+
+// punchthrough blocks use the differential bit as an opaque flag, when it's clear index 2
+// is transparent black and the small modifiers become 0
+fn decode_etc2_rgb(data: &[u8], punchthrough: bool) -> Block {
+    let block = u64::from_be_bytes(data[0..8].try_into().unwrap());
+    let differential = bits(block, 33, 33) == 1;
+    let flip = bits(block, 32, 32) == 1;
+    let opaque = !punchthrough || differential;
+    let transparent = |index: usize| !opaque && index == 2;
+
+    let mut out = [[0u8; 4]; 16];
+    let (c1, c2) = if !punchthrough && !differential {
+        (
+            [extend4(bits(block, 63, 60)), extend4(bits(block, 55, 52)), extend4(bits(block, 47, 44))],
+            [extend4(bits(block, 59, 56)), extend4(bits(block, 51, 48)), extend4(bits(block, 43, 40))],
+        )
+    } else {
+        let r = bits(block, 63, 59);
+        let g = bits(block, 55, 51);
+        let b = bits(block, 47, 43);
+        let r2 = r + ((bits(block, 58, 56) << 29) >> 29);
+        let g2 = g + ((bits(block, 50, 48) << 29) >> 29);
+        let b2 = b + ((bits(block, 42, 40) << 29) >> 29);
+
+        let paint = if !(0..32).contains(&r2) {
+            //t mode
+            let c1 = [
+                extend4((bits(block, 60, 59) << 2) | bits(block, 57, 56)),
+                extend4(bits(block, 55, 52)),
+                extend4(bits(block, 51, 48)),
+            ];
+            let c2 = [extend4(bits(block, 47, 44)), extend4(bits(block, 43, 40)), extend4(bits(block, 39, 36))];
+            let distance = ETC2_DISTANCES[((bits(block, 35, 34) << 1) | bits(block, 32, 32)) as usize];
+            Some([add(c1, 0), add(c2, distance), add(c2, 0), add(c2, -distance)])
+        } else if !(0..32).contains(&g2) {
+            //h mode
+            let r1 = bits(block, 62, 59);
+            let g1 = (bits(block, 58, 56) << 1) | bits(block, 52, 52);
+            let b1 = (bits(block, 51, 51) << 3) | bits(block, 49, 47);
+            let (r2, g2, b2) = (bits(block, 46, 43), bits(block, 42, 39), bits(block, 38, 35));
+            let order = ((r1 << 8) | (g1 << 4) | b1) >= ((r2 << 8) | (g2 << 4) | b2);
+            let index = (bits(block, 34, 34) << 2) | (bits(block, 32, 32) << 1) | order as i32;
+            let distance = ETC2_DISTANCES[index as usize];
+            let c1 = [extend4(r1), extend4(g1), extend4(b1)];
+            let c2 = [extend4(r2), extend4(g2), extend4(b2)];
+            Some([add(c1, distance), add(c1, -distance), add(c2, distance), add(c2, -distance)])
+        } else if !(0..32).contains(&b2) {
+            //planar mode, always opaque
+            let extend6 = |value: i32| (value << 2) | (value >> 4);
+            let extend7 = |value: i32| (value << 1) | (value >> 6);
+            let origin = [
+                extend6(bits(block, 62, 57)),
+                extend7((bits(block, 56, 56) << 6) | bits(block, 54, 49)),
+                extend6((bits(block, 48, 48) << 5) | (bits(block, 44, 43) << 3) | bits(block, 41, 39)),
+            ];
+            let horizontal = [
+                extend6((bits(block, 38, 34) << 1) | bits(block, 32, 32)),
+                extend7(bits(block, 31, 25)),
+                extend6(bits(block, 24, 19)),
+            ];
+            let vertical = [extend6(bits(block, 18, 13)), extend7(bits(block, 12, 6)), extend6(bits(block, 5, 0))];
+            for y in 0..4 {
+                for x in 0..4 {
+                    let pixel = &mut out[y * 4 + x];
+                    for c in 0..3 {
+                        let value = (x as i32 * (horizontal[c] - origin[c]) + y as i32 * (vertical[c] - origin[c]) + 4 * origin[c] + 2) >> 2;
+                        pixel[c] = value.clamp(0, 255) as u8;
+                    }
+                    pixel[3] = 255;
+                }
+            }
+            return out;
+        } else {
+            None
+        };
+
+        if let Some(paint) = paint {
+            for y in 0..4 {
+                for x in 0..4 {
+                    let index = pixel_index(block, x, y);
+                    out[y * 4 + x] = if transparent(index) { [0; 4] } else { paint[index] };
+                }
+            }
+            return out;
+        }
+        ([extend5(r), extend5(g), extend5(b)], [extend5(r2), extend5(g2), extend5(b2)])
+    };
+
+    //individual and differential modes, two subblocks with their own base color and table
+    let tables = [bits(block, 39, 37) as usize, bits(block, 36, 34) as usize];
+    for y in 0..4 {
+        for x in 0..4 {
+            let second = if flip { y >= 2 } else { x >= 2 };
+            let base = if second { c2 } else { c1 };
+            let [small, large] = ETC1_MODIFIERS[tables[second as usize]];
+            let index = pixel_index(block, x, y);
+            out[y * 4 + x] = if transparent(index) {
+                [0; 4]
+            } else {
+                let modifier = match index {
+                    0 if !opaque => 0,
+                    0 => small,
+                    1 => large,
+                    2 => -small,
+                    _ => -large,
+                };
+                add(base, modifier)
+            };
+        }
+    }
+    out
+}
+
+// base codeword, multiplier and the modifier of every pixel in row major order
+fn decode_eac(data: &[u8]) -> (i32, i32, [i32; 16]) {
+    let block = u64::from_be_bytes(data[0..8].try_into().unwrap());
+    let table = &EAC_MODIFIERS[bits(block, 51, 48) as usize];
+    let mut modifiers = [0i32; 16];
+    for y in 0..4 {
+        for x in 0..4 {
+            let pixel = (x * 4 + y) as u32;
+            modifiers[y * 4 + x] = table[bits(block, 47 - 3 * pixel, 45 - 3 * pixel) as usize];
+        }
+    }
+    (bits(block, 63, 56), bits(block, 55, 52), modifiers)
+}
+
+fn decode_eac_alpha(data: &[u8]) -> [u8; 16] {
+    let (base, multiplier, modifiers) = decode_eac(data);
+    modifiers.map(|modifier| (base + modifier * multiplier).clamp(0, 255) as u8)
+}
+
+// 11 bit values rounded down to 8 bits
+fn decode_eac_r11(data: &[u8]) -> [u8; 16] {
+    let (base, multiplier, modifiers) = decode_eac(data);
+    modifiers.map(|modifier| {
+        let scale = if multiplier == 0 { 1 } else { multiplier * 8 };
+        let value = (base * 8 + 4 + modifier * scale).clamp(0, 2047);
+        ((value * 255 + 1023) / 2047) as u8
+    })
+}
+
+fn decode_eac_r11_snorm(data: &[u8]) -> [i8; 16] {
+    let (base, multiplier, modifiers) = decode_eac(data);
+    let base = (base as u8 as i8).max(-127) as i32;
+    modifiers.map(|modifier| {
+        let scale = if multiplier == 0 { 1 } else { multiplier * 8 };
+        let value = (base * 8 + modifier * scale).clamp(-1023, 1023);
+        let rounded = (value.abs() * 127 + 511) / 1023;
+        (if value < 0 { -rounded } else { rounded }) as i8
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(format: wgpu::TextureFormat, data: &[u8]) -> Vec<[u8; 4]> {
+        let mut out = [0u8; 64];
+        decode_block(format, data, &mut out);
+        out.chunks_exact(4).map(|pixel| pixel.try_into().unwrap()).collect()
+    }
+
+    #[test]
+    fn individual_mode() {
+        //base colors 0x8 and 0x4 with tables 0 and 7, the top row uses indices 0, 2, 1 and 3
+        let data = [0x84, 0x84, 0x84, 0x1C, 0x10, 0x10, 0x11, 0x00];
+        let block = decode(wgpu::TextureFormat::Etc2Rgb8Unorm, &data);
+        assert_eq!(block[0], [138, 138, 138, 255]);
+        assert_eq!(block[1], [134, 134, 134, 255]);
+        assert_eq!(block[2], [251, 251, 251, 255]);
+        assert_eq!(block[3], [0, 0, 0, 255]);
+        assert_eq!(block[4], [138, 138, 138, 255]);
+    }
+
+    #[test]
+    fn punchthrough_transparent_pixels() {
+        //differential base color 16 with the opaque bit clear, the top row uses indices 0, 1, 2 and 3
+        let data = [0x80, 0x80, 0x80, 0x00, 0x11, 0x00, 0x10, 0x10];
+        let block = decode(wgpu::TextureFormat::Etc2Rgb8A1Unorm, &data);
+        assert_eq!(block[0], [132, 132, 132, 255]);
+        assert_eq!(block[1], [140, 140, 140, 255]);
+        assert_eq!(block[2], [0, 0, 0, 0]);
+        assert_eq!(block[3], [124, 124, 124, 255]);
+    }
+
+    #[test]
+    fn eac_alpha_and_r11() {
+        //base 128, multiplier 1, table 13, pixel 0 uses index 7 and pixel 4 index 3
+        let eac = [0x80, 0x1D, 0xF2, 0x47, 0x24, 0x92, 0x49, 0x24];
+        let rgb = [0x84, 0x84, 0x84, 0x1C, 0x10, 0x10, 0x11, 0x00];
+        let block = decode(wgpu::TextureFormat::Etc2Rgba8Unorm, &[eac, rgb].concat());
+        assert_eq!(block[0], [138, 138, 138, 137]);
+        assert_eq!(block[1], [134, 134, 134, 118]);
+        assert_eq!(block[5], [138, 138, 138, 128]);
+
+        let block = decode(wgpu::TextureFormat::EacR11Unorm, &eac);
+        assert_eq!(block[0], [137, 0, 0, 255]);
+        assert_eq!(block[1], [118, 0, 0, 255]);
+        assert_eq!(block[5], [128, 0, 0, 255]);
+    }
+}
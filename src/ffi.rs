@@ -0,0 +1,335 @@
+// handle based c api around the renderer, offscreen or inside a window the caller owns. every function returns KANI_OK or one of the error
+// codes below, panics are caught and never cross into the caller. the message of the last error on
+// the calling thread is available from kani_last_error. kanirenderer_viewer.h is written by hand, any
+// change to the functions, structs or error codes here has to be made there too
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::fmt::Display;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use raw_window_handle::{
+    RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle, Win32WindowHandle, WindowsDisplayHandle, XlibDisplayHandle,
+    XlibWindowHandle,
+};
+use crate::embed::RawWindow;
+use crate::State;
+
+pub const KANI_OK: i32 = 0;
+pub const KANI_ERROR_NULL_POINTER: i32 = -1;
+pub const KANI_ERROR_INVALID_ARGUMENT: i32 = -2;
+// no adapter, or the device couldn't be created
+pub const KANI_ERROR_DEVICE: i32 = -3;
+pub const KANI_ERROR_LOAD_FAILED: i32 = -4;
+pub const KANI_ERROR_RENDER_FAILED: i32 = -5;
+pub const KANI_ERROR_BUFFER_TOO_SMALL: i32 = -6;
+pub const KANI_ERROR_PANIC: i32 = -7;
+
+// opaque to c, created by one of the kani_viewer_create functions and freed by kani_viewer_destroy
+pub struct KaniViewer {
+    state: State,
+    last_frame: instant::Instant,
+}
+
+#[repr(C)]
+pub struct KaniPick {
+    pub hit: bool,
+    pub model: u32,
+    pub mesh: u32,
+    pub distance: f32,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+struct FfiError {
+    code: i32,
+    message: String,
+}
+
+fn fail(code: i32, message: impl Display) -> FfiError {
+    FfiError { code, message: format!("{:#}", message) }
+}
+
+// runs f with errors and panics turned into codes, the message is kept for kani_last_error
+fn guard(f: impl FnOnce() -> Result<(), FfiError>) -> i32 {
+    let err = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return KANI_OK,
+        Ok(Err(err)) => err,
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            fail(KANI_ERROR_PANIC, format!("panicked : {}", message))
+        }
+    };
+    LAST_ERROR.with(|last_error| {
+        *last_error.borrow_mut() = CString::new(err.message.replace('\0', " ")).ok();
+    });
+    err.code
+}
+
+unsafe fn viewer_arg<'a>(viewer: *mut KaniViewer) -> Result<&'a mut KaniViewer, FfiError> {
+    viewer.as_mut().ok_or_else(|| fail(KANI_ERROR_NULL_POINTER, "viewer is null"))
+}
+
+unsafe fn str_arg<'a>(value: *const c_char, name: &str) -> Result<&'a str, FfiError> {
+    if value.is_null() {
+        return Err(fail(KANI_ERROR_NULL_POINTER, format!("{} is null", name)));
+    }
+    CStr::from_ptr(value).to_str().map_err(|_| fail(KANI_ERROR_INVALID_ARGUMENT, format!("{} is not utf-8", name)))
+}
+
+/// message of the last failed call on this thread, NULL if there was none.
+/// valid until the next failing call on the same thread
+#[no_mangle]
+pub extern "C" fn kani_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| last_error.borrow().as_ref().map_or(std::ptr::null(), |message| message.as_ptr()))
+}
+
+/// file_type is "default" or "opengl" (NULL means "default"), the viewer renders offscreen at width x height.
+/// on success *out_viewer is set to a viewer that must be freed with kani_viewer_destroy
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_create(file_type: *const c_char, width: u32, height: u32, out_viewer: *mut *mut KaniViewer) -> i32 {
+    guard(|| {
+        if out_viewer.is_null() {
+            return Err(fail(KANI_ERROR_NULL_POINTER, "out_viewer is null"));
+        }
+        *out_viewer = std::ptr::null_mut();
+        let file_type = if file_type.is_null() { "default" } else { str_arg(file_type, "file_type")? };
+        if file_type != "default" && file_type != "opengl" {
+            return Err(fail(KANI_ERROR_INVALID_ARGUMENT, format!("unknown file type {:?}, use \"default\" or \"opengl\"", file_type)));
+        }
+        if width == 0 || height == 0 {
+            return Err(fail(KANI_ERROR_INVALID_ARGUMENT, format!("size must not be zero, got {}x{}", width, height)));
+        }
+        let state = pollster::block_on(State::new_headless(width, height, file_type.to_string(), false)).map_err(|err| fail(KANI_ERROR_DEVICE, err))?;
+        *out_viewer = Box::into_raw(Box::new(KaniViewer { state, last_frame: instant::Instant::now() }));
+        Ok(())
+    })
+}
+
+unsafe fn create_embedded(window: RawWindow, file_type: *const c_char, width: u32, height: u32, out_viewer: *mut *mut KaniViewer) -> Result<(), FfiError> {
+    if out_viewer.is_null() {
+        return Err(fail(KANI_ERROR_NULL_POINTER, "out_viewer is null"));
+    }
+    *out_viewer = std::ptr::null_mut();
+    let file_type = if file_type.is_null() { "default" } else { str_arg(file_type, "file_type")? };
+    State::check_file_type(file_type).map_err(|err| fail(KANI_ERROR_INVALID_ARGUMENT, err))?;
+    if width == 0 || height == 0 {
+        return Err(fail(KANI_ERROR_INVALID_ARGUMENT, format!("size must not be zero, got {}x{}", width, height)));
+    }
+    let state = pollster::block_on(State::new_embedded(window, width, height, file_type.to_string(), false)).map_err(|err| fail(KANI_ERROR_DEVICE, err))?;
+    *out_viewer = Box::into_raw(Box::new(KaniViewer { state, last_frame: instant::Instant::now() }));
+    Ok(())
+}
+
+/// draws into the win32 window hwnd (hinstance may be NULL), width x height is its client size.
+/// the window has to outlive the viewer, frames are drawn with kani_viewer_render_frame
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_create_win32(hwnd: *mut c_void, hinstance: *mut c_void, file_type: *const c_char, width: u32, height: u32, out_viewer: *mut *mut KaniViewer) -> i32 {
+    guard(|| {
+        if hwnd.is_null() {
+            return Err(fail(KANI_ERROR_NULL_POINTER, "hwnd is null"));
+        }
+        let mut handle = Win32WindowHandle::empty();
+        handle.hwnd = hwnd;
+        handle.hinstance = hinstance;
+        let window = RawWindow {
+            window: RawWindowHandle::Win32(handle),
+            display: RawDisplayHandle::Windows(WindowsDisplayHandle::empty()),
+        };
+        create_embedded(window, file_type, width, height, out_viewer)
+    })
+}
+
+/// draws into the x11 window on the xlib Display* display, see kani_viewer_create_win32
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_create_xlib(display: *mut c_void, window: u64, file_type: *const c_char, width: u32, height: u32, out_viewer: *mut *mut KaniViewer) -> i32 {
+    guard(|| {
+        if display.is_null() {
+            return Err(fail(KANI_ERROR_NULL_POINTER, "display is null"));
+        }
+        let mut window_handle = XlibWindowHandle::empty();
+        window_handle.window = window as _;
+        let mut display_handle = XlibDisplayHandle::empty();
+        display_handle.display = display;
+        let window = RawWindow {
+            window: RawWindowHandle::Xlib(window_handle),
+            display: RawDisplayHandle::Xlib(display_handle),
+        };
+        create_embedded(window, file_type, width, height, out_viewer)
+    })
+}
+
+/// draws into the wl_surface surface on the wl_display display, see kani_viewer_create_win32
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_create_wayland(display: *mut c_void, surface: *mut c_void, file_type: *const c_char, width: u32, height: u32, out_viewer: *mut *mut KaniViewer) -> i32 {
+    guard(|| {
+        if display.is_null() || surface.is_null() {
+            return Err(fail(KANI_ERROR_NULL_POINTER, "display or surface is null"));
+        }
+        let mut window_handle = WaylandWindowHandle::empty();
+        window_handle.surface = surface;
+        let mut display_handle = WaylandDisplayHandle::empty();
+        display_handle.display = display;
+        let window = RawWindow {
+            window: RawWindowHandle::Wayland(window_handle),
+            display: RawDisplayHandle::Wayland(display_handle),
+        };
+        create_embedded(window, file_type, width, height, out_viewer)
+    })
+}
+
+/// NULL is ignored
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_destroy(viewer: *mut KaniViewer) {
+    if !viewer.is_null() {
+        guard(|| {
+            drop(Box::from_raw(viewer));
+            Ok(())
+        });
+    }
+}
+
+/// replaces the loaded model, blocks until it's uploaded. a failed load leaves the scene empty
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_load_model(viewer: *mut KaniViewer, path: *const c_char) -> i32 {
+    guard(|| {
+        let viewer = viewer_arg(viewer)?;
+        let path = str_arg(path, "path")?;
+        viewer.state.load_model_blocking(Path::new(path)).map_err(|err| fail(KANI_ERROR_LOAD_FAILED, err))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_unload_model(viewer: *mut KaniViewer) -> i32 {
+    guard(|| {
+        viewer_arg(viewer)?.state.unload_models();
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_resize(viewer: *mut KaniViewer, width: u32, height: u32) -> i32 {
+    guard(|| {
+        let viewer = viewer_arg(viewer)?;
+        if width == 0 || height == 0 {
+            return Err(fail(KANI_ERROR_INVALID_ARGUMENT, format!("size must not be zero, got {}x{}", width, height)));
+        }
+        viewer.state.resize_checked(width, height).map_err(|err| fail(KANI_ERROR_RENDER_FAILED, err))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_size(viewer: *mut KaniViewer, out_width: *mut u32, out_height: *mut u32) -> i32 {
+    guard(|| {
+        let viewer = viewer_arg(viewer)?;
+        if out_width.is_null() || out_height.is_null() {
+            return Err(fail(KANI_ERROR_NULL_POINTER, "out_width or out_height is null"));
+        }
+        (*out_width, *out_height) = (viewer.state.config.width, viewer.state.config.height);
+        Ok(())
+    })
+}
+
+/// camera at (x, y, z) looking at (target_x, target_y, target_z)
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_set_camera(viewer: *mut KaniViewer, x: f32, y: f32, z: f32, target_x: f32, target_y: f32, target_z: f32) -> i32 {
+    guard(|| {
+        viewer_arg(viewer)?.state.look_at([x, y, z], [target_x, target_y, target_z]);
+        Ok(())
+    })
+}
+
+/// points the camera at the model from far enough away that all of it is visible,
+/// azimuth around the y axis and elevation in degrees
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_frame_model(viewer: *mut KaniViewer, azimuth: f32, elevation: f32) -> i32 {
+    guard(|| {
+        let viewer = viewer_arg(viewer)?;
+        let (position, target) = viewer.state.orbit_pose(azimuth, elevation).map_err(|err| fail(KANI_ERROR_INVALID_ARGUMENT, err))?;
+        viewer.state.look_at(position, target);
+        Ok(())
+    })
+}
+
+/// direction the light shines in, doesn't have to be normalized
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_set_directional_light(viewer: *mut KaniViewer, direction_x: f32, direction_y: f32, direction_z: f32, r: f32, g: f32, b: f32, intensity: f32) -> i32 {
+    guard(|| {
+        let viewer = viewer_arg(viewer)?;
+        if direction_x == 0.0 && direction_y == 0.0 && direction_z == 0.0 {
+            return Err(fail(KANI_ERROR_INVALID_ARGUMENT, "light direction must not be zero"));
+        }
+        viewer.state.set_directional_light([direction_x, direction_y, direction_z], [r, g, b], intensity);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_set_point_light(viewer: *mut KaniViewer, x: f32, y: f32, z: f32, r: f32, g: f32, b: f32, range: f32) -> i32 {
+    guard(|| {
+        viewer_arg(viewer)?.state.set_point_light([x, y, z], [r, g, b], range);
+        Ok(())
+    })
+}
+
+/// unlit, lit, shadow, wireframe, debug, validation, normals, normal-map, uv, uv-checker or texel-density
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_set_render_mode(viewer: *mut KaniViewer, mode: *const c_char) -> i32 {
+    guard(|| {
+        let viewer = viewer_arg(viewer)?;
+        let mode = str_arg(mode, "mode")?;
+        viewer.state.set_mode_by_name(mode).map_err(|err| fail(KANI_ERROR_INVALID_ARGUMENT, err))
+    })
+}
+
+/// draws and presents one frame in the window of a viewer made with kani_viewer_create_win32,
+/// _xlib or _wayland. call kani_viewer_resize when the window changes size
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_render_frame(viewer: *mut KaniViewer) -> i32 {
+    guard(|| {
+        let viewer = viewer_arg(viewer)?;
+        let dt = viewer.last_frame.elapsed();
+        viewer.last_frame = instant::Instant::now();
+        viewer.state.render_frame(dt).map_err(|err| fail(KANI_ERROR_RENDER_FAILED, err))
+    })
+}
+
+/// renders one frame into pixels as tightly packed rgba8 rows from the top, len has to be
+/// at least width * height * 4 bytes
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_render(viewer: *mut KaniViewer, pixels: *mut u8, len: usize) -> i32 {
+    guard(|| {
+        let viewer = viewer_arg(viewer)?;
+        if pixels.is_null() {
+            return Err(fail(KANI_ERROR_NULL_POINTER, "pixels is null"));
+        }
+        let (width, height) = (viewer.state.config.width, viewer.state.config.height);
+        let needed = width as usize * height as usize * 4;
+        if len < needed {
+            return Err(fail(KANI_ERROR_BUFFER_TOO_SMALL, format!("pixels holds {} bytes, {}x{} needs {}", len, width, height, needed)));
+        }
+        let image = viewer.state.render_image().map_err(|err| fail(KANI_ERROR_RENDER_FAILED, err))?;
+        std::slice::from_raw_parts_mut(pixels, needed).copy_from_slice(image.as_raw());
+        Ok(())
+    })
+}
+
+/// closest mesh under pixel (x, y) of the rendered image, out_pick->hit is false when there is none
+#[no_mangle]
+pub unsafe extern "C" fn kani_viewer_pick(viewer: *mut KaniViewer, x: f32, y: f32, out_pick: *mut KaniPick) -> i32 {
+    guard(|| {
+        let viewer = viewer_arg(viewer)?;
+        let out_pick = out_pick.as_mut().ok_or_else(|| fail(KANI_ERROR_NULL_POINTER, "out_pick is null"))?;
+        *out_pick = match viewer.state.pick_at(x, y) {
+            Some(hit) => KaniPick { hit: true, model: hit.model as u32, mesh: hit.mesh as u32, distance: hit.distance },
+            None => KaniPick { hit: false, model: 0, mesh: 0, distance: 0.0 },
+        };
+        Ok(())
+    })
+}
@@ -0,0 +1,167 @@
+use std::path::Path;
+use image::RgbaImage;
+use crate::{Attachment, Bounds, Entity, MeshUpdate, PickHit, State, Transform};
+
+// the viewer without a window, for rendering image sequences, thumbnails and other batch jobs.
+// picks the adapter the same way the viewer does, WGPU_BACKEND can override the backend.
+// one device is kept for every model loaded into it
+pub struct HeadlessRenderer {
+    state: State,
+}
+
+impl HeadlessRenderer {
+    // starts without a model, see load_model
+    pub fn new(file_type: &str, width: u32, height: u32) -> anyhow::Result<Self> {
+        Self::with_adapter(file_type, width, height, false)
+    }
+
+    // software uses the cpu fallback adapter, slow but the same on every machine, for reference images
+    pub fn with_adapter(file_type: &str, width: u32, height: u32, software: bool) -> anyhow::Result<Self> {
+        State::check_file_type(file_type)?;
+        if width == 0 || height == 0 {
+            return Err(anyhow::anyhow!("image size must not be zero, got {}x{}", width, height));
+        }
+        let state = pollster::block_on(State::new_headless(width, height, file_type.to_string(), software))?;
+        Ok(Self { state })
+    }
+
+    // replaces whatever was loaded before, blocks until the model and its textures are uploaded.
+    // a model that fails to load leaves the scene empty
+    pub fn load_model(&mut self, file_path: &Path) -> anyhow::Result<()> {
+        self.state.load_model_blocking(file_path)
+    }
+
+    // replaces whatever was loaded before without waiting, poll_loading uploads the model piece by piece
+    pub fn start_loading(&mut self, file_path: &Path) {
+        self.state.start_loading(file_path);
+    }
+
+    // uploads the next thing the loading thread finished, true while there is more to come.
+    // a failed load comes back as the error
+    pub fn poll_loading(&mut self) -> anyhow::Result<bool> {
+        self.state.sync_entities();
+        self.state.poll_model_loader(instant::Duration::ZERO);
+        if let Some(err) = self.state.model_load_error.take() {
+            return Err(anyhow::anyhow!(err));
+        }
+        Ok(self.state.model_loader.is_some())
+    }
+
+    // loads file_path again into the model at index the way the viewer's hot reload does,
+    // the entities placing it stay and mesh entities follow the groups the file has now
+    pub fn reload_model(&mut self, index: usize, file_path: &Path) -> anyhow::Result<()> {
+        self.state.reload_model_blocking(index, file_path)
+    }
+
+    // the live link, a mesh with that name is replaced in place, any other becomes a model of its own
+    pub fn push_mesh(&mut self, update: MeshUpdate) -> anyhow::Result<()> {
+        self.state.push_mesh(update).map(|_| ())
+    }
+
+    pub fn remove_mesh(&mut self, name: &str) -> anyhow::Result<()> {
+        self.state.remove_mesh(name)
+    }
+
+    // drops every loaded model and the textures only they used
+    pub fn unload_model(&mut self) {
+        self.state.unload_models();
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.state.config.width, self.state.config.height)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        self.state.resize_checked(width, height)
+    }
+
+    // same names as `--mode` takes: unlit, lit, shadow, wireframe, debug, validation,
+    // normals, normal-map, uv, uv-checker, texel-density
+    pub fn set_mode(&mut self, name: &str) -> anyhow::Result<()> {
+        self.state.set_mode_by_name(name)
+    }
+
+    // world space bounds of every instance of every loaded model
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.state.scene_bounds()
+    }
+
+    pub fn look_at(&mut self, position: [f32; 3], target: [f32; 3]) {
+        self.state.look_at(position, target);
+    }
+
+    // camera position looking at the bounds centre from far enough away that the whole model
+    // fits the frame, azimuth around the y axis and elevation above the centre in degrees
+    pub fn orbit_pose(&self, azimuth: f32, elevation: f32) -> anyhow::Result<([f32; 3], [f32; 3])> {
+        self.state.orbit_pose(azimuth, elevation)
+    }
+
+    // the scene graph, each newly loaded model starts out with a model entity at the origin and a child per mesh
+    pub fn entities(&self) -> &[Entity] {
+        &self.state.entities
+    }
+
+    // places model (an index into the loaded models) once more, returns the new entity's id
+    pub fn spawn_entity(&mut self, model: usize, transform: Transform) -> anyhow::Result<u32> {
+        self.state.spawn_entity(model, transform)
+    }
+
+    pub fn set_entity_transform(&mut self, id: u32, transform: Transform) -> anyhow::Result<()> {
+        self.state.set_entity_transform(id, transform)
+    }
+
+    // an entity below parent (or at the top of the tree), a model brings a child per mesh along
+    pub fn add_entity(&mut self, parent: Option<u32>, attachment: Attachment, transform: Transform) -> anyhow::Result<u32> {
+        self.state.add_entity(parent, attachment, transform)
+    }
+
+    // the transform is kept and is relative to the new parent from then on
+    pub fn set_entity_parent(&mut self, id: u32, parent: Option<u32>) -> anyhow::Result<()> {
+        self.state.set_entity_parent(id, parent)
+    }
+
+    // hides or shows the entity with everything below it
+    pub fn set_entity_visible(&mut self, id: u32, visible: bool) -> anyhow::Result<()> {
+        self.state.set_entity_visible(id, visible)
+    }
+
+    // meshes of a model uploaded so far
+    pub fn mesh_count(&self, model: usize) -> usize {
+        self.state.models.get(model).map_or(0, |loaded| loaded.meshes.len())
+    }
+
+    // world matrix of every drawn instance of a mesh, columns first
+    pub fn mesh_transforms(&self, model: usize, mesh: usize) -> Vec<[[f32; 4]; 4]> {
+        match self.state.models.get(model) {
+            Some(loaded) if mesh < loaded.meshes.len() => loaded.mesh_transforms(mesh).into_iter().map(Into::into).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // removes everything below it too
+    pub fn remove_entity(&mut self, id: u32) -> anyhow::Result<()> {
+        self.state.remove_entity(id)
+    }
+
+    // direction the directional light shines in, it doesn't have to be normalized
+    pub fn set_light_direction(&mut self, direction: [f32; 3]) {
+        self.state.directional_light.light_direction = direction;
+    }
+
+    pub fn set_directional_light(&mut self, direction: [f32; 3], color: [f32; 3], intensity: f32) {
+        self.state.set_directional_light(direction, color, intensity);
+    }
+
+    pub fn set_point_light(&mut self, position: [f32; 3], color: [f32; 3], range: f32) {
+        self.state.set_point_light(position, color, range);
+    }
+
+    // closest mesh under a pixel of the rendered image, x and y from the top left
+    pub fn pick(&self, x: f32, y: f32) -> Option<PickHit> {
+        self.state.pick_at(x, y)
+    }
+
+    pub fn render(&mut self) -> anyhow::Result<RgbaImage> {
+        self.state.render_image()
+    }
+}
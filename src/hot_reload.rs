@@ -0,0 +1,131 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+// what to rebuild when a watched file changes
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReloadTarget {
+    Model { index: usize, file_name: String, file_type: String },
+    Texture,
+    Shader(String),
+}
+
+// editors usually write a file in several steps, wait until it has been quiet this long
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+// watches the directories of loaded files, editors often save by writing a
+// temp file and renaming it over the original which a plain file watch misses
+pub struct HotReloader {
+    watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<notify::Event>>,
+    watched_dirs: HashSet<PathBuf>,
+    targets: HashMap<PathBuf, Vec<ReloadTarget>>,
+    changed: HashMap<PathBuf, Instant>,
+}
+
+impl HotReloader {
+    pub fn new() -> anyhow::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })?;
+        Ok(Self {
+            watcher,
+            receiver,
+            watched_dirs: HashSet::new(),
+            targets: HashMap::new(),
+            changed: HashMap::new(),
+        })
+    }
+
+    pub fn watch(&mut self, path: &Path, target: ReloadTarget) {
+        let path = normalize_path(path);
+        if let Some(dir) = path.parent() {
+            if !self.watched_dirs.contains(dir) {
+                match self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    Ok(()) => {
+                        self.watched_dirs.insert(dir.to_path_buf());
+                    }
+                    Err(err) => {
+                        println!("could not watch {:?} : {}", dir, err);
+                        return;
+                    }
+                }
+            }
+        }
+        let targets = self.targets.entry(path).or_default();
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+
+    // forget everything watched for a model, used before it gets reloaded or removed
+    pub fn unwatch_model(&mut self, model_index: usize) {
+        for targets in self.targets.values_mut() {
+            targets.retain(|target| !matches!(target, ReloadTarget::Model { index, .. } if *index == model_index));
+        }
+        self.targets.retain(|_, targets| !targets.is_empty());
+    }
+
+    pub fn unwatch_models(&mut self) {
+        for targets in self.targets.values_mut() {
+            targets.retain(|target| !matches!(target, ReloadTarget::Model { .. }));
+        }
+        self.targets.retain(|_, targets| !targets.is_empty());
+    }
+
+    // files that changed and have settled since the last call
+    pub fn poll(&mut self) -> Vec<(PathBuf, ReloadTarget)> {
+        while let Ok(event) = self.receiver.try_recv() {
+            let event = match event {
+                Ok(event) => event,
+                Err(err) => {
+                    println!("file watcher error : {}", err);
+                    continue;
+                }
+            };
+            if !(event.kind.is_modify() || event.kind.is_create()) {
+                continue;
+            }
+            for path in event.paths {
+                let path = normalize_path(&path);
+                if self.targets.contains_key(&path) {
+                    self.changed.insert(path, Instant::now());
+                }
+            }
+        }
+
+        let settled = self.changed
+            .iter()
+            .filter(|(_, time)| time.elapsed() >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+
+        let mut reloads = Vec::new();
+        for path in settled {
+            self.changed.remove(&path);
+            if let Some(targets) = self.targets.get(&path) {
+                for target in targets {
+                    reloads.push((path.clone(), target.clone()));
+                }
+            }
+        }
+        reloads
+    }
+}
+
+// absolute path with the directory resolved, works for files that are
+// currently missing because an editor is in the middle of replacing them
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(dir), Some(name)) => std::fs::canonicalize(dir).unwrap_or(dir.to_path_buf()).join(name),
+        _ => absolute,
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use rayon::prelude::*;
+use serde::Serialize;
+use crate::{compressed_texture, model, resources, texture};
+
+// what `kanirenderer info` reports, everything is read from disk, no gpu needed
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelInfo {
+    pub file_name: String,
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    pub bounds: Option<Bounds>,
+    pub meshes: Vec<MeshInfo>,
+    pub materials: Vec<MaterialInfo>,
+    pub textures: Vec<TextureInfo>,
+    pub missing_textures: Vec<String>,
+    pub mesh_memory_bytes: u64,
+    pub texture_memory_bytes: u64,
+    pub estimated_gpu_memory_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Bounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MeshInfo {
+    pub name: String,
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    pub material: Option<String>,
+    pub bounds: Option<Bounds>,
+    pub gpu_memory_bytes: u64,
+}
+
+// None means the slot uses the built in fallback texture
+#[derive(Debug, Clone, Serialize)]
+pub struct MaterialInfo {
+    pub name: String,
+    pub diffuse_texture: Option<String>,
+    pub normal_texture: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TextureInfo {
+    pub path: String,
+    //"diffuse" or "normal", the same file used as both gets uploaded twice
+    pub usage: String,
+    pub container: String,
+    pub width: u32,
+    pub height: u32,
+    pub mip_levels: u32,
+    //color type for regular images, the stored format for ktx2/dds
+    pub source_format: String,
+    pub gpu_format: String,
+    pub gpu_memory_bytes: u64,
+    //set when the file can't be decoded, the viewer uses the fallback texture then
+    pub error: Option<String>,
+}
+
+impl Bounds {
+    fn from_positions(positions: &[f32]) -> Option<Self> {
+        let mut points = positions.chunks_exact(3);
+        let first = points.next()?;
+        let mut bounds = Bounds { min: [first[0], first[1], first[2]], max: [first[0], first[1], first[2]] };
+        for point in points {
+            bounds = bounds.union(&Bounds { min: [point[0], point[1], point[2]], max: [point[0], point[1], point[2]] });
+        }
+        Some(bounds)
+    }
+
+    fn union(&self, other: &Bounds) -> Bounds {
+        let mut bounds = *self;
+        for axis in 0..3 {
+            bounds.min[axis] = bounds.min[axis].min(other.min[axis]);
+            bounds.max[axis] = bounds.max[axis].max(other.max[axis]);
+        }
+        bounds
+    }
+
+    pub fn size(&self) -> [f32; 3] {
+        [self.max[0] - self.min[0], self.max[1] - self.min[1], self.max[2] - self.min[2]]
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        [(self.min[0] + self.max[0]) * 0.5, (self.min[1] + self.max[1]) * 0.5, (self.min[2] + self.max[2]) * 0.5]
+    }
+
+    pub(crate) fn from_points(points: impl IntoIterator<Item = [f32; 3]>) -> Option<Self> {
+        points.into_iter().map(|point| Bounds { min: point, max: point }).reduce(|a, b| a.union(&b))
+    }
+}
+
+impl ModelInfo {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+// parse an obj and its mtl files and read every texture it references.
+// memory is what the viewer would upload: vertex and index buffers plus textures
+pub fn model_info(file_name: &str, file_type: &str) -> anyhow::Result<ModelInfo> {
+    let obj = pollster::block_on(resources::parse_obj(file_name, file_type))?;
+
+    let vertex_size = std::mem::size_of::<model::ModelVertex>() as u64;
+    let meshes = obj.models
+        .iter()
+        .map(|m| {
+            let vertex_count = m.mesh.positions.len() / 3;
+            MeshInfo {
+                name: m.name.clone(),
+                vertex_count,
+                triangle_count: m.mesh.indices.len() / 3,
+                material: m.mesh.material_id.and_then(|id| obj.material_names.get(id).cloned()),
+                bounds: Bounds::from_positions(&m.mesh.positions),
+                gpu_memory_bytes: vertex_count as u64 * vertex_size + m.mesh.indices.len() as u64 * 4,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let texture_name = |key: &texture::TextureKey| {
+        if key.is_fallback() { None } else { Some(key.path.to_string_lossy().to_string()) }
+    };
+    let materials = obj.material_names
+        .iter()
+        .zip(obj.texture_keys.chunks(2))
+        .map(|(name, keys)| MaterialInfo {
+            name: name.clone(),
+            diffuse_texture: texture_name(&keys[0]),
+            normal_texture: texture_name(&keys[1]),
+        })
+        .collect::<Vec<_>>();
+
+    let mut texture_keys: Vec<&texture::TextureKey> = Vec::new();
+    for key in &obj.texture_keys {
+        if !key.is_fallback() && !texture_keys.contains(&key) {
+            texture_keys.push(key);
+        }
+    }
+    let textures = texture_keys.par_iter().map(|key| texture_info(key)).collect::<Vec<_>>();
+
+    let bounds = meshes.iter().filter_map(|mesh| mesh.bounds).reduce(|a, b| a.union(&b));
+    let mesh_memory_bytes = meshes.iter().map(|mesh| mesh.gpu_memory_bytes).sum::<u64>();
+    let texture_memory_bytes = textures.iter().map(|texture| texture.gpu_memory_bytes).sum::<u64>();
+
+    Ok(ModelInfo {
+        file_name: obj.file_name.clone(),
+        vertex_count: meshes.iter().map(|mesh| mesh.vertex_count).sum(),
+        triangle_count: meshes.iter().map(|mesh| mesh.triangle_count).sum(),
+        bounds,
+        meshes,
+        materials,
+        textures,
+        missing_textures: obj.missing_textures.iter().map(|path| path.to_string_lossy().to_string()).collect(),
+        mesh_memory_bytes,
+        texture_memory_bytes,
+        estimated_gpu_memory_bytes: mesh_memory_bytes + texture_memory_bytes,
+    })
+}
+
+fn texture_info(key: &texture::TextureKey) -> TextureInfo {
+    let path = key.path.to_string_lossy().to_string();
+    let mut info = TextureInfo {
+        path: path.clone(),
+        usage: if key.is_normal_map { "normal" } else { "diffuse" }.to_string(),
+        container: String::new(),
+        width: 0,
+        height: 0,
+        mip_levels: 0,
+        source_format: String::new(),
+        gpu_format: String::new(),
+        gpu_memory_bytes: 0,
+        error: None,
+    };
+
+    //plain images only get their header read, dds and ktx2 are parsed but not decompressed
+    let mut header = Vec::new();
+    if let Err(err) = std::fs::File::open(&key.path).and_then(|file| file.take(64).read_to_end(&mut header)) {
+        info.error = Some(err.to_string());
+        return info;
+    }
+    info.container = container_name(&header);
+    if !compressed_texture::is_compressed_container(&header) {
+        match image::image_dimensions(&key.path) {
+            Ok((width, height)) => {
+                let color = header_color(&key.path);
+                //without a color type the loader's default is the best guess, an 8 bit texture
+                let format = texture::color_format(color.unwrap_or(image::ColorType::Rgba8), key.is_normal_map);
+                info.width = width;
+                info.height = height;
+                info.mip_levels = 1;
+                info.source_format = color.map(|color| format!("{:?}", color)).unwrap_or_else(|| "unknown".to_string());
+                info.gpu_format = format!("{:?}", format);
+                info.gpu_memory_bytes = compressed_texture::level_byte_size(format, width, height, 0) as u64;
+            }
+            Err(err) => info.error = Some(err.to_string()),
+        }
+        return info;
+    }
+
+    let bytes = match std::fs::read(&key.path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            info.error = Some(err.to_string());
+            return info;
+        }
+    };
+    match texture::DecodedImage::from_bytes(&bytes, &path, key.is_normal_map) {
+        Ok(decoded) => match &decoded.data {
+            texture::ImageData::Image(_) => unreachable!("only dds and ktx2 get here"),
+            texture::ImageData::Compressed(compressed) => {
+                info.width = compressed.width;
+                info.height = compressed.height;
+                info.mip_levels = compressed.levels.len() as u32;
+                info.source_format = format!("{:?}", compressed.format);
+                info.gpu_format = format!("{:?}", compressed.format);
+                info.gpu_memory_bytes = (0..info.mip_levels)
+                    .map(|level| compressed_texture::level_byte_size(compressed.format, compressed.width, compressed.height, level) as u64)
+                    .sum();
+            }
+            texture::ImageData::Cached(_) => unreachable!("from_bytes never reads the asset cache"),
+            texture::ImageData::Basis(basis) => {
+                info.width = basis.width;
+                info.height = basis.height;
+                info.mip_levels = basis.level_count;
+                info.source_format = format!("basis {:?}", basis.codec).to_lowercase();
+                //the block format depends on the adapter, astc 4x4 and bc7 are what most get
+                info.gpu_format = "transcoded on load".to_string();
+                let format = wgpu::TextureFormat::Bc7RgbaUnorm;
+                info.gpu_memory_bytes = (0..info.mip_levels)
+                    .map(|level| compressed_texture::level_byte_size(format, basis.width, basis.height, level) as u64)
+                    .sum();
+            }
+        },
+        Err(err) => info.error = Some(err.to_string()),
+    }
+    info
+}
+
+// the color type from the header, for the formats whose decoder reads it without decoding the pixels
+fn header_color(path: &Path) -> Option<image::ColorType> {
+    use image::codecs::{bmp, jpeg, png, tga};
+    use image::ImageDecoder;
+    let format = image::io::Reader::open(path).ok()?.with_guessed_format().ok()?.format()?;
+    let file = std::io::BufReader::new(std::fs::File::open(path).ok()?);
+    match format {
+        image::ImageFormat::Png => png::PngDecoder::new(file).ok().map(|decoder| decoder.color_type()),
+        image::ImageFormat::Jpeg => jpeg::JpegDecoder::new(file).ok().map(|decoder| decoder.color_type()),
+        image::ImageFormat::Tga => tga::TgaDecoder::new(file).ok().map(|decoder| decoder.color_type()),
+        image::ImageFormat::Bmp => bmp::BmpDecoder::new(file).ok().map(|decoder| decoder.color_type()),
+        _ => None,
+    }
+}
+
+fn container_name(bytes: &[u8]) -> String {
+    if bytes.starts_with(b"DDS ") {
+        return "dds".to_string();
+    }
+    if compressed_texture::is_compressed_container(bytes) {
+        return "ktx2".to_string();
+    }
+    match image::guess_format(bytes) {
+        Ok(format) => format!("{:?}", format).to_lowercase(),
+        Err(_) => "unknown".to_string(),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= MIB {
+        format!("{:.1} MiB", bytes as f64 / MIB)
+    } else {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    }
+}
+
+fn format_vector(v: [f32; 3]) -> String {
+    format!("({:.3}, {:.3}, {:.3})", v[0], v[1], v[2])
+}
+
+impl fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "model: {}", self.file_name)?;
+        writeln!(f, "meshes: {}, vertices: {}, triangles: {}", self.meshes.len(), self.vertex_count, self.triangle_count)?;
+        if let Some(bounds) = &self.bounds {
+            writeln!(f, "bounds: min {} max {} size {}", format_vector(bounds.min), format_vector(bounds.max), format_vector(bounds.size()))?;
+        }
+        writeln!(
+            f,
+            "estimated gpu memory: {} (meshes {}, textures {})",
+            format_bytes(self.estimated_gpu_memory_bytes),
+            format_bytes(self.mesh_memory_bytes),
+            format_bytes(self.texture_memory_bytes),
+        )?;
+
+        writeln!(f)?;
+        writeln!(f, "meshes:")?;
+        writeln!(f, "  {:<32} {:>10} {:>10} {:>10}  material", "name", "vertices", "triangles", "memory")?;
+        for mesh in &self.meshes {
+            writeln!(
+                f,
+                "  {:<32} {:>10} {:>10} {:>10}  {}",
+                mesh.name,
+                mesh.vertex_count,
+                mesh.triangle_count,
+                format_bytes(mesh.gpu_memory_bytes),
+                mesh.material.as_deref().unwrap_or("-"),
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "materials:")?;
+        for material in &self.materials {
+            writeln!(
+                f,
+                "  {:<32} diffuse: {}  normal: {}",
+                material.name,
+                material.diffuse_texture.as_deref().unwrap_or("fallback"),
+                material.normal_texture.as_deref().unwrap_or("fallback"),
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "textures:")?;
+        for texture in &self.textures {
+            match &texture.error {
+                Some(err) => writeln!(f, "  {} ({}) error: {}", texture.path, texture.usage, err)?,
+                None => writeln!(
+                    f,
+                    "  {} ({}) {} {}x{}, {} mip levels, {} -> {}, {}",
+                    texture.path,
+                    texture.usage,
+                    texture.container,
+                    texture.width,
+                    texture.height,
+                    texture.mip_levels,
+                    texture.source_format,
+                    texture.gpu_format,
+                    format_bytes(texture.gpu_memory_bytes),
+                )?,
+            }
+        }
+
+        if !self.missing_textures.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "missing textures:")?;
+            for path in &self.missing_textures {
+                writeln!(f, "  {}", path)?;
+            }
+        }
+        Ok(())
+    }
+}
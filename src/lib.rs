@@ -1,4 +1,6 @@
 #![allow(warnings)] 
+// the stress test loop in State::new is switched off with an empty 1..=0 range
+#![allow(clippy::reversed_empty_ranges)]
 
 use std::{clone, default, env, fs::{read, File}, io::BufWriter, ops::Deref, sync::{mpsc::{self, Receiver, Sender, SyncSender}, Arc, Mutex}, thread, vec};
 mod texture;
@@ -8,6 +10,42 @@ mod camera;
 mod light;
 mod deferredRenderPipeline;
 mod frametime;
+mod compressed_texture;
+mod bcn;
+mod etc;
+mod astc;
+mod basis;
+mod loader;
+mod shaders;
+mod hot_reload;
+mod picking;
+mod asset_cache;
+mod info;
+mod validation;
+mod vector_overlay;
+mod debug_view;
+mod capture;
+mod headless;
+mod sequence;
+mod thumbnails;
+mod ffi;
+mod controls;
+mod embed;
+mod remote;
+mod live_link;
+mod entity;
+#[cfg(feature = "python")]
+mod python;
+
+pub use info::{model_info, Bounds, MaterialInfo, MeshInfo, ModelInfo, TextureInfo};
+pub use validation::{validate_model, MeshReport, ValidationReport};
+pub use headless::HeadlessRenderer;
+pub use embed::{EmbeddedViewer, RawWindow};
+pub use picking::PickHit;
+pub use entity::{Attachment, Entity, Transform};
+pub use live_link::MeshUpdate;
+pub use sequence::{load_camera_path, render_sequence, CameraMotion, Keyframe, SequenceOptions};
+pub use thumbnails::{find_models, render_thumbnails, ThumbnailFailure, ThumbnailOptions, ThumbnailReport};
 
 use bytemuck::{cast_slice, Contiguous};
 use frametime::FrameTimeGraphRaw;
@@ -22,7 +60,7 @@ use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterato
 use texture::Texture;
 use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, Buffer, BufferAddress, BufferSize, BufferUsages, DepthBiasState, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Origin3d, PipelineLayout, RenderPipeline, Sampler, ShaderModule, TextureView};
 use winit::{
-    dpi::PhysicalSize, event::*, event_loop::{ControlFlow, EventLoop}, window::{self, Fullscreen, WindowBuilder}
+    dpi::PhysicalSize, event::*, event_loop::{ControlFlow, EventLoop, EventLoopBuilder}, window::{self, Fullscreen, WindowBuilder}
 };
 use winit::window::Window;
 use wgpu::TextureFormat;
@@ -42,6 +80,17 @@ struct DebugVert {
     position: [f32;2],
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DebugShadingParams {
+    target_texel_density: f32,
+    checker_scale: f32,
+    _padding: [f32; 2],
+}
+
+// fragment entry points of debug_shading.wgsl, one pipeline each
+const DEBUG_SHADING_ENTRIES: [&str; 5] = ["fs_world_normal", "fs_normal_map", "fs_uv", "fs_checker", "fs_texel_density"];
+
 use winit::window::Icon;
 
 #[cfg(windows)]
@@ -62,17 +111,115 @@ fn get_icon() -> Icon{
 }
 
 
+#[derive(Clone, Copy, PartialEq)]
 enum RenderOutputMode {
     Unlit,
     Lit,
     LitWithShadow,
     Wireframe,
     DebugLitWithShadow,
+    Validation,
+    WorldNormals,
+    NormalMapSample,
+    UvCoordinates,
+    UvChecker,
+    TexelDensity,
+}
+
+impl RenderOutputMode {
+    //same order as the tab cycle
+    const ALL: [RenderOutputMode; 11] = [
+        RenderOutputMode::Unlit,
+        RenderOutputMode::Lit,
+        RenderOutputMode::LitWithShadow,
+        RenderOutputMode::Wireframe,
+        RenderOutputMode::DebugLitWithShadow,
+        RenderOutputMode::Validation,
+        RenderOutputMode::WorldNormals,
+        RenderOutputMode::NormalMapSample,
+        RenderOutputMode::UvCoordinates,
+        RenderOutputMode::UvChecker,
+        RenderOutputMode::TexelDensity,
+    ];
+
+    // names used on the command line
+    fn name(&self) -> &'static str {
+        match self {
+            RenderOutputMode::Unlit => "unlit",
+            RenderOutputMode::Lit => "lit",
+            RenderOutputMode::LitWithShadow => "shadow",
+            RenderOutputMode::Wireframe => "wireframe",
+            RenderOutputMode::DebugLitWithShadow => "debug",
+            RenderOutputMode::Validation => "validation",
+            RenderOutputMode::WorldNormals => "normals",
+            RenderOutputMode::NormalMapSample => "normal-map",
+            RenderOutputMode::UvCoordinates => "uv",
+            RenderOutputMode::UvChecker => "uv-checker",
+            RenderOutputMode::TexelDensity => "texel-density",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    // index into DEBUG_SHADING_ENTRIES for modes drawn with debug_shading.wgsl
+    fn debug_shading_index(&self) -> Option<usize> {
+        match self {
+            RenderOutputMode::WorldNormals => Some(0),
+            RenderOutputMode::NormalMapSample => Some(1),
+            RenderOutputMode::UvCoordinates => Some(2),
+            RenderOutputMode::UvChecker => Some(3),
+            RenderOutputMode::TexelDensity => Some(4),
+            _ => None,
+        }
+    }
 }
 
 enum DebugTexture {
     DepthTexture,
     ShadowTexture,
+    Normals,
+    Albedo,
+    Lighting,
+    Overdraw,
+    ShadowCascade,
+}
+
+impl DebugTexture {
+    fn next(&self) -> Self {
+        match self {
+            DebugTexture::DepthTexture => DebugTexture::ShadowTexture,
+            DebugTexture::ShadowTexture => DebugTexture::Normals,
+            DebugTexture::Normals => DebugTexture::Albedo,
+            DebugTexture::Albedo => DebugTexture::Lighting,
+            DebugTexture::Lighting => DebugTexture::Overdraw,
+            DebugTexture::Overdraw => DebugTexture::ShadowCascade,
+            DebugTexture::ShadowCascade => DebugTexture::DepthTexture,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            DebugTexture::DepthTexture => "linear depth",
+            DebugTexture::ShadowTexture => "shadow map",
+            DebugTexture::Normals => "world space normals",
+            DebugTexture::Albedo => "albedo",
+            DebugTexture::Lighting => "lighting only",
+            DebugTexture::Overdraw => "overdraw",
+            DebugTexture::ShadowCascade => "shadow cascade index",
+        }
+    }
+
+    // mode value read by debug_depth.wgsl and debug_view.wgsl
+    fn shader_mode(&self) -> u32 {
+        match self {
+            DebugTexture::DepthTexture => 0,
+            DebugTexture::ShadowTexture => 1,
+            DebugTexture::Overdraw => 3,
+            _ => 2,
+        }
+    }
 }
 
 enum WindowMode{
@@ -82,12 +229,13 @@ enum WindowMode{
 
 struct State {
     free_cam : bool,
-    surface: wgpu::Surface,
+    //None when rendering headless, frames then only go to offscreen textures
+    surface: Option<wgpu::Surface>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
-    window: Window,
+    window: Option<Window>,
     window_mode: WindowMode,
     render_pipeline: wgpu::RenderPipeline,
     wireframe_pipeline: wgpu::RenderPipeline,
@@ -115,7 +263,6 @@ struct State {
     shadow_texture: wgpu::Texture,
     shadow_texture_view: TextureView,
     shadow_sampler: Sampler,
-    shadow_shader: ShaderModule,
     shadow_bind_group: BindGroup,
     shadow_pass_light_bind_group: BindGroup,
     shadow_pipeline: RenderPipeline,
@@ -124,6 +271,13 @@ struct State {
     debug_pass_pipeline: RenderPipeline,
     debug_pass_bind_group: BindGroup,
     debug_depth_bind_group_layout: BindGroupLayout,
+    debug_view_target: debug_view::DebugViewTarget,
+    debug_view_pipeline: RenderPipeline,
+    debug_view_pipeline_layout: wgpu::PipelineLayout,
+    //normals, albedo and overdraw, drawn with debug_shading.wgsl
+    debug_view_shading_pipelines: [RenderPipeline; 3],
+    //lighting only and shadow cascade, drawn with the main shader
+    debug_view_lighting_pipelines: [RenderPipeline; 2],
     debug_vertex_buffer: wgpu::Buffer,
     debug_index_buffer: wgpu::Buffer,
     debug_indices: Vec<i32>,
@@ -139,6 +293,38 @@ struct State {
     frame_time_graph: frametime::FrameTimeGraph,
     frametime_vertex_buffer: wgpu::Buffer,
     frame_time_render_pipeline: RenderPipeline,
+    texture_cache: texture::TextureCache,
+    texture_bind_group_layout: BindGroupLayout,
+    model_loader: Option<loader::ModelLoader>,
+    loading_screen: loader::LoadingScreen,
+    validation_overlay: validation::OverlayRenderer,
+    vector_overlay: vector_overlay::VectorOverlay,
+    debug_shading_pipelines: [RenderPipeline; 5],
+    debug_shading_pipeline_layout: wgpu::PipelineLayout,
+    debug_shading_params: DebugShadingParams,
+    debug_shading_params_buffer: wgpu::Buffer,
+    debug_shading_bind_group: BindGroup,
+    camera_bind_group_layout: BindGroupLayout,
+    hot_reloader: Option<hot_reload::HotReloader>,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    unlit_render_pipeline_layout: wgpu::PipelineLayout,
+    lit_render_pipeline_layout: wgpu::PipelineLayout,
+    shadow_pipeline_layout: wgpu::PipelineLayout,
+    debug_pass_pipeline_layout: wgpu::PipelineLayout,
+    file_type: String,
+    movable_model_counts: u32,
+    cursor_position: winit::dpi::PhysicalPosition<f64>,
+    modifiers: ModifiersState,
+    screenshot_requested: bool,
+    model_load_error: Option<String>,
+    //what other programs pushed through the live link, by mesh name
+    live_meshes: live_link::LiveMeshes,
+    //the scene graph, every visible mesh entity is one instance of its mesh
+    entities: Vec<entity::Entity>,
+    next_entity_id: u32,
+    //models below this index already got their first entity
+    entity_model_count: usize,
+    entities_dirty: bool,
 }
 
 fn create_render_pipeline(
@@ -148,6 +334,19 @@ fn create_render_pipeline(
     depth_format: Option<wgpu::TextureFormat>,
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    create_render_pipeline_with_entry(device, layout, color_format, depth_format, vertex_layouts, shader, "fs_main")
+}
+
+// same as create_render_pipeline for shaders with several fragment entry points
+fn create_render_pipeline_with_entry(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader: wgpu::ShaderModuleDescriptor,
+    fragment_entry: &str,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(shader);
 
@@ -161,7 +360,7 @@ fn create_render_pipeline(
         },
         fragment: Some(wgpu::FragmentState {
             module: &shader,
-            entry_point: "fs_main",
+            entry_point: fragment_entry,
             targets: &[Some(wgpu::ColorTargetState {
                 format: color_format,
                 // blend: Some(wgpu::BlendState {
@@ -251,7 +450,8 @@ fn create_wireframe_pipeline(
             strip_index_format: None,
             front_face: wgpu::FrontFace::Ccw,
             cull_mode: None,
-            polygon_mode: wgpu::PolygonMode::Line,
+            //gl adapters like llvmpipe can't draw lines from triangles, the mode draws them filled there
+            polygon_mode: if device.features().contains(wgpu::Features::POLYGON_MODE_LINE) { wgpu::PolygonMode::Line } else { wgpu::PolygonMode::Fill },
             unclipped_depth: false,
             conservative: false,
         },
@@ -270,6 +470,183 @@ fn create_wireframe_pipeline(
         multiview: None,
     })
 }
+fn create_shadow_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    let shadow_shader = device.create_shader_module(shader);
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor{
+        label: Some("Shadow Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState{
+            module: &shadow_shader,
+            entry_point: "vs_main",
+            buffers: &[model::ModelVertex::desc(), 
+            model::InstanceRaw::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shadow_shader,
+            entry_point: "fs_main",
+            targets: &[], // No color targets
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState{
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState{
+                constant: 2,
+                slope_scale: 2.0,
+                clamp: 0.0,
+            },
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+fn create_debug_pass_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    let debug_depth_shader = device.create_shader_module(shader);
+
+    let debug_vertex_buffer_layout = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<DebugVert>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float32x2,
+            },
+        ],
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor { 
+        label: Some("Debug Pass Render Pipeline"), 
+        layout: Some(layout), 
+        vertex: wgpu::VertexState{
+            module: &debug_depth_shader,
+            entry_point: "vs_main",
+            buffers: &[debug_vertex_buffer_layout],
+        }, 
+        fragment: Some(wgpu::FragmentState {
+            module: &debug_depth_shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState{
+                format: color_format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }), 
+        primitive: wgpu::PrimitiveState{
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            ..Default::default()
+        }, 
+        depth_stencil: None, 
+        multisample: wgpu::MultisampleState::default(),             
+        multiview: None,
+    })
+}
+
+// scene pipelines behind the debug views that render into the offscreen debug view target
+fn create_debug_view_shading_pipelines(device: &wgpu::Device, layout: &wgpu::PipelineLayout, source: &str) -> [wgpu::RenderPipeline; 3] {
+    let shader = || wgpu::ShaderModuleDescriptor {
+        label: Some("Debug View Shading Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.to_string().into()),
+    };
+    let model_buffers = [model::ModelVertex::desc(), model::InstanceRaw::desc()];
+    [
+        create_render_pipeline_with_entry(device, layout, debug_view::TARGET_FORMAT, Some(wgpu::TextureFormat::Depth32Float), &model_buffers, shader(), "fs_world_normal"),
+        create_render_pipeline_with_entry(device, layout, debug_view::TARGET_FORMAT, Some(wgpu::TextureFormat::Depth32Float), &model_buffers, shader(), "fs_albedo"),
+        debug_view::create_overdraw_pipeline(device, layout, shader()),
+    ]
+}
+
+fn create_debug_view_lighting_pipelines(device: &wgpu::Device, layout: &wgpu::PipelineLayout, source: &str) -> [wgpu::RenderPipeline; 2] {
+    let shader = || wgpu::ShaderModuleDescriptor {
+        label: Some("Debug View Lighting Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.to_string().into()),
+    };
+    let model_buffers = [model::ModelVertex::desc(), model::InstanceRaw::desc()];
+    [
+        create_render_pipeline_with_entry(device, layout, debug_view::TARGET_FORMAT, Some(wgpu::TextureFormat::Depth32Float), &model_buffers, shader(), "fs_lighting"),
+        create_render_pipeline_with_entry(device, layout, debug_view::TARGET_FORMAT, Some(wgpu::TextureFormat::Depth32Float), &model_buffers, shader(), "fs_shadow_cascade"),
+    ]
+}
+
+fn create_frame_time_pipeline(
+    device: &wgpu::Device,
+    color_format: wgpu::TextureFormat,
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::RenderPipeline {
+    let frame_time_shader = device.create_shader_module(shader);
+
+    let frame_time_vertex_buffer_layout = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<[f32; 2]>() as u64, // 8 bytes per vertex
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &[
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2, // Each vertex is a vec2<f32>
+                offset: 0,
+                shader_location: 0,
+            },
+        ],
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor{
+        label: Some("Frame Graph Overlay Pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &frame_time_shader,
+                entry_point: "vs_main",
+                buffers: &[frame_time_vertex_buffer_layout],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &frame_time_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineStrip,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        multiview: None,
+    })
+}
+// lit with shadow shader depends on the surface format
+fn main_shader_name(format: TextureFormat) -> &'static str {
+    match format{
+        TextureFormat::Rgba16Float => "shader_hdr.wgsl",
+        TextureFormat::Rgba8UnormSrgb => "shader.wgsl",
+        _ => "shader.wgsl",
+    }
+}
+
 fn optional_features() -> wgpu::Features {
         let mut f = wgpu::Features::POLYGON_MODE_LINE;
         //f.insert(wgpu::Features::VERTEX_WRITABLE_STORAGE);
@@ -277,10 +654,34 @@ fn optional_features() -> wgpu::Features {
         f
     }
 
+// compressed formats are only requested when the adapter has them,
+// everything else gets decoded on the cpu at load time
+fn compressed_texture_features() -> wgpu::Features {
+    wgpu::Features::TEXTURE_COMPRESSION_BC
+        | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+        | wgpu::Features::TEXTURE_COMPRESSION_ASTC
+}
+
 impl State {
     async fn new(window: Window, file_path: String, file_type:String, use_hdr: bool, window_mode: WindowMode) -> Self {
-        let free_cam = true;
         let size = window.inner_size();
+        let surface_target = embed::RawWindow::of(&window);
+        Self::create(Some(window), Some(surface_target), size, Some(file_path), file_type, use_hdr, window_mode, false).await.unwrap()
+    }
+
+    // no window or surface and no model yet, open_model loads one and render_offscreen draws the frames.
+    // software picks the cpu fallback adapter (lavapipe, warp) so output doesn't depend on the gpu
+    async fn new_headless(width: u32, height: u32, file_type: String, software: bool) -> anyhow::Result<Self> {
+        Self::create(None, None, PhysicalSize::new(width, height), None, file_type, false, WindowMode::Windowed, software).await
+    }
+
+    // draws into a window the host owns and keeps alive, see EmbeddedViewer
+    async fn new_embedded(surface_target: embed::RawWindow, width: u32, height: u32, file_type: String, use_hdr: bool) -> anyhow::Result<Self> {
+        Self::create(None, Some(surface_target), PhysicalSize::new(width, height), None, file_type, use_hdr, WindowMode::Windowed, false).await
+    }
+
+    async fn create(window: Option<Window>, surface_target: Option<embed::RawWindow>, size: PhysicalSize<u32>, file_path: Option<String>, file_type:String, use_hdr: bool, window_mode: WindowMode, force_fallback_adapter: bool) -> anyhow::Result<Self> {
+        let free_cam = true;
 
         #[cfg(windows)]
         let default_backend = wgpu::Backends::DX12;
@@ -288,24 +689,29 @@ impl State {
         #[cfg(not(windows))]
         let default_backend = wgpu::Backends::VULKAN;
 
+        //WGPU_BACKEND overrides it, e.g. for a gl or software adapter on machines without vulkan
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: default_backend,
+            backends: wgpu::util::backend_bits_from_env().unwrap_or(default_backend),
             dx12_shader_compiler: Default::default(),
         });
 
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+        //the window outlives the surface, State owns it or the host promised to keep it open
+        let surface = match &surface_target {
+            Some(surface_target) => Some(unsafe { instance.create_surface(surface_target) }?),
+            None => None,
+        };
 
         let adapter= instance.request_adapter(
             &wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
+                compatible_surface: surface.as_ref(),
+                force_fallback_adapter,
             },
-        ).await.unwrap();
+        ).await.ok_or(anyhow::anyhow!(if force_fallback_adapter { "no software adapter found" } else { "no graphics adapter found" }))?;
 
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                features: optional_features(),
+                features: adapter.features() & (optional_features() | compressed_texture_features()),
                 limits: if cfg!(target_arch = "wasm32") {
                     wgpu::Limits::downlevel_webgl2_defaults()
                 } else {
@@ -314,14 +720,9 @@ impl State {
                 label: None,
             },
             None,
-        ).await.unwrap();
+        ).await?;
 
-        let surface_caps = surface.get_capabilities(&adapter);
-        
-        let mut surface_format = surface_caps.formats.iter()
-            .copied()
-            .find(|f| *f == TextureFormat::Rgba8UnormSrgb)
-            .unwrap_or(surface_caps.formats[0]);
+        let mut surface_format = TextureFormat::Rgba8UnormSrgb;
         if use_hdr{
             surface_format = TextureFormat::Rgba16Float;
         } else {
@@ -338,7 +739,9 @@ impl State {
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
             view_formats: vec![surface_format],
         };
-        surface.configure(&device, &config);
+        if let Some(surface) = &surface {
+            surface.configure(&device, &config);
+        }
 
         let texture_bind_group_layout = device.create_bind_group_layout(
             &wgpu::BindGroupLayoutDescriptor {
@@ -631,32 +1034,16 @@ impl State {
         });
 
 
-        let debug_vertex_buffer_layout = wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<DebugVert>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-            ],
-        };
-
-        let debug_depth_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor{
-            label: Some("Debug Depth Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("debug_depth.wgsl").into()),
-        });
-
         let debug_depth_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor{
             label: Some("Debug Depth Bind Group Layout"),
             entries: &[
-                //depth texture
+                //depth texture, read as a plain float texture since gl can only sample depth textures
+                //through a comparison sampler
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Texture { 
-                        sample_type: wgpu::TextureSampleType::Depth, 
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false }, 
                         view_dimension: wgpu::TextureViewDimension::D2, 
                         multisampled: false, 
                     },
@@ -689,43 +1076,42 @@ impl State {
             ]
         });
 
+        let debug_view_target = debug_view::DebugViewTarget::new(&device, config.width, config.height, projection.znear(), projection.zfar());
+
         let debug_pass_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor{
             label: Some("Debug Depth Pass Pipeline Layout"),
             bind_group_layouts:&[
                 &debug_depth_bind_group_layout,
+                &debug_view_target.params_bind_group_layout,
             ],
             push_constant_ranges: &[],
         });
 
-        let debug_pass_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor { 
-            label: Some("Debug Pass Render Pipeline"), 
-            layout: Some(&debug_pass_pipeline_layout), 
-            vertex: wgpu::VertexState{
-                module: &debug_depth_shader,
-                entry_point: "vs_main",
-                buffers: &[debug_vertex_buffer_layout],
-            }, 
-            fragment: Some(wgpu::FragmentState {
-                module: &debug_depth_shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState{
-                    format: config.format,
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }), 
-            primitive: wgpu::PrimitiveState{
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                ..Default::default()
-            }, 
-            depth_stencil: None, 
-            multisample: wgpu::MultisampleState::default(),             
-            multiview: None,
+        let debug_view_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor{
+            label: Some("Debug View Pipeline Layout"),
+            bind_group_layouts:&[
+                &debug_view_target.view_bind_group_layout,
+                &debug_view_target.params_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
         });
 
+        let debug_view_pipeline = create_debug_pass_pipeline(&device,
+            &debug_view_pipeline_layout,
+            config.format,
+            wgpu::ShaderModuleDescriptor{
+                label: Some("Debug View Shader"),
+                source: wgpu::ShaderSource::Wgsl(shaders::load_shader("debug_view.wgsl").into()),
+            });
+
+        let debug_pass_pipeline = create_debug_pass_pipeline(&device,
+            &debug_pass_pipeline_layout,
+            config.format,
+            wgpu::ShaderModuleDescriptor{
+                label: Some("Debug Depth Shader"),
+                source: wgpu::ShaderSource::Wgsl(shaders::load_shader("debug_depth.wgsl").into()),
+            });
+
         //to do
         let useDeferredRenderer = false;
 
@@ -766,11 +1152,6 @@ impl State {
             ..Default::default()
         });
 
-        let shadow_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor{
-            label:Some("Shadow Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shadow.wgsl").into()),
-        });
-
         let shadow_pass_light_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
                 wgpu::BindGroupLayoutEntry {
@@ -874,34 +1255,9 @@ impl State {
             push_constant_ranges: &[],
         });
 
-        let shadow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor{
-            label: Some("Shadow Pipeline"),
-            layout: Some(&shadow_pipeline_layout),
-            vertex: wgpu::VertexState{
-                module: &shadow_shader,
-                entry_point: "vs_main",
-                buffers: &[model::ModelVertex::desc(), 
-                model::InstanceRaw::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shadow_shader,
-                entry_point: "fs_main",
-                targets: &[], // No color targets
-            }),
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: Some(wgpu::DepthStencilState{
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState{
-                    constant: 2,
-                    slope_scale: 2.0,
-                    clamp: 0.0,
-                },
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
+        let shadow_pipeline = create_shadow_pipeline(&device, &shadow_pipeline_layout, wgpu::ShaderModuleDescriptor{
+            label:Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::load_shader("shadow.wgsl").into()),
         });
 
         let unlit_render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -912,12 +1268,12 @@ impl State {
             ],
             push_constant_ranges: &[],
         });
-        let unlit_shader = include_str!("unlit_shader.wgsl").into();
+        let unlit_shader = shaders::load_shader("unlit_shader.wgsl");
 
         let unlit_render_pipeline = {
             let shader = wgpu::ShaderModuleDescriptor {
                 label: Some("Unlit Shader"),
-                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(unlit_shader)),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(unlit_shader)),
             };
 
             create_render_pipeline(&device, 
@@ -929,7 +1285,65 @@ impl State {
                 shader)
         };
 
-        let lit_shader = include_str!("lit_shader.wgsl").into();
+        //texels per world unit shown as on target in the texel density view
+        let target_texel_density = std::env::var("KANIRENDERER_TEXEL_DENSITY")
+            .ok()
+            .and_then(|value| value.parse::<f32>().ok())
+            .filter(|value| *value > 0.0)
+            .unwrap_or(256.0);
+        let debug_shading_params = DebugShadingParams {
+            target_texel_density,
+            checker_scale: 8.0,
+            _padding: [0.0; 2],
+        };
+        let debug_shading_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Debug Shading Params Buffer"),
+            contents: bytemuck::cast_slice(&[debug_shading_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let debug_shading_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("debug_shading_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let debug_shading_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Debug Shading Bind Group"),
+            layout: &debug_shading_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: debug_shading_params_buffer.as_entire_binding(),
+            }],
+        });
+        let debug_shading_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Shading Pipeline Layout"),
+            bind_group_layouts: &[
+                &texture_bind_group_layout,
+                &camera_bind_group_layout,
+                &debug_shading_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let debug_shading_pipelines = DEBUG_SHADING_ENTRIES.map(|fragment_entry| create_render_pipeline_with_entry(&device,
+            &debug_shading_pipeline_layout,
+            config.format,
+            Some(wgpu::TextureFormat::Depth32Float),
+            &[model::ModelVertex::desc(), model::InstanceRaw::desc()],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Debug Shading Shader"),
+                source: wgpu::ShaderSource::Wgsl(shaders::load_shader("debug_shading.wgsl").into()),
+            },
+            fragment_entry));
+        let debug_view_shading_pipelines = create_debug_view_shading_pipelines(&device, &debug_shading_pipeline_layout, &shaders::load_shader("debug_shading.wgsl"));
+
+        let lit_shader = shaders::load_shader("lit_shader.wgsl");
 
         let lit_render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Lit Without Shadow Map Render Pipeline Layout"),
@@ -944,7 +1358,7 @@ impl State {
         let lit_render_pipeline = {
             let shader = wgpu::ShaderModuleDescriptor {
                 label: Some("Lit Without Shadow Map Shader"),
-                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(lit_shader)),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(lit_shader)),
             };
 
             create_render_pipeline(&device, 
@@ -966,16 +1380,12 @@ impl State {
             ],
             push_constant_ranges: &[],
         });
-        let mut shaders: &str;
-        match config.format{
-            TextureFormat::Rgba16Float => {shaders = include_str!("shader_hdr.wgsl").into()}
-            TextureFormat::Rgba8UnormSrgb => {shaders = include_str!("shader.wgsl").into()}
-            _ => {shaders = include_str!("shader.wgsl").into()}
-        }
+        let shaders = shaders::load_shader(main_shader_name(config.format));
+        let debug_view_lighting_pipelines = create_debug_view_lighting_pipelines(&device, &render_pipeline_layout, &shaders);
         let render_pipeline = {
             let shader = wgpu::ShaderModuleDescriptor {
                 label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(shaders)),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(shaders)),
             };
 
             create_render_pipeline(&device, 
@@ -990,7 +1400,7 @@ impl State {
         let wireframe_pipeline = {
             let shader = wgpu::ShaderModuleDescriptor{
                 label: Some("Wireframe Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shader_wireframe.wgsl").into()),
+                source: wgpu::ShaderSource::Wgsl(shaders::load_shader("shader_wireframe.wgsl").into()),
             };
             create_wireframe_pipeline(&device, 
                 &render_pipeline_layout, 
@@ -1001,19 +1411,38 @@ impl State {
                 shader)
         };
 
-        use std::time::{Duration,Instant};
-        let start_loading_time = Instant::now();
-        let obj_model = match file_type.clone().as_str() {
-            "opengl" => resources::load_model(&file_path, file_type.clone(), &device, &queue, &texture_bind_group_layout,1, cgmath::Vector3 { x: 0.0, y: 0.0, z: 0.0 }).await.unwrap(),
-            "default" => resources::load_model(&file_path, file_type.clone(), &device, &queue, &texture_bind_group_layout,1,cgmath::Vector3 { x: 0.0, y: 0.0, z: 0.0 }).await.unwrap(),
+        //model is loaded on a worker thread, meshes show up as they are uploaded in update()
+        let mut texture_cache = texture::TextureCache::new();
+        match file_type.as_str() {
+            "opengl" | "default" => {}
             _ => panic!("no file type given"),
         };
-        let loading_duration = start_loading_time.elapsed();
-        println!("total loading time {:?}" , loading_duration);
+        let model_loader = file_path.map(|file_path| loader::ModelLoader::spawn(file_path, file_type.clone(), None, &texture_cache, device.features()));
+
+        //loaded files are watched once loading is done, shaders only in dev mode
+        let mut hot_reloader = match hot_reload::HotReloader::new() {
+            //nothing is displayed headless, so nothing to reload
+            Ok(_) if surface.is_none() => None,
+            Ok(hot_reloader) => Some(hot_reloader),
+            Err(err) => {
+                println!("hot reload disabled : {}", err);
+                None
+            }
+        };
+        if let Some(hot_reloader) = &mut hot_reloader {
+            if shaders::dev_mode() {
+                println!("dev mode, watching shaders in {:?}", shaders::shader_dir());
+                for name in shaders::SHADER_FILES {
+                    hot_reloader.watch(&shaders::shader_dir().join(name), hot_reload::ReloadTarget::Shader(name.to_string()));
+                }
+            }
+        }
+        let loading_screen = loader::LoadingScreen::new(&device, surface_format);
+        let validation_overlay = validation::OverlayRenderer::new(&device, surface_format, &camera_bind_group_layout);
+        let vector_overlay = vector_overlay::VectorOverlay::new(&device, surface_format, &camera_bind_group_layout);
         let mut movable_model_counts = 0;
 
         let mut models = Vec::new();
-        models.push(obj_model);
         //movable_model_counts +=1;
         let mut rng = rand::rng();
         let instances_num = 1000;
@@ -1022,7 +1451,7 @@ impl State {
                 "opengl".to_string(), 
                 &device, &queue, &texture_bind_group_layout,
                 instances_num,cgmath::Vector3 { x: (rng.random_range(-1500.0..1500.0) as f32),
-                y: (rng.random_range(30.0..100.0)  as f32), z: (rng.random_range(-1500.0..1500.0)  as f32) }).await.unwrap();
+                y: (rng.random_range(30.0..100.0)  as f32), z: (rng.random_range(-1500.0..1500.0)  as f32) }, &mut texture_cache).await.unwrap();
 
         models.push(test_mesh); 
         println!("pushed : {i}");
@@ -1030,6 +1459,11 @@ impl State {
         } 
         println!("total movable model/object : {:?}",movable_model_counts*instances_num);
         
+        //the stress test models keep their scattered instances as entities
+        let entities = entity::entities_from_instances(&models);
+        let next_entity_id = entities.len() as u32;
+        let entity_model_count = models.len();
+
         let mut render_output_mode = RenderOutputMode::LitWithShadow;
         let mut left_mouse_pressed = false;
         let debug_mode_texture = DebugTexture::DepthTexture;
@@ -1043,59 +1477,12 @@ impl State {
         });
         let mut frame_time_graph = frametime::FrameTimeGraph::new();
 
-        let frame_time_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor { 
-            label: Some("Frame Time Shader") , 
-            source: wgpu::ShaderSource::Wgsl(include_str!("frametime.wgsl").into()), 
-        });
-
-        let frame_time_vertex_buffer_layout = wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<[f32; 2]>() as u64, // 8 bytes per vertex
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    format: wgpu::VertexFormat::Float32x2, // Each vertex is a vec2<f32>
-                    offset: 0,
-                    shader_location: 0,
-                },
-            ],
-        };
-
-        let frame_time_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor{
-            label: Some("Frame Graph Overlay Pipeline"),
-                layout: None,
-                vertex: wgpu::VertexState {
-                    module: &frame_time_shader,
-                    entry_point: "vs_main",
-                    buffers: &[frame_time_vertex_buffer_layout],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &frame_time_shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: surface_format,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::LineStrip,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: None,
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                    ..Default::default()
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-            multiview: None,
+        let frame_time_render_pipeline = create_frame_time_pipeline(&device, surface_format, wgpu::ShaderModuleDescriptor {
+            label: Some("Frame Time Shader") ,
+            source: wgpu::ShaderSource::Wgsl(shaders::load_shader("frametime.wgsl").into()),
         });
 
-        Self {
+        Result::Ok(Self {
             free_cam,
             window,
             window_mode,
@@ -1128,7 +1515,6 @@ impl State {
             shadow_texture,
             shadow_texture_view,
             shadow_sampler,
-            shadow_shader,
             shadow_bind_group,
             shadow_pass_light_bind_group,
             shadow_pipeline,
@@ -1137,6 +1523,11 @@ impl State {
             debug_pass_pipeline,
             debug_pass_bind_group,
             debug_depth_bind_group_layout,
+            debug_view_target,
+            debug_view_pipeline,
+            debug_view_pipeline_layout,
+            debug_view_shading_pipelines,
+            debug_view_lighting_pipelines,
             debug_vertex_buffer,
             debug_index_buffer,
             debug_indices,
@@ -1152,15 +1543,50 @@ impl State {
             debug_mode_texture,
             frame_time_graph,
             frametime_vertex_buffer,
-            frame_time_render_pipeline
-        }
+            frame_time_render_pipeline,
+            texture_cache,
+            texture_bind_group_layout,
+            model_loader,
+            loading_screen,
+            validation_overlay,
+            vector_overlay,
+            debug_shading_pipelines,
+            debug_shading_pipeline_layout,
+            debug_shading_params,
+            debug_shading_params_buffer,
+            debug_shading_bind_group,
+            camera_bind_group_layout,
+            hot_reloader,
+            render_pipeline_layout,
+            unlit_render_pipeline_layout,
+            lit_render_pipeline_layout,
+            shadow_pipeline_layout,
+            debug_pass_pipeline_layout,
+            file_type,
+            movable_model_counts,
+            cursor_position: winit::dpi::PhysicalPosition::new(0.0, 0.0),
+            modifiers: ModifiersState::empty(),
+            screenshot_requested: false,
+            model_load_error: None,
+            live_meshes: live_link::LiveMeshes::new(),
+            entities,
+            next_entity_id,
+            entity_model_count,
+            entities_dirty: false,
+        })
     }
 
     
     
 
     pub fn window(&self) -> &Window {
-        &self.window
+        self.window.as_ref().expect("headless state has no window")
+    }
+
+    fn configure_surface(&self) {
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
     }
 
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -1169,7 +1595,7 @@ impl State {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.projection.resize(new_size.width, new_size.height);
-            self.surface.configure(&self.device, &self.config);
+            self.configure_surface();
             self.depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
                 label: Some("Scene Depth Texture"),
                 size: wgpu::Extent3d {
@@ -1185,26 +1611,35 @@ impl State {
                 view_formats: &[],
             });
             self.depth_view = self.depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-            self.debug_pass_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor{
-                label:Some("Debug Pass Bind Group"),
-                layout: &self.debug_depth_bind_group_layout,
-                entries: &[
-                    //depth texture
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&self.depth_view)
-                    },
-                    //depth samplers
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&self.depth_sampler),
-                    }
-                ]
-            });
+            self.debug_pass_bind_group = self.create_debug_pass_bind_group();
+            self.debug_view_target.resize(&self.device, new_size.width, new_size.height);
         }
     }
 
+    // depth or shadow map for debug_depth.wgsl, the other debug views use the debug view target
+    fn create_debug_pass_bind_group(&self) -> BindGroup {
+        let view = match self.debug_mode_texture {
+            DebugTexture::ShadowTexture => &self.shadow_texture_view,
+            _ => &self.depth_view,
+        };
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor{
+            label:Some("Debug Pass Bind Group"),
+            layout: &self.debug_depth_bind_group_layout,
+            entries: &[
+                //depth texture
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view)
+                },
+                //depth samplers
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.depth_sampler),
+                }
+            ]
+        })
+    }
+
     fn input(&mut self, event: &WindowEvent,) -> bool {
         match event {
             WindowEvent::KeyboardInput {
@@ -1224,23 +1659,85 @@ impl State {
                                     RenderOutputMode::Lit => {self.render_output_mode = RenderOutputMode::LitWithShadow; true}
                                     RenderOutputMode::LitWithShadow => {self.render_output_mode = RenderOutputMode::Wireframe; true}
                                     RenderOutputMode::Wireframe => {self.render_output_mode = RenderOutputMode::DebugLitWithShadow; true}
-                                    RenderOutputMode::DebugLitWithShadow => {self.render_output_mode = RenderOutputMode::Unlit; true}
+                                    RenderOutputMode::DebugLitWithShadow => {self.render_output_mode = RenderOutputMode::Validation; true}
+                                    RenderOutputMode::Validation => {self.render_output_mode = RenderOutputMode::WorldNormals; true}
+                                    RenderOutputMode::WorldNormals => {self.render_output_mode = RenderOutputMode::NormalMapSample; true}
+                                    RenderOutputMode::NormalMapSample => {self.render_output_mode = RenderOutputMode::UvCoordinates; true}
+                                    RenderOutputMode::UvCoordinates => {self.render_output_mode = RenderOutputMode::UvChecker; true}
+                                    RenderOutputMode::UvChecker => {self.render_output_mode = RenderOutputMode::TexelDensity; true}
+                                    RenderOutputMode::TexelDensity => {self.render_output_mode = RenderOutputMode::Unlit; true}
                                 } 
                             }
 
-                        VirtualKeyCode::F11 if *state == ElementState::Released => {
+                        VirtualKeyCode::N if *state == ElementState::Released => {
+                                self.vector_overlay.toggle(&self.queue, &self.models);
+                                true
+                            }
+                        VirtualKeyCode::Comma if *state == ElementState::Pressed => {
+                                self.vector_overlay.scale_length(&self.queue, &self.models, 1.0 / 1.25);
+                                true
+                            }
+                        VirtualKeyCode::Period if *state == ElementState::Pressed => {
+                                self.vector_overlay.scale_length(&self.queue, &self.models, 1.25);
+                                true
+                            }
+
+                        VirtualKeyCode::PageUp | VirtualKeyCode::PageDown if *state == ElementState::Pressed => {
+                                let factor = if *key == VirtualKeyCode::PageUp { 2.0 } else { 0.5 };
+                                match self.render_output_mode {
+                                    RenderOutputMode::UvChecker => {
+                                        self.debug_shading_params.checker_scale *= factor;
+                                        println!("checker cells per uv unit : {}", self.debug_shading_params.checker_scale);
+                                    }
+                                    RenderOutputMode::TexelDensity => {
+                                        self.debug_shading_params.target_texel_density *= factor;
+                                        println!("target texel density : {} texels per unit", self.debug_shading_params.target_texel_density);
+                                    }
+                                    //shift moves the near end of the range, without it the far end
+                                    RenderOutputMode::DebugLitWithShadow if matches!(self.debug_mode_texture, DebugTexture::DepthTexture) => {
+                                        let params = &mut self.debug_view_target.params;
+                                        if self.modifiers.shift() {
+                                            params.range_min = (params.range_min * factor).clamp(params.near, params.range_max * 0.5);
+                                        } else {
+                                            params.range_max = (params.range_max * factor).clamp(params.range_min * 2.0, params.far);
+                                        }
+                                        println!("depth range : {} to {}", params.range_min, params.range_max);
+                                        self.debug_view_target.write_params(&self.queue);
+                                        return true;
+                                    }
+                                    _ => return false,
+                                }
+                                self.queue.write_buffer(&self.debug_shading_params_buffer, 0, bytemuck::cast_slice(&[self.debug_shading_params]));
+                                true
+                            }
+
+                        //screenshot of the next frame, with shift a poster at KANIRENDERER_POSTER_SCALE times the window size
+                        VirtualKeyCode::F12 if *state == ElementState::Released => {
+                                if self.modifiers.shift() {
+                                    match capture::poster_scale().and_then(|scale| self.capture_poster(scale)) {
+                                        Result::Ok(path) => println!("rendering poster {:?}", path),
+                                        Err(err) => eprintln!("poster capture failed : {:#}", err),
+                                    }
+                                } else {
+                                    self.screenshot_requested = true;
+                                }
+                                true
+                            }
+
+                        //an embedded viewer's window belongs to the host
+                        VirtualKeyCode::F11 if *state == ElementState::Released && self.window.is_some() => {
                                 println!("updating window mode");
                                 match self.window_mode {
                                     WindowMode::Fullscreen => {
-                                        self.window.set_fullscreen(None);
+                                        self.window().set_fullscreen(None);
                                         self.window_mode = WindowMode::Windowed;
-                                        self.window.set_cursor_grab(window::CursorGrabMode::Confined);
+                                        self.window().set_cursor_grab(window::CursorGrabMode::Confined);
                                         true
                                     }
                                     WindowMode::Windowed => {
-                                        self.window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+                                        self.window().set_fullscreen(Some(Fullscreen::Borderless(None)));
                                         self.window_mode = WindowMode::Fullscreen;
-                                        self.window.set_cursor_grab(window::CursorGrabMode::Locked);
+                                        self.window().set_cursor_grab(window::CursorGrabMode::Locked);
                                         true
                                     }
                                 }
@@ -1249,81 +1746,47 @@ impl State {
                             match self.config.present_mode {
                                 wgpu::PresentMode::AutoVsync => {
                                     self.config.present_mode = wgpu::PresentMode::AutoNoVsync; 
-                                    self.surface.configure(&self.device, &self.config);
+                                    self.configure_surface();
                                     true
                                 },
                                 wgpu::PresentMode::AutoNoVsync => {
                                     self.config.present_mode = wgpu::PresentMode::Fifo;
-                                    self.surface.configure(&self.device, &self.config);
+                                    self.configure_surface();
                                     true
                                 },
                                 wgpu::PresentMode::Fifo => {
                                     self.config.present_mode = wgpu::PresentMode::Immediate;
-                                    self.surface.configure(&self.device, &self.config);
+                                    self.configure_surface();
                                     true
                                 },
                                 wgpu::PresentMode::FifoRelaxed => {
                                     self.config.present_mode = wgpu::PresentMode::Immediate;
-                                    self.surface.configure(&self.device, &self.config);
+                                    self.configure_surface();
                                     true},
                                 wgpu::PresentMode::Immediate => {
                                     self.config.present_mode = wgpu::PresentMode::AutoVsync;
-                                    self.surface.configure(&self.device, &self.config);
+                                    self.configure_surface();
                                     true
                                 },
                                 wgpu::PresentMode::Mailbox => {
                                     self.config.present_mode = wgpu::PresentMode::AutoVsync;
-                                    self.surface.configure(&self.device, &self.config);
+                                    self.configure_surface();
                                     true
                                 },
                             }
                         }
                         
                         VirtualKeyCode::Key1 if *state == ElementState::Released => {
-                                match self.debug_mode_texture {
-                                    DebugTexture::DepthTexture => {
-                                            self.debug_mode_texture = DebugTexture::ShadowTexture;
-
-                                            self.debug_pass_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor{
-                                                label:Some("Debug Pass Bind Group"),
-                                                layout: &self.debug_depth_bind_group_layout,
-                                                entries: &[
-                                                    //depth texture
-                                                    wgpu::BindGroupEntry {
-                                                        binding: 0,
-                                                        resource: wgpu::BindingResource::TextureView(&self.shadow_texture_view)
-                                                    },
-                                                    //depth samplers
-                                                    wgpu::BindGroupEntry {
-                                                        binding: 1,
-                                                        resource: wgpu::BindingResource::Sampler(&self.depth_sampler),
-                                                    }
-                                                ]
-                                            });
-                                            true
-                                        },
-
-                                    DebugTexture::ShadowTexture => {
-                                            self.debug_mode_texture = DebugTexture::DepthTexture;
-                                            self.debug_pass_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor{
-                                                label:Some("Debug Pass Bind Group"),
-                                                layout: &self.debug_depth_bind_group_layout,
-                                                entries: &[
-                                                    //depth texture
-                                                    wgpu::BindGroupEntry {
-                                                        binding: 0,
-                                                        resource: wgpu::BindingResource::TextureView(&self.depth_view)
-                                                    },
-                                                    //depth samplers
-                                                    wgpu::BindGroupEntry {
-                                                        binding: 1,
-                                                        resource: wgpu::BindingResource::Sampler(&self.depth_sampler),
-                                                    }
-                                                ]
-                                            });
-                                            true
-                                        },
-                                }
+                                self.debug_mode_texture = self.debug_mode_texture.next();
+                                self.debug_pass_bind_group = self.create_debug_pass_bind_group();
+                                self.debug_view_target.params.mode = self.debug_mode_texture.shader_mode();
+                                self.debug_view_target.write_params(&self.queue);
+                                println!("debug view : {}", self.debug_mode_texture.name());
+                                true
+                            }
+                        VirtualKeyCode::Key4 if *state == ElementState::Released => {
+                                self.debug_view_target.toggle_fullscreen(&self.queue);
+                                true
                             }
                         
                         VirtualKeyCode::Key2 if *state == ElementState::Pressed && self.directional_light.distance > -3000.0 => {
@@ -1358,6 +1821,14 @@ impl State {
                     }
                     
                 }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = *position;
+                false
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                false
+            }
             WindowEvent::MouseWheel { delta, .. } => {
                 self.camera_controller.process_scroll(delta);
                 true
@@ -1380,19 +1851,24 @@ impl State {
 
 
     fn update(&mut self, dt: instant::Duration) {
+        self.sync_entities();
         self.frame_time_graph.update(dt);
         self.camera_controller.update_camera(&mut self.camera, dt);
         self.movable_light_controller.update_light(&mut self.movable_light, &mut self.light_uniform, dt);
         self.camera_uniform.update_view_proj(&self.camera, &self.projection);
         //self.directional_light.rotate_light((4.0 * dt.as_secs_f32()), (10.0 * dt.as_secs_f32()), 0.0);
         self.directional_light_uniform_data = self.directional_light.generate_directional_light_data();
+
+        self.poll_model_loader(instant::Duration::from_millis(8));
+
+        self.poll_hot_reload();
         
         
         
         use rayon::prelude::*;
 
-        if self.models.len() > 1{
-            ///// test moving models, will be ignored when model len() !>1 ///
+        if self.movable_model_counts > 0{
+            ///// test moving models, will be ignored when no stress test models were spawned ///
             println!("chunking model update()");
             let chunk_size_f = self.models.len().to_f32().unwrap()/8.0;
             let chunk_size = chunk_size_f.ceil().to_usize().unwrap();
@@ -1690,7 +2166,7 @@ impl State {
         }
 
         // Generate vertices
-        let frametime_vertices = self.frame_time_graph.get_vertices(self.window.inner_size().width as f32, self.window.inner_size().height as f32);
+        let frametime_vertices = self.frame_time_graph.get_vertices(self.config.width as f32, self.config.height as f32);
 
         // Write to vertex buffer
         
@@ -1704,22 +2180,241 @@ impl State {
 
     }
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
+    // obj files replace the scene, or get added to it with shift held.
+    // images replace the diffuse texture of the mesh under the cursor, or the normal map with ctrl held
+    fn open_dropped_file(&mut self, path: &std::path::Path) {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        match extension.as_str() {
+            "obj" => self.open_model(path, self.modifiers.shift()),
+            "png" | "jpg" | "jpeg" | "tga" | "ktx2" | "dds" => self.replace_texture_at_cursor(path, self.modifiers.ctrl()),
+            _ => println!("unsupported file {:?}", path),
+        }
+    }
 
-        let view = output.texture.create_view(&wgpu:: TextureViewDescriptor{
-                format: Some(self.config.format), 
-                ..Default::default()
+    fn open_model(&mut self, path: &std::path::Path, add_to_scene: bool) {
+        if let Some(model_loader) = &self.model_loader {
+            println!("still loading {:?}, ignoring {:?}", model_loader.file_name, path);
+            return;
+        }
+        if !add_to_scene {
+            self.clear_models();
+            self.movable_model_counts = 0;
+            if let Some(hot_reloader) = &mut self.hot_reloader {
+                hot_reloader.unwatch_models();
             }
-        );
+            self.texture_cache.remove_unused();
+        }
+        let file_name = path.to_string_lossy().to_string();
+        self.model_loader = Some(loader::ModelLoader::spawn(file_name, self.file_type.clone(), None, &self.texture_cache, self.device.features()));
+    }
 
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
-        });
+    fn replace_texture_at_cursor(&mut self, path: &std::path::Path, is_normal_map: bool) {
+        let Some(ray) = picking::ray_from_cursor(&self.camera, &self.projection, self.cursor_position.x as f32, self.cursor_position.y as f32, self.config.width, self.config.height) else {
+            return;
+        };
+        let Some(hit) = picking::pick(&self.models, &ray) else {
+            println!("no mesh under the cursor for {:?}", path);
+            return;
+        };
+        let material_index = self.models[hit.model].meshes[hit.mesh].material;
+        let Some(material) = self.models[hit.model].materials.get(material_index) else {
+            return;
+        };
+
+        //keep the opengl flip of whatever texture the material used before
+        let old_texture = if is_normal_map { &material.normal_texture } else { &material.diffuse_texture };
+        let opengl = self.texture_cache
+            .keys()
+            .into_iter()
+            .find(|key| self.texture_cache.get(key).is_some_and(|t| Arc::ptr_eq(&t, old_texture)))
+            .map_or(self.file_type == "opengl", |key| key.opengl);
+
+        let key = texture::TextureKey { path: path.to_path_buf(), is_normal_map, opengl };
+        let new_texture = match self.texture_cache.get(&key) {
+            Some(texture) => texture,
+            None => {
+                let decoded = resources::decode_material_texture(&key, self.device.features());
+                match texture::Texture::from_decoded(&self.device, &self.queue, &decoded) {
+                    Ok(texture) => self.texture_cache.insert(key, texture),
+                    Err(err) => {
+                        println!("failed to load {:?} : {:?}", path, err);
+                        return;
+                    }
+                }
+            }
+        };
+
+        let (diffuse, normal) = if is_normal_map {
+            (material.diffuse_texture.clone(), new_texture)
+        } else {
+            (new_texture, material.normal_texture.clone())
+        };
+        println!("replacing {} texture of material {:?} with {:?}", if is_normal_map { "normal" } else { "diffuse" }, material.name, path);
+        let material = model::Material::new(&self.device, &material.name, diffuse, normal, &self.texture_bind_group_layout);
+        self.models[hit.model].materials[material_index] = material;
+
+        if let Some(hot_reloader) = &mut self.hot_reloader {
+            hot_reloader.watch(path, hot_reload::ReloadTarget::Texture);
+        }
+        //a running load counts on the textures that were cached when it started, it cleans up once it's done
+        if self.model_loader.is_none() {
+            self.texture_cache.remove_unused();
+        }
+    }
+
+    fn watch_model_files(&mut self, model_loader: &loader::ModelLoader) {
+        let (Some(hot_reloader), Some(index)) = (&mut self.hot_reloader, model_loader.model_index()) else {
+            return;
+        };
+        hot_reloader.unwatch_model(index);
+        //obj and mtl files rebuild the whole model, textures only their materials
+        for path in &model_loader.model_paths {
+            hot_reloader.watch(path, hot_reload::ReloadTarget::Model {
+                index,
+                file_name: model_loader.file_name.clone(),
+                file_type: model_loader.file_type.clone(),
+            });
+        }
+        for path in model_loader.texture_paths() {
+            hot_reloader.watch(&path, hot_reload::ReloadTarget::Texture);
+        }
+    }
+
+    fn poll_hot_reload(&mut self) {
+        let Some(hot_reloader) = &mut self.hot_reloader else {
+            return;
+        };
+        for (path, target) in hot_reloader.poll() {
+            println!("{:?} changed, reloading", path);
+            match target {
+                hot_reload::ReloadTarget::Model { index, file_name, file_type } => {
+                    if self.model_loader.is_some() {
+                        println!("still loading, ignoring change to {:?}", path);
+                        continue;
+                    }
+                    self.model_loader = Some(loader::ModelLoader::spawn(file_name, file_type, Some(index), &self.texture_cache, self.device.features()));
+                }
+                hot_reload::ReloadTarget::Texture => self.reload_texture(&path),
+                hot_reload::ReloadTarget::Shader(name) => self.reload_shader(&name),
+            }
+        }
+    }
+
+    // re-decode every cached variant of the file and point materials at the new textures
+    fn reload_texture(&mut self, path: &std::path::Path) {
+        let keys = self.texture_cache
+            .keys()
+            .into_iter()
+            .filter(|key| !key.is_fallback() && hot_reload::normalize_path(&key.path) == *path)
+            .collect::<Vec<_>>();
+
+        for key in keys {
+            let decoded = resources::decode_material_texture(&key, self.device.features());
+            let texture = match texture::Texture::from_decoded(&self.device, &self.queue, &decoded) {
+                Ok(texture) => texture,
+                Err(err) => {
+                    println!("failed to reload {:?} : {:?}", key.path, err);
+                    continue;
+                }
+            };
+            let Some(old_texture) = self.texture_cache.get(&key) else {
+                continue;
+            };
+            let new_texture = self.texture_cache.insert(key, texture);
+
+            for model in &mut self.models {
+                for material in &mut model.materials {
+                    let diffuse_changed = Arc::ptr_eq(&material.diffuse_texture, &old_texture);
+                    let normal_changed = Arc::ptr_eq(&material.normal_texture, &old_texture);
+                    if !diffuse_changed && !normal_changed {
+                        continue;
+                    }
+                    let diffuse = if diffuse_changed { new_texture.clone() } else { material.diffuse_texture.clone() };
+                    let normal = if normal_changed { new_texture.clone() } else { material.normal_texture.clone() };
+                    *material = model::Material::new(&self.device, &material.name, diffuse, normal, &self.texture_bind_group_layout);
+                }
+            }
+        }
+    }
+
+    // rebuild the pipelines using this shader, a shader that fails to compile keeps the old pipeline
+    fn reload_shader(&mut self, name: &str) {
+        let source = shaders::load_shader(name);
+        let shader = |label| wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.clone().into()),
+        };
+        let model_buffers = [model::ModelVertex::desc(), model::InstanceRaw::desc()];
+        let device = &self.device;
+        let format = self.config.format;
+
+        match name {
+            "unlit_shader.wgsl" => if let Some(pipeline) = validated(device, name, || create_render_pipeline(device, &self.unlit_render_pipeline_layout, format, Some(wgpu::TextureFormat::Depth32Float), &model_buffers, shader("Unlit Shader"))) {
+                self.unlit_render_pipeline = pipeline;
+            },
+            "lit_shader.wgsl" => if let Some(pipeline) = validated(device, name, || create_render_pipeline(device, &self.lit_render_pipeline_layout, format, Some(wgpu::TextureFormat::Depth32Float), &model_buffers, shader("Lit Without Shadow Map Shader"))) {
+                self.lit_render_pipeline = pipeline;
+            },
+            "shader.wgsl" | "shader_hdr.wgsl" => {
+                if name != main_shader_name(format) {
+                    return;
+                }
+                if let Some(pipeline) = validated(device, name, || create_render_pipeline(device, &self.render_pipeline_layout, format, Some(wgpu::TextureFormat::Depth32Float), &model_buffers, shader("Normal Shader"))) {
+                    self.render_pipeline = pipeline;
+                }
+                if let Some(pipelines) = validated(device, name, || create_debug_view_lighting_pipelines(device, &self.render_pipeline_layout, &source)) {
+                    self.debug_view_lighting_pipelines = pipelines;
+                }
+            }
+            "shader_wireframe.wgsl" => if let Some(pipeline) = validated(device, name, || create_wireframe_pipeline(device, &self.render_pipeline_layout, format, Some(wgpu::TextureFormat::Depth32Float), &model_buffers, shader("Wireframe Shader"))) {
+                self.wireframe_pipeline = pipeline;
+            },
+            "shadow.wgsl" => if let Some(pipeline) = validated(device, name, || create_shadow_pipeline(device, &self.shadow_pipeline_layout, shader("Shadow Shader"))) {
+                self.shadow_pipeline = pipeline;
+            },
+            "debug_depth.wgsl" => if let Some(pipeline) = validated(device, name, || create_debug_pass_pipeline(device, &self.debug_pass_pipeline_layout, format, shader("Debug Depth Shader"))) {
+                self.debug_pass_pipeline = pipeline;
+            },
+            "debug_view.wgsl" => if let Some(pipeline) = validated(device, name, || create_debug_pass_pipeline(device, &self.debug_view_pipeline_layout, format, shader("Debug View Shader"))) {
+                self.debug_view_pipeline = pipeline;
+            },
+            "frametime.wgsl" => if let Some(pipeline) = validated(device, name, || create_frame_time_pipeline(device, format, shader("Frame Time Shader"))) {
+                self.frame_time_render_pipeline = pipeline;
+            },
+            "loading.wgsl" => if let Some(loading_screen) = validated(device, name, || loader::LoadingScreen::new(device, format)) {
+                self.loading_screen = loading_screen;
+            },
+            "validation.wgsl" => if let Some(overlay) = validated(device, name, || validation::OverlayRenderer::new(device, format, &self.camera_bind_group_layout)) {
+                self.validation_overlay = overlay;
+            },
+            "vectors.wgsl" => if let Some(pipeline) = validated(device, name, || self.vector_overlay.reload(device, format)) {
+                self.vector_overlay.pipeline = pipeline;
+            },
+            "debug_shading.wgsl" => {
+                let pipelines = DEBUG_SHADING_ENTRIES.map(|fragment_entry| validated(device, name, || create_render_pipeline_with_entry(device, &self.debug_shading_pipeline_layout, format, Some(wgpu::TextureFormat::Depth32Float), &model_buffers, shader("Debug Shading Shader"), fragment_entry)));
+                if pipelines.iter().all(Option::is_some) {
+                    self.debug_shading_pipelines = pipelines.map(Option::unwrap);
+                }
+                if let Some(pipelines) = validated(device, name, || create_debug_view_shading_pipelines(device, &self.debug_shading_pipeline_layout, &source)) {
+                    self.debug_view_shading_pipelines = pipelines;
+                }
+            }
+            _ => println!("no pipeline uses {}", name),
+        }
+    }
+
+    // shadow and main passes of the current render mode into any target, used for the window
+    // and for offscreen captures. overlays like the debug view and frame time graph are left out
+    fn encode_scene(&self, encoder: &mut wgpu::CommandEncoder, view: &TextureView, depth_view: &TextureView) {
+        //the rayon closures below can't borrow self, it isn't Sync
+        let shadow_pass_light_bind_group = &self.shadow_pass_light_bind_group;
+        let camera_bind_group = &self.camera_bind_group;
+        let light_bind_group = &self.light_bind_group;
+        let shadow_bind_group = &self.shadow_bind_group;
 
         //shadow pass
         match self.render_output_mode{
-            RenderOutputMode::LitWithShadow | RenderOutputMode::DebugLitWithShadow => {
+            RenderOutputMode::LitWithShadow | RenderOutputMode::DebugLitWithShadow | RenderOutputMode::Validation => {
                 let mut shadow_pass = Arc::new(Mutex::new(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                     label: Some("Shadow Pass"),
                     color_attachments: &[], // No color output
@@ -1733,17 +2428,17 @@ impl State {
                     }),
                 })));
                 shadow_pass.lock().unwrap().set_pipeline(&self.shadow_pipeline);
-                shadow_pass.lock().unwrap().set_bind_group(0, &self.shadow_pass_light_bind_group, &[]);
+                shadow_pass.lock().unwrap().set_bind_group(0, shadow_pass_light_bind_group, &[]);
                 &self.models.par_iter().for_each(|model|{
                     let vb =model.instance_buffer.slice(..);
                     let mut locked_sp = shadow_pass.lock().unwrap();
                     locked_sp.set_vertex_buffer(1, vb );
-                    for mesh in &model.meshes{
+                    for (mesh_index, mesh) in model.meshes.iter().enumerate(){
                         locked_sp.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
                         locked_sp.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                        locked_sp.set_bind_group(0, &self.shadow_pass_light_bind_group, &[]);
-                        locked_sp.set_bind_group(1, &self.camera_bind_group, &[]);
-                        locked_sp.draw_indexed(0..mesh.num_elements, 0, (0..model.instances.len() as u32).clone());
+                        locked_sp.set_bind_group(0, shadow_pass_light_bind_group, &[]);
+                        locked_sp.set_bind_group(1, camera_bind_group, &[]);
+                        locked_sp.draw_indexed(0..mesh.num_elements, 0, model.instance_range(mesh_index));
                     }
                 });
             }
@@ -1755,7 +2450,7 @@ impl State {
             let mut render_pass = Arc::new(Mutex::new(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -1768,7 +2463,7 @@ impl State {
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_view,
+                    view: depth_view,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Clear(1.0),
                         store: true,
@@ -1786,15 +2481,15 @@ impl State {
                         let mut locked_rp = render_pass.lock().unwrap();
                         locked_rp.set_vertex_buffer(1, vb );
 
-                        for mesh in &model.meshes{
+                        for (mesh_index, mesh) in model.meshes.iter().enumerate(){
                             locked_rp.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
                             locked_rp.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
                             if !&model.materials.is_empty() {
                                 let material = &model.materials[mesh.material];
                                 locked_rp.set_bind_group(0, &material.bind_group, &[]);
                             }
-                            locked_rp.set_bind_group(1, &self.camera_bind_group, &[]);
-                            locked_rp.draw_indexed(0..mesh.num_elements, 0, (0..model.instances.len() as u32).clone());
+                            locked_rp.set_bind_group(1, camera_bind_group, &[]);
+                            locked_rp.draw_indexed(0..mesh.num_elements, 0, model.instance_range(mesh_index));
                         }
                     });
                 }
@@ -1805,16 +2500,16 @@ impl State {
                         let mut locked_rp = render_pass.lock().unwrap();
                         locked_rp.set_vertex_buffer(1, vb );
 
-                        for mesh in &model.meshes{
+                        for (mesh_index, mesh) in model.meshes.iter().enumerate(){
                             locked_rp.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
                             locked_rp.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
                             if !&model.materials.is_empty() {
                                 let material = &model.materials[mesh.material];
                                 locked_rp.set_bind_group(0, &material.bind_group, &[]);
                             }
-                            locked_rp.set_bind_group(1, &self.camera_bind_group, &[]);
-                            locked_rp.set_bind_group(2, &self.light_bind_group, &[]);
-                            locked_rp.draw_indexed(0..mesh.num_elements, 0, (0..model.instances.len() as u32).clone());
+                            locked_rp.set_bind_group(1, camera_bind_group, &[]);
+                            locked_rp.set_bind_group(2, light_bind_group, &[]);
+                            locked_rp.draw_indexed(0..mesh.num_elements, 0, model.instance_range(mesh_index));
                         }
                     });
                 }
@@ -1826,11 +2521,11 @@ impl State {
                         let mut locked_rp = render_pass.lock().unwrap();
                         locked_rp.set_vertex_buffer(1, vb );
 
-                        locked_rp.draw_model_instanced(model, 0..model.instances.len() as u32, &self.camera_bind_group, &self.light_bind_group, &self.shadow_bind_group);
+                        locked_rp.draw_model_instanced(model, camera_bind_group, light_bind_group, shadow_bind_group);
                     });
                     // for model in &self.models {
                     //     render_pass.lock().unwrap().set_vertex_buffer(1, model.instance_buffer.slice(..) );
-                    //     render_pass.lock().unwrap().draw_model_instanced(model, 0..model.instances.len() as u32, &self.camera_bind_group, &self.light_bind_group)
+                    //     render_pass.lock().unwrap().draw_model_instanced(model, &self.camera_bind_group, &self.light_bind_group)
                     // }
                 }
                 RenderOutputMode::Wireframe => {
@@ -1840,12 +2535,12 @@ impl State {
                         let vb =model.instance_buffer.slice(..);
                         let mut locked_rp = render_pass.lock().unwrap();
                         locked_rp.set_vertex_buffer(1, vb );
-                        locked_rp.draw_model_instanced(model, 0..model.instances.len() as u32, &self.camera_bind_group, &self.light_bind_group, &self.shadow_bind_group);
+                        locked_rp.draw_model_instanced(model, camera_bind_group, light_bind_group, shadow_bind_group);
                     });
                     // for model in &self.models{
                     //     render_pass.lock().unwrap().set_vertex_buffer(1, model.instance_buffer.slice(..) );
 
-                    //     render_pass.lock().unwrap().draw_model_instanced(model, 0..model.instances.len() as u32, &self.camera_bind_group, &self.light_bind_group);
+                    //     render_pass.lock().unwrap().draw_model_instanced(model, &self.camera_bind_group, &self.light_bind_group);
                     // }
                 }
                 RenderOutputMode::DebugLitWithShadow => {
@@ -1855,15 +2550,100 @@ impl State {
                         let mut locked_rp = render_pass.lock().unwrap();
                         locked_rp.set_vertex_buffer(1, vb );
 
-                        locked_rp.draw_model_instanced(model, 0..model.instances.len() as u32, &self.camera_bind_group, &self.light_bind_group, &self.shadow_bind_group);
+                        locked_rp.draw_model_instanced(model, camera_bind_group, light_bind_group, shadow_bind_group);
                     });
                 }
+                RenderOutputMode::Validation => {
+                    let mut locked_rp = render_pass.lock().unwrap();
+                    locked_rp.set_pipeline(&self.render_pipeline);
+                    for model in &self.models {
+                        locked_rp.set_vertex_buffer(1, model.instance_buffer.slice(..));
+                        locked_rp.draw_model_instanced(model, camera_bind_group, light_bind_group, shadow_bind_group);
+                    }
+                    self.validation_overlay.render(&mut locked_rp, &self.models, camera_bind_group);
+                }
+                RenderOutputMode::WorldNormals | RenderOutputMode::NormalMapSample | RenderOutputMode::UvCoordinates | RenderOutputMode::UvChecker | RenderOutputMode::TexelDensity => {
+                    let pipeline = &self.debug_shading_pipelines[self.render_output_mode.debug_shading_index().unwrap()];
+                    let mut locked_rp = render_pass.lock().unwrap();
+                    locked_rp.set_pipeline(pipeline);
+                    locked_rp.set_bind_group(2, &self.debug_shading_bind_group, &[]);
+                    for model in &self.models {
+                        locked_rp.set_vertex_buffer(1, model.instance_buffer.slice(..));
+                        for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+                            locked_rp.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                            locked_rp.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                            if !model.materials.is_empty() {
+                                locked_rp.set_bind_group(0, &model.materials[mesh.material].bind_group, &[]);
+                            }
+                            locked_rp.set_bind_group(1, camera_bind_group, &[]);
+                            locked_rp.draw_indexed(0..mesh.num_elements, 0, model.instance_range(mesh_index));
+                        }
+                    }
+                }
+            }
+
+            if self.vector_overlay.enabled {
+                self.vector_overlay.render(&mut render_pass.lock().unwrap(), &self.models, camera_bind_group);
             }
         }
+    }
 
+    // the debug view drawn over the scene in DebugLitWithShadow mode, nothing in the other modes
+    fn encode_debug_view(&self, encoder: &mut wgpu::CommandEncoder, view: &TextureView) {
         //debug pass
         match self.render_output_mode {
             RenderOutputMode::DebugLitWithShadow => {
+                let scene_pipeline = match self.debug_mode_texture {
+                    DebugTexture::Normals => Some(&self.debug_view_shading_pipelines[0]),
+                    DebugTexture::Albedo => Some(&self.debug_view_shading_pipelines[1]),
+                    DebugTexture::Overdraw => Some(&self.debug_view_shading_pipelines[2]),
+                    DebugTexture::Lighting => Some(&self.debug_view_lighting_pipelines[0]),
+                    DebugTexture::ShadowCascade => Some(&self.debug_view_lighting_pipelines[1]),
+                    DebugTexture::DepthTexture | DebugTexture::ShadowTexture => None,
+                };
+                //render the scene again into the debug view target
+                if let Some(pipeline) = scene_pipeline {
+                    use crate::model::DrawModel;
+                    let main_shader = matches!(self.debug_mode_texture, DebugTexture::Lighting | DebugTexture::ShadowCascade);
+                    let mut scene_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Debug View Scene Pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &self.debug_view_target.color_view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: &self.debug_view_target.depth_view,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: false,
+                            }),
+                            stencil_ops: None,
+                        }),
+                    });
+                    scene_pass.set_pipeline(pipeline);
+                    for model in &self.models {
+                        scene_pass.set_vertex_buffer(1, model.instance_buffer.slice(..));
+                        if main_shader {
+                            scene_pass.draw_model_instanced(model, &self.camera_bind_group, &self.light_bind_group, &self.shadow_bind_group);
+                            continue;
+                        }
+                        for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+                            scene_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                            scene_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                            if !model.materials.is_empty() {
+                                scene_pass.set_bind_group(0, &model.materials[mesh.material].bind_group, &[]);
+                            }
+                            scene_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+                            scene_pass.set_bind_group(2, &self.debug_shading_bind_group, &[]);
+                            scene_pass.draw_indexed(0..mesh.num_elements, 0, model.instance_range(mesh_index));
+                        }
+                    }
+                }
+
                 let mut debug_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor{
                     label: Some("Debug Pass"),
                     color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -1878,8 +2658,14 @@ impl State {
                     //..Default::default()
                 });
 
-                debug_pass.set_pipeline(&self.debug_pass_pipeline);
-                debug_pass.set_bind_group(0, &self.debug_pass_bind_group, &[]);
+                if scene_pipeline.is_some() {
+                    debug_pass.set_pipeline(&self.debug_view_pipeline);
+                    debug_pass.set_bind_group(0, &self.debug_view_target.view_bind_group, &[]);
+                } else {
+                    debug_pass.set_pipeline(&self.debug_pass_pipeline);
+                    debug_pass.set_bind_group(0, &self.debug_pass_bind_group, &[]);
+                }
+                debug_pass.set_bind_group(1, &self.debug_view_target.params_bind_group, &[]);
                 debug_pass.set_vertex_buffer(0, self.debug_vertex_buffer.slice(..));
                 debug_pass.set_index_buffer(self.debug_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
                 debug_pass.draw_indexed(0..self.debug_indices.len() as u32, 0, 0..1);
@@ -1888,6 +2674,179 @@ impl State {
                 //skip debug pass
                 }
         }
+    }
+
+    // renders the scene at scale times the window size, in tiles when that's bigger than a texture can be.
+    // returns where the png is going to be written once it's encoded
+    fn capture_poster(&mut self, scale: u32) -> anyhow::Result<std::path::PathBuf> {
+        let width = self.config.width.checked_mul(scale).ok_or(anyhow::anyhow!("poster size overflows"))?;
+        let height = self.config.height.checked_mul(scale).ok_or(anyhow::anyhow!("poster size overflows"))?;
+        let max_size = self.device.limits().max_texture_dimension_2d;
+        self.validate_meshes();
+        let (tile_width, tile_height) = (width.min(max_size), height.min(max_size));
+        println!("rendering {}x{} poster in {} tiles", width, height, ((width + tile_width - 1) / tile_width) * ((height + tile_height - 1) / tile_height));
+
+        let tile_size = wgpu::Extent3d {
+            width: tile_width,
+            height: tile_height,
+            depth_or_array_layers: 1,
+        };
+        let color_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Poster Tile Texture"),
+            size: tile_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Poster Tile Depth Texture"),
+            size: tile_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut poster = image::RgbaImage::new(width, height);
+        let mut result = Result::Ok(());
+        'tiles: for y in (0..height).step_by(tile_height as usize) {
+            for x in (0..width).step_by(tile_width as usize) {
+                let clip = capture::tile_clip_transform(x, y, tile_width, tile_height, width, height);
+                self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform.with_clip_transform(clip)]));
+
+                let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Poster Encoder"),
+                });
+                self.encode_scene(&mut encoder, &color_view, &depth_view);
+                let tile = capture::PendingCapture::new(&self.device, &mut encoder, &color_texture);
+                self.queue.submit(std::iter::once(encoder.finish()));
+                match tile.read(&self.device) {
+                    //tiles on the right and bottom edge hang over the poster, replace crops them
+                    Result::Ok(tile) => image::imageops::replace(&mut poster, &tile, x as i64, y as i64),
+                    Err(err) => {
+                        result = Err(err);
+                        break 'tiles;
+                    }
+                }
+            }
+        }
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        result?;
+
+        let path = capture::timestamped_path(std::path::Path::new("."), "poster");
+        capture::save_in_background(poster, path.clone());
+        Ok(path)
+    }
+
+    // one frame of the current mode into a texture the size of config, without the loading bar
+    // and frame time graph so the same scene always gives the same image
+    fn render_offscreen(&mut self) -> anyhow::Result<image::RgbaImage> {
+        let size = wgpu::Extent3d {
+            width: self.config.width,
+            height: self.config.height,
+            depth_or_array_layers: 1,
+        };
+        let color_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        self.validate_meshes();
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Offscreen Encoder"),
+        });
+        self.encode_scene(&mut encoder, &view, &self.depth_view);
+        self.encode_debug_view(&mut encoder, &view);
+        let capture = capture::PendingCapture::new(&self.device, &mut encoder, &color_texture);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        capture.read(&self.device)
+    }
+
+    // uploads what the loading thread finished within time_budget, at least one event per call
+    pub(crate) fn poll_model_loader(&mut self, time_budget: instant::Duration) {
+        let Some(model_loader) = &mut self.model_loader else {
+            return;
+        };
+        let meshes_done = model_loader.progress.meshes_done;
+        let status = model_loader.poll(&self.device, &self.queue, &self.texture_bind_group_layout, &mut self.models, &mut self.texture_cache, time_budget);
+        self.loading_screen.update(&self.queue, &model_loader.progress);
+        let new_meshes = model_loader.progress.meshes_done != meshes_done;
+        match status {
+            loader::LoadStatus::Loading => {
+                //meshes get their entities as they come in, otherwise they'd be drawn with no instances until the load finishes
+                if new_meshes {
+                    self.attach_mesh_entities();
+                    self.sync_entities();
+                }
+            }
+            loader::LoadStatus::Finished => {
+                if let Some(model_loader) = self.model_loader.take() {
+                    self.watch_model_files(&model_loader);
+                }
+                self.texture_cache.remove_unused();
+                //a hot reloaded model comes with a fresh instance buffer
+                self.attach_mesh_entities();
+                self.sync_entities();
+            }
+            loader::LoadStatus::Failed(err) => {
+                println!("failed to load {:?} : {}", model_loader.file_name, err);
+                self.model_load_error = Some(err);
+                self.model_loader = None;
+            }
+        }
+    }
+
+    // keeps polling the loader until the model is uploaded, for callers without an event loop
+    fn wait_for_model(&mut self) -> anyhow::Result<()> {
+        while self.model_loader.is_some() {
+            self.update(instant::Duration::ZERO);
+            //poll returns straight away while the worker is still parsing
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        match self.model_load_error.take() {
+            Some(err) => Err(anyhow::anyhow!(err)),
+            None => Result::Ok(()),
+        }
+    }
+
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.validate_meshes();
+        let Some(surface) = &self.surface else {
+            return Result::Ok(());
+        };
+        let output = surface.get_current_texture()?;
+
+        let view = output.texture.create_view(&wgpu:: TextureViewDescriptor{
+                format: Some(self.config.format), 
+                ..Default::default()
+            }
+        );
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
+
+        self.encode_scene(&mut encoder, &view, &self.depth_view);
+        self.encode_debug_view(&mut encoder, &view);
+
+        //loading progress bar
+        if self.model_loader.is_some() {
+            self.loading_screen.render(&mut encoder, &view);
+        }
 
         //frame time graph pass
         match self.render_output_mode {
@@ -1913,8 +2872,21 @@ impl State {
             _ => {}
         }
         
+        let screenshot = if self.screenshot_requested {
+            self.screenshot_requested = false;
+            Some(capture::PendingCapture::new(&self.device, &mut encoder, &output.texture))
+        } else {
+            None
+        };
+
         self.queue.submit(std::iter::once(encoder.finish()));
 
+        if let Some(screenshot) = screenshot {
+            match screenshot.read(&self.device) {
+                Result::Ok(image) => capture::save_in_background(image, capture::timestamped_path(std::path::Path::new("."), "screenshot")),
+                Err(err) => eprintln!("screenshot failed : {:#}", err),
+            }
+        }
         
         //experiment with picking value from texture
         //todo color id pass
@@ -2047,12 +3019,28 @@ impl State {
 
 }
 
+// run f with validation errors captured instead of panicking, None if anything failed
+fn validated<T>(device: &wgpu::Device, name: &str, f: impl FnOnce() -> T) -> Option<T> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = f();
+    match pollster::block_on(device.pop_error_scope()) {
+        None => {
+            println!("reloaded {}", name);
+            Some(value)
+        }
+        Some(err) => {
+            println!("{} failed to compile, keeping the old pipeline\n{}", name, err);
+            None
+        }
+    }
+}
+
 fn align_up(value: u32, alignment: u32) -> u32 {
     (value + alignment - 1) / alignment * alignment
 }
 
 pub async fn run(file_path: String, file_type:String, fullscreen_mode: String, use_hdr: bool) {
-    let event_loop = EventLoop::new();
+    let event_loop = EventLoopBuilder::<remote::RemoteRequest>::with_user_event().build();
     let window_size: PhysicalSize<u32> = PhysicalSize { width: 1440, height: 1080 };
     //let icon = get_icon();
     let window = WindowBuilder::new()
@@ -2088,6 +3076,17 @@ pub async fn run(file_path: String, file_type:String, fullscreen_mode: String, u
     let mut state = State::new(window, file_path, file_type, use_hdr, window_mode).await;
     let mut last_render_time = instant::Instant::now();
 
+    //the viewer still opens when the port is taken or there's no token, just without remote control
+    match remote::port_from_env() {
+        Ok(Some(port)) => {
+            if let Err(err) = remote::token_from_env().and_then(|token| remote::start(port, token, event_loop.create_proxy())) {
+                println!("{:#}", err);
+            }
+        }
+        Ok(None) => {}
+        Err(err) => println!("{:#}", err),
+    }
+
     event_loop.run(move | event, _, control_flow | {*control_flow = ControlFlow::Poll; match event {
         Event::DeviceEvent { event: DeviceEvent::MouseMotion{delta,}, .. } => {
             match state.left_mouse_pressed{
@@ -2124,7 +3123,8 @@ pub async fn run(file_path: String, file_type:String, fullscreen_mode: String, u
                     }
 
                     WindowEvent::DroppedFile(file_path) => {
-                        println!("File Dropped, File Path: {:?}", file_path)
+                        println!("File Dropped, File Path: {:?}", file_path);
+                        state.open_dropped_file(file_path);
                     }
 
                     WindowEvent::HoveredFile(file_path) => {
@@ -2162,6 +3162,8 @@ pub async fn run(file_path: String, file_type:String, fullscreen_mode: String, u
             state.window().request_redraw();
         }
 
+        Event::UserEvent(request) => state.handle_remote(request),
+
         _ => {}
     }}
     );
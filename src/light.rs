@@ -198,6 +198,12 @@ impl MovableLightController {
         }
     }
 
+    // update_light copies these into the uniform every frame, so they have to be set here
+    pub fn set_light(&mut self, light_color: Vector3<f32>, range: f32) {
+        self.light_color = light_color;
+        self.range = range;
+    }
+
     pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
         let amount = if state == ElementState::Pressed { 1.0 } else { 0.0 };
 
@@ -0,0 +1,354 @@
+// meshes streamed in by other programs (a blender add-on, a generator script...) through the
+// remote control server. a pushed mesh replaces the buffers of the loaded mesh with the same name,
+// its material and textures are left alone. a name that isn't loaded becomes a model of its own.
+// the last pushed attributes are kept so an update only has to send what changed
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::{anyhow, Context};
+use cgmath::{InnerSpace, Vector3};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use wgpu::util::DeviceExt;
+use crate::{model, resources, texture, State};
+
+pub(crate) type LiveMeshes = HashMap<String, LiveMesh>;
+
+pub(crate) struct LiveMesh {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+// flat arrays, xyz per vertex for positions and normals, uv per vertex, three indices per triangle.
+// missing normals are generated from the triangles, missing uvs are zero
+#[derive(Deserialize, Default)]
+pub struct MeshUpdate {
+    pub name: String,
+    pub positions: Option<Vec<f32>>,
+    pub normals: Option<Vec<f32>>,
+    pub uvs: Option<Vec<f32>>,
+    pub indices: Option<Vec<u32>>,
+    // name of a material of any loaded model
+    pub material: Option<String>,
+}
+
+fn chunked<const N: usize>(values: &[f32], what: &str) -> anyhow::Result<Vec<[f32; N]>> {
+    if values.len() % N != 0 {
+        return Err(anyhow!("{} has {} values, not a multiple of {}", what, values.len(), N));
+    }
+    Ok(values.chunks_exact(N).map(|chunk| chunk.try_into().unwrap()).collect())
+}
+
+// area weighted average of the face normals around each vertex
+fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(positions[triangle[i] as usize]));
+        let face = (b - a).cross(c - a);
+        for &index in triangle {
+            normals[index as usize] += face;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| if normal.magnitude2() > 0.0 { normal.normalize().into() } else { [0.0, 1.0, 0.0] })
+        .collect()
+}
+
+impl LiveMesh {
+    // previous is what was pushed for this name before, if it's still loaded
+    fn merge(update: &MeshUpdate, previous: Option<&LiveMesh>) -> anyhow::Result<Self> {
+        let positions = match (&update.positions, &previous) {
+            (Some(positions), _) => chunked::<3>(positions, "positions")?,
+            (None, Some(previous)) => previous.positions.clone(),
+            (None, None) => return Err(anyhow!("{:?} has not been pushed before, send positions and indices", update.name)),
+        };
+        let vertex_count = positions.len();
+        let indices = match (&update.indices, &previous) {
+            (Some(indices), _) => indices.clone(),
+            (None, Some(previous)) => previous.indices.clone(),
+            (None, None) => return Err(anyhow!("{:?} has not been pushed before, send positions and indices", update.name)),
+        };
+        if indices.len() % 3 != 0 {
+            return Err(anyhow!("indices has {} values, not a multiple of 3", indices.len()));
+        }
+        if let Some(index) = indices.iter().find(|index| **index as usize >= vertex_count) {
+            return Err(anyhow!("index {} is out of range for {} vertices", index, vertex_count));
+        }
+
+        //old normals only fit while the shape stays the same
+        let normals = match (&update.normals, &previous) {
+            (Some(normals), _) => chunked::<3>(normals, "normals")?,
+            (None, Some(previous)) if update.positions.is_none() && update.indices.is_none() => previous.normals.clone(),
+            _ => smooth_normals(&positions, &indices),
+        };
+        let uvs = match (&update.uvs, &previous) {
+            (Some(uvs), _) => chunked::<2>(uvs, "uvs")?,
+            (None, Some(previous)) if previous.uvs.len() == vertex_count => previous.uvs.clone(),
+            _ => vec![[0.0, 0.0]; vertex_count],
+        };
+        if normals.len() != vertex_count || uvs.len() != vertex_count {
+            return Err(anyhow!("{} positions but {} normals and {} uvs", vertex_count, normals.len(), uvs.len()));
+        }
+        Ok(Self { positions, normals, uvs, indices })
+    }
+
+    fn vertices(&self) -> Vec<model::ModelVertex> {
+        let mut vertices = (0..self.positions.len())
+            .map(|i| model::ModelVertex {
+                position: self.positions[i],
+                tex_coords: self.uvs[i],
+                normal: self.normals[i],
+                tangent: [0.0; 3],
+                bitangent: [0.0; 3],
+            })
+            .collect::<Vec<_>>();
+        resources::generate_tangents(&mut vertices, &self.indices);
+
+        //triangles without uvs give nan tangents, any basis around the normal does there
+        for vertex in &mut vertices {
+            if vertex.tangent.iter().chain(&vertex.bitangent).all(|value| value.is_finite()) {
+                continue;
+            }
+            let normal = Vector3::from(vertex.normal);
+            let helper = if normal.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+            let tangent = helper.cross(normal).normalize();
+            vertex.tangent = tangent.into();
+            vertex.bitangent = normal.cross(tangent).into();
+        }
+        vertices
+    }
+}
+
+impl State {
+    pub(crate) fn push_mesh(&mut self, update: MeshUpdate) -> anyhow::Result<Value> {
+        let location = self.find_mesh(&update.name);
+        //the stored state is only replaced at the end, a rejected update leaves it for the next one
+        let previous = match location {
+            Some(_) => self.live_meshes.get(&update.name),
+            None => None,
+        };
+        let live_mesh = LiveMesh::merge(&update, previous)?;
+        let vertices = live_mesh.vertices();
+
+        let (model_index, mesh_index, created) = match location {
+            Some((model_index, mesh_index)) => {
+                let material = match &update.material {
+                    Some(name) => Some(self.material_in_model(model_index, name)?),
+                    None => None,
+                };
+                self.replace_mesh_buffers(model_index, mesh_index, &vertices, &live_mesh.indices);
+                let mesh = &mut self.models[model_index].meshes[mesh_index];
+                if let Some(material) = material {
+                    mesh.material = material;
+                }
+                (model_index, mesh_index, false)
+            }
+            None => {
+                let material = match &update.material {
+                    Some(name) => self.shared_material(name)?,
+                    None => self.fallback_material()?,
+                };
+                let mut model = resources::create_empty_model(&self.device, 1);
+                model.materials.push(material);
+                model.meshes.push(resources::upload_mesh(&self.device, &resources::MeshData {
+                    name: update.name.clone(),
+                    vertices,
+                    indices: live_mesh.indices.clone(),
+                    material: 0,
+                }));
+                self.models.push(model);
+                //places it right away so its entities are there for the caller
+                self.sync_entities();
+                (self.models.len() - 1, 0, true)
+            }
+        };
+
+        let result = json!({
+            "model": model_index,
+            "mesh": mesh_index,
+            "created": created,
+            "vertices": live_mesh.positions.len(),
+            "triangles": live_mesh.indices.len() / 3,
+        });
+        self.live_meshes.insert(update.name, live_mesh);
+        Ok(result)
+    }
+
+    pub(crate) fn remove_mesh(&mut self, name: &str) -> anyhow::Result<()> {
+        let (model_index, mesh_index) = self.find_mesh(name).ok_or_else(|| anyhow!("no mesh named {:?}", name))?;
+        //an emptied model stays so the indices of the others don't move
+        self.models[model_index].meshes.remove(mesh_index);
+        self.mesh_removed(model_index, mesh_index);
+        self.live_meshes.remove(name);
+        Ok(())
+    }
+
+    fn find_mesh(&self, name: &str) -> Option<(usize, usize)> {
+        self.models.iter().enumerate().find_map(|(model_index, model)| {
+            model.meshes.iter().position(|mesh| mesh.name == name).map(|mesh_index| (model_index, mesh_index))
+        })
+    }
+
+    // same sized data is written into the existing buffers, anything else gets new ones
+    fn replace_mesh_buffers(&mut self, model_index: usize, mesh_index: usize, vertices: &[model::ModelVertex], indices: &[u32]) {
+        let device = &self.device;
+        let queue = &self.queue;
+        let mesh = &mut self.models[model_index].meshes[mesh_index];
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(vertices);
+        if mesh.vertex_buffer.size() == vertex_bytes.len() as u64 && !vertex_bytes.is_empty() {
+            queue.write_buffer(&mesh.vertex_buffer, 0, vertex_bytes);
+        } else {
+            mesh.vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{:?} Vertex Buffer", mesh.name)),
+                contents: vertex_bytes,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            });
+        }
+        let index_bytes: &[u8] = bytemuck::cast_slice(indices);
+        if mesh.index_buffer.size() == index_bytes.len() as u64 && !index_bytes.is_empty() {
+            queue.write_buffer(&mesh.index_buffer, 0, index_bytes);
+        } else {
+            mesh.index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{:?} Index Buffer", mesh.name)),
+                contents: index_bytes,
+                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            });
+        }
+        mesh.num_elements = indices.len() as u32;
+        mesh.positions = vertices.iter().map(|vertex| vertex.position).collect();
+        mesh.indices = indices.to_vec();
+        //checked again the next time the validation view draws it
+        mesh.overlay = None;
+    }
+
+    // index of the material in this model, one from another model is shared into it
+    fn material_in_model(&mut self, model_index: usize, name: &str) -> anyhow::Result<usize> {
+        if let Some(index) = self.models[model_index].materials.iter().position(|material| material.name == name) {
+            return Ok(index);
+        }
+        let material = self.shared_material(name)?;
+        let materials = &mut self.models[model_index].materials;
+        materials.push(material);
+        Ok(materials.len() - 1)
+    }
+
+    // a new bind group over the textures of a loaded material, nothing is read from disk
+    fn shared_material(&self, name: &str) -> anyhow::Result<model::Material> {
+        let material = self.models
+            .iter()
+            .flat_map(|model| &model.materials)
+            .find(|material| material.name == name)
+            .ok_or_else(|| anyhow!("no loaded model has a material named {:?}", name))?;
+        Ok(model::Material::new(&self.device, name, material.diffuse_texture.clone(), material.normal_texture.clone(), &self.texture_bind_group_layout))
+    }
+
+    fn fallback_material(&mut self) -> anyhow::Result<model::Material> {
+        let opengl = self.file_type == "opengl";
+        let diffuse = self.cached_texture(texture::TextureKey::fallback(false, opengl))?;
+        let normal = self.cached_texture(texture::TextureKey::fallback(true, opengl))?;
+        Ok(model::Material::new(&self.device, "live link default", diffuse, normal, &self.texture_bind_group_layout))
+    }
+
+    fn cached_texture(&mut self, key: texture::TextureKey) -> anyhow::Result<Arc<texture::Texture>> {
+        if let Some(texture) = self.texture_cache.get(&key) {
+            return Ok(texture);
+        }
+        let decoded = resources::decode_material_texture(&key, self.device.features());
+        let texture = texture::Texture::from_decoded(&self.device, &self.queue, &decoded).context("could not create the fallback texture")?;
+        Ok(self.texture_cache.insert(key, texture))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle(name: &str) -> MeshUpdate {
+        MeshUpdate {
+            name: name.to_string(),
+            positions: Some(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]),
+            indices: Some(vec![0, 1, 2]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn smooth_normals_follow_the_winding() {
+        let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        assert_eq!(smooth_normals(&positions, &[0, 1, 2]), vec![[0.0, 0.0, 1.0]; 3]);
+        assert_eq!(smooth_normals(&positions, &[0, 2, 1]), vec![[0.0, 0.0, -1.0]; 3]);
+    }
+
+    #[test]
+    fn smooth_normals_average_shared_vertices() {
+        //two faces of a cube corner meeting along the edge from 0 to 1
+        let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let normals = smooth_normals(&positions, &[0, 1, 2, 0, 3, 1]);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        for (normal, expected) in normals[..2].iter().zip([[0.0, half, half]; 2]) {
+            for axis in 0..3 {
+                assert!((normal[axis] - expected[axis]).abs() < 1e-6, "{:?}", normals);
+            }
+        }
+        assert_eq!(normals[2], [0.0, 0.0, 1.0]);
+        assert_eq!(normals[3], [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn smooth_normals_of_unused_vertices_point_up() {
+        let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [5.0, 5.0, 5.0]];
+        assert_eq!(smooth_normals(&positions, &[0, 1, 2])[3], [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn first_push_needs_positions_and_indices() {
+        let update = MeshUpdate { name: "a".to_string(), positions: Some(vec![0.0; 9]), ..Default::default() };
+        assert!(LiveMesh::merge(&update, None).is_err());
+        let mesh = LiveMesh::merge(&triangle("a"), None).unwrap();
+        assert_eq!(mesh.normals, vec![[0.0, 0.0, 1.0]; 3]);
+        assert_eq!(mesh.uvs, vec![[0.0, 0.0]; 3]);
+    }
+
+    #[test]
+    fn partial_updates_keep_the_rest() {
+        let mut first = triangle("a");
+        first.uvs = Some(vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        first.normals = Some(vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        let previous = LiveMesh::merge(&first, None).unwrap();
+
+        //only uvs, the shape and its normals stay
+        let update = MeshUpdate { name: "a".to_string(), uvs: Some(vec![0.5; 6]), ..Default::default() };
+        let mesh = LiveMesh::merge(&update, Some(&previous)).unwrap();
+        assert_eq!(mesh.positions, previous.positions);
+        assert_eq!(mesh.indices, previous.indices);
+        assert_eq!(mesh.normals, previous.normals);
+        assert_eq!(mesh.uvs, vec![[0.5, 0.5]; 3]);
+
+        //moved positions don't fit the old normals anymore, the uvs still do
+        let update = MeshUpdate { name: "a".to_string(), positions: Some(vec![0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0]), ..Default::default() };
+        let mesh = LiveMesh::merge(&update, Some(&previous)).unwrap();
+        assert_eq!(mesh.normals, vec![[-1.0, 0.0, 0.0]; 3]);
+        assert_eq!(mesh.uvs, previous.uvs);
+
+        //a different vertex count drops the old uvs
+        let update = MeshUpdate { name: "a".to_string(), positions: Some(vec![0.0; 12]), indices: Some(vec![0, 1, 2, 1, 2, 3]), ..Default::default() };
+        assert_eq!(LiveMesh::merge(&update, Some(&previous)).unwrap().uvs, vec![[0.0, 0.0]; 4]);
+    }
+
+    #[test]
+    fn bad_updates_are_rejected() {
+        let previous = LiveMesh::merge(&triangle("a"), None).unwrap();
+        let cases = [
+            MeshUpdate { name: "a".to_string(), indices: Some(vec![0, 1, 3]), ..Default::default() },
+            MeshUpdate { name: "a".to_string(), indices: Some(vec![0, 1]), ..Default::default() },
+            MeshUpdate { name: "a".to_string(), positions: Some(vec![0.0; 8]), ..Default::default() },
+            MeshUpdate { name: "a".to_string(), normals: Some(vec![0.0; 6]), ..Default::default() },
+            MeshUpdate { name: "a".to_string(), uvs: Some(vec![0.0; 5]), ..Default::default() },
+        ];
+        for update in &cases {
+            assert!(LiveMesh::merge(update, Some(&previous)).is_err());
+        }
+    }
+}
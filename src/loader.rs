@@ -0,0 +1,435 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+use rayon::prelude::*;
+use wgpu::util::DeviceExt;
+use anyhow::Context;
+use crate::{asset_cache, model, resources, shaders, texture};
+
+// messages from the loading thread, Parsed first, then every Texture and every Mesh mixed as
+// they're ready, then Finished (or Failed at any point)
+pub enum LoadEvent {
+    Parsed {
+        material_names: Vec<String>,
+        texture_keys: Vec<texture::TextureKey>,
+        texture_count: usize,
+        mesh_count: usize,
+        //obj and mtl files, for hot reload
+        model_paths: Vec<PathBuf>,
+    },
+    Texture(texture::TextureKey, texture::DecodedImage),
+    //index of the mesh in the obj, they can come in any order
+    Mesh(usize, resources::MeshData),
+    Finished,
+    Failed(String),
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LoadProgress {
+    pub textures_done: usize,
+    pub textures_total: usize,
+    pub meshes_done: usize,
+    pub meshes_total: usize,
+}
+
+impl LoadProgress {
+    pub fn fraction(&self) -> f32 {
+        let total = self.textures_total + self.meshes_total;
+        if total == 0 {
+            return 0.0;
+        }
+        (self.textures_done + self.meshes_done) as f32 / total as f32
+    }
+}
+
+// loads an obj on a worker thread, the render thread polls it every frame
+// and uploads whatever is ready. a new model shows up mesh by mesh, a reloaded
+// model is built on the side and swapped in once it's complete
+pub struct ModelLoader {
+    pub file_name: String,
+    pub file_type: String,
+    pub progress: LoadProgress,
+    pub model_paths: Vec<PathBuf>,
+    receiver: Receiver<LoadEvent>,
+    replace_index: Option<usize>,
+    model_index: Option<usize>,
+    model: Option<model::Model>,
+    //meshes that came in before one with a lower index, a mesh is only added once every one
+    //before it is there so its index in the model is the one it has in the obj
+    pending_meshes: BTreeMap<usize, model::Mesh>,
+    material_names: Vec<String>,
+    texture_keys: Vec<texture::TextureKey>,
+    start_time: Instant,
+}
+
+pub enum LoadStatus {
+    Loading,
+    Finished,
+    Failed(String),
+}
+
+impl ModelLoader {
+    pub fn spawn(file_name: String, file_type: String, replace_index: Option<usize>, texture_cache: &texture::TextureCache, features: wgpu::Features) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cached_keys = texture_cache.keys();
+        let thread_file_name = file_name.clone();
+        let thread_file_type = file_type.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = load_on_worker(&thread_file_name, &thread_file_type, &cached_keys, features, &sender) {
+                let _ = sender.send(LoadEvent::Failed(format!("{:#}", err)));
+            }
+        });
+
+        Self {
+            file_name,
+            file_type,
+            progress: LoadProgress::default(),
+            model_paths: Vec::new(),
+            receiver,
+            replace_index,
+            model_index: None,
+            model: None,
+            pending_meshes: BTreeMap::new(),
+            material_names: Vec::new(),
+            texture_keys: Vec::new(),
+            start_time: Instant::now(),
+        }
+    }
+
+    // upload what the worker has produced so far, at least one event and then more until time_budget is used up
+    // so the window keeps drawing while big textures come in
+    pub fn poll(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        models: &mut Vec<model::Model>,
+        texture_cache: &mut texture::TextureCache,
+        time_budget: Duration,
+    ) -> LoadStatus {
+        let status = self.poll_events(device, queue, layout, models, texture_cache, time_budget);
+        if let LoadStatus::Finished = status {
+            if let (Some(index), Some(model)) = (self.replace_index, self.model.take()) {
+                if index < models.len() {
+                    models[index] = model;
+                } else {
+                    models.push(model);
+                }
+            }
+        }
+        status
+    }
+
+    pub fn texture_paths(&self) -> Vec<PathBuf> {
+        self.texture_keys.iter().filter(|key| !key.is_fallback()).map(|key| key.path.clone()).collect()
+    }
+
+    pub fn model_index(&self) -> Option<usize> {
+        self.replace_index.or(self.model_index)
+    }
+
+    fn target_model<'a>(&'a mut self, models: &'a mut [model::Model]) -> Option<&'a mut model::Model> {
+        match self.model_index {
+            Some(index) => models.get_mut(index),
+            None => self.model.as_mut(),
+        }
+    }
+
+    fn poll_events(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        models: &mut Vec<model::Model>,
+        texture_cache: &mut texture::TextureCache,
+        time_budget: Duration,
+    ) -> LoadStatus {
+        let poll_start = Instant::now();
+        loop {
+            let event = match self.receiver.try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Empty) => return LoadStatus::Loading,
+                Err(TryRecvError::Disconnected) => return LoadStatus::Failed("loading thread stopped".to_string()),
+            };
+
+            match event {
+                LoadEvent::Parsed { material_names, texture_keys, texture_count, mesh_count, model_paths } => {
+                    self.progress.textures_total = texture_count;
+                    self.progress.meshes_total = mesh_count;
+                    self.material_names = material_names;
+                    self.texture_keys = texture_keys;
+                    self.model_paths = model_paths;
+                    let model = resources::create_empty_model(device, 1);
+                    if self.replace_index.is_some() {
+                        self.model = Some(model);
+                    } else {
+                        models.push(model);
+                        self.model_index = Some(models.len() - 1);
+                    }
+                    if texture_count == 0 {
+                        self.create_materials(device, layout, models, texture_cache);
+                    } else if let Err(err) = self.create_placeholder_materials(device, queue, layout, models, texture_cache) {
+                        return LoadStatus::Failed(format!("{:#}", err));
+                    }
+                }
+                LoadEvent::Texture(key, decoded) => {
+                    match texture::Texture::from_decoded(device, queue, &decoded) {
+                        Ok(texture) => {
+                            texture_cache.insert(key, texture);
+                        }
+                        Err(err) => return LoadStatus::Failed(format!("{:#}", err)),
+                    }
+                    self.progress.textures_done += 1;
+                    if self.progress.textures_done == self.progress.textures_total {
+                        self.create_materials(device, layout, models, texture_cache);
+                    }
+                }
+                LoadEvent::Mesh(index, mesh) => {
+                    //materials (placeholders until the textures are in) are created before the first mesh arrives
+                    let mut pending = std::mem::take(&mut self.pending_meshes);
+                    pending.insert(index, resources::upload_mesh(device, &mesh));
+                    if let Some(model) = self.target_model(models) {
+                        while let Some(mesh) = pending.remove(&model.meshes.len()) {
+                            model.meshes.push(mesh);
+                        }
+                    }
+                    self.pending_meshes = pending;
+                    self.progress.meshes_done += 1;
+                }
+                LoadEvent::Finished => {
+                    println!("total loading time {:?}", self.start_time.elapsed());
+                    return LoadStatus::Finished;
+                }
+                LoadEvent::Failed(err) => return LoadStatus::Failed(err),
+            }
+            //checked after the event so a zero budget still gets one through
+            if poll_start.elapsed() >= time_budget {
+                return LoadStatus::Loading;
+            }
+        }
+    }
+
+    fn create_materials(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        models: &mut [model::Model],
+        texture_cache: &texture::TextureCache,
+    ) {
+        let materials = resources::create_materials(device, layout, &self.material_names, &self.texture_keys, texture_cache);
+        if let Some(model) = self.target_model(models) {
+            model.materials = materials;
+        }
+    }
+
+    // the fallback textures stand in for the ones still decoding so meshes can be drawn as they come in,
+    // create_materials swaps the real ones in once the last texture is there
+    fn create_placeholder_materials(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        models: &mut [model::Model],
+        texture_cache: &mut texture::TextureCache,
+    ) -> anyhow::Result<()> {
+        let opengl = self.file_type == "opengl";
+        let mut texture = |key: &texture::TextureKey| -> anyhow::Result<std::sync::Arc<texture::Texture>> {
+            if let Some(texture) = texture_cache.get(key) {
+                return Ok(texture);
+            }
+            let fallback = texture::TextureKey::fallback(key.is_normal_map, opengl);
+            if let Some(texture) = texture_cache.get(&fallback) {
+                return Ok(texture);
+            }
+            let decoded = resources::decode_material_texture(&fallback, device.features());
+            Ok(texture_cache.insert(fallback, texture::Texture::from_decoded(device, queue, &decoded)?))
+        };
+        let mut materials = Vec::new();
+        for (name, keys) in self.material_names.iter().zip(self.texture_keys.chunks(2)) {
+            let (diffuse, normal) = (texture(&keys[0])?, texture(&keys[1])?);
+            materials.push(model::Material::new(device, name, diffuse, normal, layout));
+        }
+        if let Some(model) = self.target_model(models) {
+            model.materials = materials;
+        }
+        Ok(())
+    }
+}
+
+fn load_on_worker(
+    file_name: &str,
+    file_type: &str,
+    cached_keys: &HashSet<texture::TextureKey>,
+    features: wgpu::Features,
+    sender: &Sender<LoadEvent>,
+) -> anyhow::Result<()> {
+    let cache = asset_cache::AssetCache::from_env();
+    let cached = cache.as_ref().and_then(|cache| cache.load_model(file_name, file_type));
+    let (obj, cached_meshes) = match cached {
+        Some((obj, meshes)) => (obj, Some(meshes)),
+        None => (pollster::block_on(resources::parse_obj(file_name, file_type)).with_context(|| format!("could not read {}", file_name))?, None),
+    };
+
+    let mut missing_keys: Vec<&texture::TextureKey> = Vec::new();
+    for key in &obj.texture_keys {
+        if !cached_keys.contains(key) && !missing_keys.contains(&key) {
+            missing_keys.push(key);
+        }
+    }
+
+    let mut model_paths = vec![PathBuf::from(&obj.file_name)];
+    model_paths.extend(obj.mtl_paths.iter().cloned());
+
+    let _ = sender.send(LoadEvent::Parsed {
+        material_names: obj.material_names.clone(),
+        texture_keys: obj.texture_keys.clone(),
+        texture_count: missing_keys.len(),
+        mesh_count: cached_meshes.as_ref().map_or(obj.models.len(), |meshes| meshes.len()),
+        model_paths,
+    });
+
+    //meshes are built while the textures decode, so they show up before the last texture is in
+    rayon::join(
+        || {
+            let texture_decode_start = Instant::now();
+            missing_keys.par_iter().for_each_with(sender.clone(), |sender, key| {
+                let decoded = resources::decode_material_texture(key, features);
+                let _ = sender.send(LoadEvent::Texture((*key).clone(), decoded));
+            });
+            println!("texture decoding time {:?} ({} textures, {} reused from cache)", texture_decode_start.elapsed(), missing_keys.len(), obj.texture_keys.len() - missing_keys.len());
+        },
+        || {
+            let mesh_start = Instant::now();
+            match (cached_meshes, &cache) {
+                (Some(meshes), _) => {
+                    for (index, mesh) in meshes.into_iter().enumerate() {
+                        let _ = sender.send(LoadEvent::Mesh(index, mesh));
+                    }
+                }
+                (None, Some(cache)) => {
+                    //keep a copy of every mesh to write the cache entry once all are built
+                    let meshes = obj.models
+                        .par_iter()
+                        .enumerate()
+                        .map_with(sender.clone(), |sender, (index, m)| {
+                            let mesh = resources::build_mesh_data(&obj.file_name, m);
+                            let _ = sender.send(LoadEvent::Mesh(index, mesh.clone()));
+                            mesh
+                        })
+                        .collect::<Vec<_>>();
+                    cache.store_model(&obj, file_type, &meshes);
+                }
+                (None, None) => {
+                    obj.models.par_iter().enumerate().for_each_with(sender.clone(), |sender, (index, m)| {
+                        let _ = sender.send(LoadEvent::Mesh(index, resources::build_mesh_data(&obj.file_name, m)));
+                    });
+                }
+            }
+            println!("mesh processing time {:?}", mesh_start.elapsed());
+        },
+    );
+
+    let _ = sender.send(LoadEvent::Finished);
+    Ok(())
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LoadingVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+// two quads, the bar background and the filled part
+const LOADING_VERTEX_COUNT: u32 = 12;
+
+// progress bar drawn over the scene while a model is loading
+pub struct LoadingScreen {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+}
+
+impl LoadingScreen {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Loading Screen Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::load_shader("loading.wgsl").into()),
+        });
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Loading Bar Buffer"),
+            contents: bytemuck::cast_slice(&progress_bar_vertices(0.0)),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Loading Screen Pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<LoadingVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self { pipeline, vertex_buffer }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, progress: &LoadProgress) {
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&progress_bar_vertices(progress.fraction())));
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut loading_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Loading Screen Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        loading_pass.set_pipeline(&self.pipeline);
+        loading_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        loading_pass.draw(0..LOADING_VERTEX_COUNT, 0..1);
+    }
+}
+
+fn quad(x0: f32, y0: f32, x1: f32, y1: f32, color: [f32; 4]) -> [LoadingVertex; 6] {
+    let v = |x: f32, y: f32| LoadingVertex { position: [x, y], color };
+    [v(x0, y0), v(x1, y0), v(x1, y1), v(x0, y0), v(x1, y1), v(x0, y1)]
+}
+
+fn progress_bar_vertices(fraction: f32) -> [LoadingVertex; LOADING_VERTEX_COUNT as usize] {
+    let (left, right, bottom, top) = (-0.5, 0.5, -0.03, 0.03);
+    let fill_right = left + (right - left) * fraction.clamp(0.0, 1.0);
+    let mut vertices = [LoadingVertex { position: [0.0; 2], color: [0.0; 4] }; LOADING_VERTEX_COUNT as usize];
+    vertices[..6].copy_from_slice(&quad(left, bottom, right, top, [0.05, 0.05, 0.05, 0.8]));
+    vertices[6..].copy_from_slice(&quad(left, bottom, fill_right, top, [0.9, 0.9, 0.9, 1.0]));
+    vertices
+}
@@ -1,8 +1,208 @@
+// the welcome text spaces its lines with println!("")
+#![allow(clippy::println_empty_string)]
 use std::env;
-use kanirenderer_viewer::run;
+use kanirenderer_viewer::{load_camera_path, model_info, render_sequence, render_thumbnails, run, validate_model, CameraMotion, HeadlessRenderer, SequenceOptions, ThumbnailOptions};
+
+fn info(args: &[String]) {
+    let args = CommandArgs::parse(args, "usage: kanirenderer info <model.obj> [default|opengl] [--json]", &[], &["--json"]);
+    let (file_path, file_type) = args.path_and_file_type("model");
+    let json = args.switch("--json");
+    match model_info(file_path, file_type) {
+        Ok(info) if json => println!("{}", info.to_json()),
+        Ok(info) => print!("{}", info),
+        Err(err) => {
+            eprintln!("could not read {:?} : {:#}", file_path, err);
+            std::process::exit(2);
+        }
+    }
+}
+
+// exits with 1 when a mesh has errors so asset scripts can fail on it, warnings don't count
+fn validate(args: &[String]) {
+    let args = CommandArgs::parse(args, "usage: kanirenderer validate <model.obj> [default|opengl] [--json]", &[], &["--json"]);
+    let (file_path, file_type) = args.path_and_file_type("model");
+    let json = args.switch("--json");
+    let report = match validate_model(file_path, file_type) {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("could not read {:?} : {:#}", file_path, err);
+            std::process::exit(2);
+        }
+    };
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report);
+    }
+    if report.error_count > 0 {
+        std::process::exit(1);
+    }
+}
+
+// subcommand arguments split into positionals, --flag value pairs and --switches
+struct CommandArgs<'a> {
+    usage: &'static str,
+    positional: Vec<&'a str>,
+    flags: Vec<(&'a str, &'a str)>,
+    switches: Vec<&'a str>,
+}
+
+impl<'a> CommandArgs<'a> {
+    fn parse(args: &'a [String], usage: &'static str, value_flags: &[&str], switches: &[&str]) -> Self {
+        let mut parsed = CommandArgs { usage, positional: Vec::new(), flags: Vec::new(), switches: Vec::new() };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if value_flags.contains(&arg.as_str()) {
+                let Some(value) = iter.next() else {
+                    parsed.error(&format!("{} needs a value", arg));
+                };
+                parsed.flags.push((arg.as_str(), value.as_str()));
+            } else if switches.contains(&arg.as_str()) {
+                parsed.switches.push(arg.as_str());
+            } else if arg.starts_with("--") {
+                parsed.error(&format!("unknown option {}", arg));
+            } else {
+                parsed.positional.push(arg.as_str());
+            }
+        }
+        parsed
+    }
+
+    fn error(&self, message: &str) -> ! {
+        eprintln!("{}", message);
+        eprintln!("{}", self.usage);
+        std::process::exit(2);
+    }
+
+    fn flag(&self, name: &str) -> Option<&'a str> {
+        self.flags.iter().find(|(flag, _)| *flag == name).map(|(_, value)| *value)
+    }
+
+    fn parse_flag<T: std::str::FromStr>(&self, name: &str, default: T) -> T {
+        match self.flag(name) {
+            Some(value) => value.parse().unwrap_or_else(|_| self.error(&format!("invalid value {:?} for {}", value, name))),
+            None => default,
+        }
+    }
+
+    fn switch(&self, name: &str) -> bool {
+        self.switches.contains(&name)
+    }
+
+    // <model or dir> [default|opengl]
+    fn path_and_file_type(&self, what: &str) -> (&'a str, &'a str) {
+        let Some(path) = self.positional.first() else {
+            self.error(&format!("no {} given", what));
+        };
+        let file_type = self.positional.get(1).copied().unwrap_or("default");
+        if file_type != "default" && file_type != "opengl" {
+            self.error(&format!("unknown file type {:?}, use \"default\" or \"opengl\"", file_type));
+        }
+        (path, file_type)
+    }
+}
+
+// renders without opening a window, frames go to --out (default "frames")
+fn sequence(args: &[String]) {
+    let args = CommandArgs::parse(
+        args,
+        "usage: kanirenderer sequence <model.obj> [default|opengl] (--turntable <frames> | --path <keyframes.json>) [--out <dir>] [--size <width>x<height>] [--fps <n>] [--elevation <degrees>] [--mode <name>] [--gif] [--apng]",
+        &["--turntable", "--path", "--out", "--size", "--fps", "--elevation", "--mode"],
+        &["--gif", "--apng"],
+    );
+    let (file_path, file_type) = args.path_and_file_type("model");
+
+    let (width, height) = match args.parse_flag("--size", "960x540".to_string()).split_once('x') {
+        Some((width, height)) => match (width.parse::<u32>(), height.parse::<u32>()) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => (width, height),
+            _ => args.error("--size must look like 960x540"),
+        },
+        None => args.error("--size must look like 960x540"),
+    };
+    let motion = match (args.flag("--turntable"), args.flag("--path")) {
+        (Some(_), None) => CameraMotion::Turntable {
+            frames: args.parse_flag("--turntable", 0),
+            elevation: args.parse_flag("--elevation", 20.0),
+        },
+        (None, Some(path)) => match load_camera_path(std::path::Path::new(path)) {
+            Ok(keyframes) => CameraMotion::Path(keyframes),
+            Err(err) => {
+                eprintln!("{:#}", err);
+                std::process::exit(2);
+            }
+        },
+        _ => args.error("use one of --turntable or --path"),
+    };
+    let options = SequenceOptions {
+        motion,
+        out_dir: args.parse_flag("--out", std::path::PathBuf::from("frames")),
+        fps: args.parse_flag("--fps", 30),
+        gif: args.switch("--gif"),
+        apng: args.switch("--apng"),
+    };
+
+    let result = HeadlessRenderer::new(file_type, width, height).and_then(|mut renderer| {
+        if let Some(mode) = args.flag("--mode") {
+            renderer.set_mode(mode)?;
+        }
+        renderer.load_model(std::path::Path::new(file_path))?;
+        render_sequence(&mut renderer, &options)
+    });
+    match result {
+        Ok(frames) => println!("wrote {} frames to {:?}", frames.len(), options.out_dir),
+        Err(err) => {
+            eprintln!("sequence failed : {:#}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// exits with 1 when any model failed, the rest still get their thumbnails
+fn thumbnails(args: &[String]) {
+    let args = CommandArgs::parse(
+        args,
+        "usage: kanirenderer thumbnails <dir> [default|opengl] [--out <dir>] [--size <pixels>] [--mode <name>]",
+        &["--out", "--size", "--mode"],
+        &[],
+    );
+    let (dir, file_type) = args.path_and_file_type("directory");
+    let size = args.parse_flag("--size", 256u32);
+    if size == 0 {
+        args.error("--size must be at least 1");
+    }
+    let options = ThumbnailOptions {
+        out_dir: args.parse_flag("--out", std::path::PathBuf::from("thumbnails")),
+        size,
+        file_type: file_type.to_string(),
+        mode: args.flag("--mode").map(|mode| mode.to_string()),
+    };
+
+    let report = match render_thumbnails(std::path::Path::new(dir), &options) {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("thumbnails failed : {:#}", err);
+            std::process::exit(2);
+        }
+    };
+    println!("wrote {} thumbnails to {:?}, {} failed", report.written.len(), options.out_dir, report.failures.len());
+    for failure in &report.failures {
+        eprintln!("  {:?} : {}", failure.path, failure.error);
+    }
+    if !report.failures.is_empty() {
+        std::process::exit(1);
+    }
+}
 
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
+    let args = std::env::args().collect::<Vec<_>>();
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("info") => return info(&args[2..]),
+        Some("validate") => return validate(&args[2..]),
+        Some("sequence") => return sequence(&args[2..]),
+        Some("thumbnails") => return thumbnails(&args[2..]),
+        _ => {}
+    }
     println!("Hello!");
     println!("");
     println!("Loading...");
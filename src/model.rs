@@ -1,9 +1,10 @@
-use std::{ops::Range, usize};
+use std::{ops::Range, sync::Arc, usize};
 
 use cgmath::{num_traits::ToPrimitive, perspective, prelude::*, Point3, Vector3};
 use instant::Duration;
 use rand::Rng;
 use crate::texture::Texture;
+use crate::validation;
 
 pub trait Vertex {
     fn desc() -> wgpu::VertexBufferLayout<'static>;
@@ -63,9 +64,30 @@ pub struct Model {
     pub instances: Vec<Instance>,
     pub instance_buffer: wgpu::Buffer,
     pub instance_num: i32,
+    //where each mesh's instances sit in the instance buffer once the entities placed the model,
+    //empty until then and every mesh draws all of `instances`
+    pub mesh_instances: Vec<Range<u32>>,
+    //cpu copy of the instance buffer for picking and bounds
+    pub instance_data: Vec<InstanceRaw>,
 }
 
 impl Model {
+    pub fn instance_range(&self, mesh: usize) -> Range<u32> {
+        if self.mesh_instances.is_empty() {
+            return 0..self.instances.len() as u32;
+        }
+        self.mesh_instances.get(mesh).cloned().unwrap_or(0..0)
+    }
+
+    // world matrix of every instance of a mesh
+    pub fn mesh_transforms(&self, mesh: usize) -> Vec<cgmath::Matrix4<f32>> {
+        if self.mesh_instances.is_empty() {
+            return self.instances.iter().map(|instance| instance.to_raw().model.into()).collect();
+        }
+        let range = self.instance_range(mesh);
+        self.instance_data[range.start as usize..range.end as usize].iter().map(|raw| raw.model.into()).collect()
+    }
+
     pub fn test_move_model(posx: f32 , i :usize, dt: Duration) ->f32 {
         let mut newpos = posx;
         if i % 2 == 0{
@@ -94,16 +116,16 @@ pub fn test_move_model_vec3(vec_pos: Vector3<f32> , dt: Duration) ->Vector3<f32>
 
 pub struct Material {
     pub name: String,
-    pub diffuse_texture: Texture,
-    pub normal_texture: Texture,
+    pub diffuse_texture: Arc<Texture>,
+    pub normal_texture: Arc<Texture>,
     pub bind_group: wgpu::BindGroup,
 }
 
 impl Material {
     pub fn new( device: &wgpu::Device, 
         name: &str, 
-        diffuse_texture: Texture, 
-        normal_texture: Texture,
+        diffuse_texture: Arc<Texture>, 
+        normal_texture: Arc<Texture>,
         layout: &wgpu::BindGroupLayout) -> Self {
             let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
                 layout,
@@ -143,6 +165,11 @@ pub struct Mesh  {
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
     pub material: usize,
+    //cpu copy of the geometry for picking
+    pub positions: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+    //built the first time the validation view draws the mesh
+    pub overlay: Option<validation::MeshOverlay>,
 }
 
 pub trait  DrawModel<'a> {
@@ -176,7 +203,6 @@ pub trait  DrawModel<'a> {
     fn draw_model_instanced(
         &mut self,
         model: &'a Model,
-        instances: Range<u32>,
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
         shadow_bind_group: &'a wgpu::BindGroup,
@@ -224,21 +250,26 @@ where
             shadow_bind_group: &'b wgpu::BindGroup,
 
         ) {
-        self.draw_model_instanced(model, 0..1, camera_bind_group, light_bind_group, shadow_bind_group);
+        for (index, mesh) in model.meshes.iter().enumerate() {
+            let instances = model.instance_range(index);
+            if !model.materials.is_empty() && !instances.is_empty() {
+                let material = &model.materials[mesh.material];
+                self.draw_mesh_instanced(mesh, material, instances.start..instances.start + 1, camera_bind_group, light_bind_group, shadow_bind_group);
+            }
+        }
     }
 
     fn draw_model_instanced(
             &mut self,
             model: &'b Model,
-            instances: Range<u32>,
             camera_bind_group: &'b wgpu::BindGroup,
             light_bind_group: &'b wgpu::BindGroup,
             shadow_bind_group: &'b wgpu::BindGroup,
         ) {
-        for mesh in &model.meshes {
+        for (index, mesh) in model.meshes.iter().enumerate() {
             if !&model.materials.is_empty() {
                 let material = &model.materials[mesh.material];
-                self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group, light_bind_group, shadow_bind_group);
+                self.draw_mesh_instanced(mesh, material, model.instance_range(index), camera_bind_group, light_bind_group, shadow_bind_group);
             }
             
         }
@@ -249,6 +280,7 @@ where
 pub struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
+    pub scale: cgmath::Vector3<f32>,
 }
 
 #[repr(C)]
@@ -264,15 +296,17 @@ pub fn update_instance_position_rotation(instances: &Instance, dt: Duration) ->
     use rayon::prelude::*;
     let new_pos = test_move_model_vec3(instances.position, dt);
             
-    Instance { position: new_pos, rotation: instances.rotation }
+    Instance { position: new_pos, rotation: instances.rotation, scale: instances.scale }
 }
 
 impl Instance {
     pub fn to_raw(&self) -> InstanceRaw {
-        let model = cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation);
+        let model = cgmath::Matrix4::from_translation(self.position)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z);
         InstanceRaw { 
             model: model.into(),
-            normal: cgmath::Matrix3::from(self.rotation).into(),
+            normal: crate::entity::normal_matrix(self.rotation, self.scale).into(),
             _padding: 0,
         }
     }
@@ -0,0 +1,104 @@
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use crate::{camera, model};
+
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PickHit {
+    pub model: usize,
+    pub mesh: usize,
+    // which instance of the mesh, in the order of the mesh entities drawing it
+    pub instance: usize,
+    pub distance: f32,
+}
+
+// world space ray through a pixel of the window
+pub fn ray_from_cursor(
+    camera: &camera::Camera,
+    projection: &camera::Projection,
+    cursor_x: f32,
+    cursor_y: f32,
+    width: u32,
+    height: u32,
+) -> Option<Ray> {
+    let ndc_x = 2.0 * cursor_x / width as f32 - 1.0;
+    let ndc_y = 1.0 - 2.0 * cursor_y / height as f32;
+    let inverse_view_proj = (projection.calc_matrix() * camera.calc_matrix()).invert()?;
+
+    let unproject = |z: f32| {
+        let p = inverse_view_proj * Vector4::new(ndc_x, ndc_y, z, 1.0);
+        Point3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+    };
+    let near = unproject(-1.0);
+    let far = unproject(1.0);
+    Some(Ray { origin: near, direction: (far - near).normalize() })
+}
+
+// closest mesh hit by the ray, tested against every instance on the cpu
+pub fn pick(models: &[model::Model], ray: &Ray) -> Option<PickHit> {
+    let mut closest: Option<PickHit> = None;
+    for (model_index, model) in models.iter().enumerate() {
+        for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+            for (instance_index, model_matrix) in model.mesh_transforms(mesh_index).into_iter().enumerate() {
+                let Some(inverse) = model_matrix.invert() else {
+                    continue;
+                };
+                //into model space, distances are measured back in world space
+                let local_origin = Point3::from_homogeneous(inverse * ray.origin.to_homogeneous());
+                let local_direction = (inverse * ray.direction.extend(0.0)).truncate();
+
+                let Some(t) = intersect_mesh(mesh, local_origin, local_direction) else {
+                    continue;
+                };
+                let hit_point = Point3::from_homogeneous(model_matrix * (local_origin + local_direction * t).to_homogeneous());
+                let distance = (hit_point - ray.origin).magnitude();
+                if closest.map_or(true, |hit| distance < hit.distance) {
+                    closest = Some(PickHit { model: model_index, mesh: mesh_index, instance: instance_index, distance });
+                }
+            }
+        }
+    }
+    closest
+}
+
+fn intersect_mesh(mesh: &model::Mesh, origin: Point3<f32>, direction: Vector3<f32>) -> Option<f32> {
+    let mut closest: Option<f32> = None;
+    for triangle in mesh.indices.chunks_exact(3) {
+        let v0 = Point3::from(mesh.positions[triangle[0] as usize]);
+        let v1 = Point3::from(mesh.positions[triangle[1] as usize]);
+        let v2 = Point3::from(mesh.positions[triangle[2] as usize]);
+        if let Some(t) = intersect_triangle(origin, direction, v0, v1, v2) {
+            if closest.map_or(true, |c| t < c) {
+                closest = Some(t);
+            }
+        }
+    }
+    closest
+}
+
+// moller-trumbore, both faces count as a hit
+fn intersect_triangle(origin: Point3<f32>, direction: Vector3<f32>, v0: Point3<f32>, v1: Point3<f32>, v2: Point3<f32>) -> Option<f32> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let p = direction.cross(edge2);
+    let det = edge1.dot(p);
+    if det.abs() < 1e-8 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = origin - v0;
+    let u = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) * inv_det;
+    if t > 0.0 { Some(t) } else { None }
+}
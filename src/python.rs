@@ -0,0 +1,250 @@
+// python module around HeadlessRenderer, built with `--features python`. frames come back as
+// height x width x 4 uint8 numpy arrays, model stats as the dicts `kanirenderer info --json` prints
+
+use std::path::PathBuf;
+use numpy::ndarray::Array3;
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use crate::{HeadlessRenderer, State};
+
+fn runtime_error(err: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{:#}", err))
+}
+
+fn value_error(err: anyhow::Error) -> PyErr {
+    PyValueError::new_err(format!("{:#}", err))
+}
+
+// State holds wgpu handles that have to stay on the thread that made them
+#[pyclass(name = "Viewer", unsendable)]
+struct PyViewer {
+    renderer: HeadlessRenderer,
+    file_type: String,
+    model_path: Option<PathBuf>,
+}
+
+#[pymethods]
+impl PyViewer {
+    #[new]
+    #[pyo3(signature = (width = 512, height = 512, file_type = "default", software = false))]
+    fn new(width: u32, height: u32, file_type: &str, software: bool) -> PyResult<Self> {
+        State::check_file_type(file_type).map_err(value_error)?;
+        let renderer = HeadlessRenderer::with_adapter(file_type, width, height, software).map_err(runtime_error)?;
+        Ok(Self {
+            renderer,
+            file_type: file_type.to_string(),
+            model_path: None,
+        })
+    }
+
+    // replaces whatever was loaded before, blocks until the model and its textures are uploaded
+    fn load_model(&mut self, path: PathBuf) -> PyResult<()> {
+        self.model_path = None;
+        self.renderer.load_model(&path).map_err(runtime_error)?;
+        self.model_path = Some(path);
+        Ok(())
+    }
+
+    fn unload_model(&mut self) {
+        self.model_path = None;
+        self.renderer.unload_model();
+    }
+
+    #[getter]
+    fn size(&self) -> (u32, u32) {
+        self.renderer.size()
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> PyResult<()> {
+        self.renderer.resize(width, height).map_err(value_error)
+    }
+
+    fn set_mode(&mut self, mode: &str) -> PyResult<()> {
+        self.renderer.set_mode(mode).map_err(value_error)
+    }
+
+    // (min, max) of the loaded model in world space, None when nothing is loaded
+    fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        self.renderer.bounds().map(|bounds| (bounds.min, bounds.max))
+    }
+
+    fn look_at(&mut self, position: [f32; 3], target: [f32; 3]) {
+        self.renderer.look_at(position, target);
+    }
+
+    // points the camera at the model from far enough away that all of it is visible
+    #[pyo3(signature = (azimuth = 45.0, elevation = 25.0))]
+    fn frame_model(&mut self, azimuth: f32, elevation: f32) -> PyResult<()> {
+        let (position, target) = self.renderer.orbit_pose(azimuth, elevation).map_err(value_error)?;
+        self.renderer.look_at(position, target);
+        Ok(())
+    }
+
+    #[pyo3(signature = (direction, color = [1.0, 1.0, 1.0], intensity = 1.0))]
+    fn set_directional_light(&mut self, direction: [f32; 3], color: [f32; 3], intensity: f32) -> PyResult<()> {
+        if direction == [0.0; 3] {
+            return Err(PyValueError::new_err("light direction must not be zero"));
+        }
+        self.renderer.set_directional_light(direction, color, intensity);
+        Ok(())
+    }
+
+    fn set_point_light(&mut self, position: [f32; 3], color: [f32; 3], range: f32) {
+        self.renderer.set_point_light(position, color, range);
+    }
+
+    // {"model", "mesh", "distance"} of the closest mesh under pixel (x, y), None if there is none
+    fn pick<'py>(&self, py: Python<'py>, x: f32, y: f32) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some(hit) = self.renderer.pick(x, y) else {
+            return Ok(None);
+        };
+        let dict = PyDict::new(py);
+        dict.set_item("model", hit.model)?;
+        dict.set_item("mesh", hit.mesh)?;
+        dict.set_item("distance", hit.distance)?;
+        Ok(Some(dict))
+    }
+
+    fn render<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let image = self.renderer.render().map_err(runtime_error)?;
+        let (width, height) = image.dimensions();
+        let pixels = Array3::from_shape_vec((height as usize, width as usize, 4), image.into_raw())
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        Ok(pixels.into_pyarray(py))
+    }
+
+    // model_info of the loaded file, None when nothing is loaded
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let Some(path) = &self.model_path else {
+            return Ok(None);
+        };
+        stats_dict(py, &path.to_string_lossy(), &self.file_type).map(Some)
+    }
+}
+
+// vertex, triangle, mesh, material and texture stats of an obj, read from disk without a device
+#[pyfunction]
+#[pyo3(signature = (path, file_type = "default"))]
+fn model_info<'py>(py: Python<'py>, path: PathBuf, file_type: &str) -> PyResult<Bound<'py, PyAny>> {
+    stats_dict(py, &path.to_string_lossy(), file_type)
+}
+
+fn stats_dict<'py>(py: Python<'py>, path: &str, file_type: &str) -> PyResult<Bound<'py, PyAny>> {
+    let info = crate::model_info(path, file_type).map_err(runtime_error)?;
+    let value = serde_json::to_value(&info).map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+    json_to_py(py, &value)
+}
+
+fn json_to_py<'py>(py: Python<'py>, value: &serde_json::Value) -> PyResult<Bound<'py, PyAny>> {
+    use serde_json::Value;
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(value) => value.into_pyobject(py)?.to_owned().into_any(),
+        Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+            (Some(value), _) => value.into_pyobject(py)?.into_any(),
+            (None, Some(value)) => value.into_pyobject(py)?.into_any(),
+            _ => number.as_f64().unwrap_or(f64::NAN).into_pyobject(py)?.into_any(),
+        },
+        Value::String(value) => value.into_pyobject(py)?.into_any(),
+        Value::Array(items) => {
+            let items = items.iter().map(|item| json_to_py(py, item)).collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items)?.into_any()
+        }
+        Value::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, value) in fields {
+                dict.set_item(key, json_to_py(py, value)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+// the module name has to match the file python imports, see the README
+#[pymodule]
+fn kanirenderer_viewer(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyViewer>()?;
+    module.add_function(wrap_pyfunction!(model_info, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("res").join("cube.obj")
+    }
+
+    fn software_viewer() -> PyViewer {
+        if std::env::var_os("WGPU_BACKEND").is_none() {
+            std::env::set_var("WGPU_BACKEND", "gl");
+        }
+        PyViewer::new(64, 64, "default", true).unwrap_or_else(|err| panic!("no software adapter : {}, install mesa (llvmpipe)", err))
+    }
+
+    #[test]
+    fn model_info_is_a_dict_like_the_json() {
+        Python::initialize();
+        Python::attach(|py| {
+            let info = crate::model_info(&cube().to_string_lossy(), "default").unwrap();
+            let dict = model_info(py, cube(), "default").unwrap();
+            let dict = dict.cast::<PyDict>().unwrap();
+            let triangles: usize = dict.get_item("triangle_count").unwrap().unwrap().extract().unwrap();
+            assert_eq!(triangles, info.triangle_count);
+            let meshes = dict.get_item("meshes").unwrap().unwrap();
+            assert_eq!(meshes.cast::<PyList>().unwrap().len(), info.meshes.len());
+            let bounds = dict.get_item("bounds").unwrap().unwrap();
+            let min: Vec<f32> = bounds.get_item("min").unwrap().extract().unwrap();
+            assert_eq!(min, info.bounds.unwrap().min.to_vec());
+            assert!(model_info(py, PathBuf::from("missing.obj"), "default").unwrap_err().is_instance_of::<PyRuntimeError>(py));
+        });
+    }
+
+    #[test]
+    fn json_values_become_python_values() {
+        Python::initialize();
+        Python::attach(|py| {
+            let value = serde_json::json!({"none": null, "yes": true, "big": u64::MAX, "negative": -3, "half": 0.5, "list": ["a", 1]});
+            let converted = json_to_py(py, &value).unwrap();
+            assert!(converted.get_item("none").unwrap().is_none());
+            assert!(converted.get_item("yes").unwrap().extract::<bool>().unwrap());
+            assert_eq!(converted.get_item("big").unwrap().extract::<u64>().unwrap(), u64::MAX);
+            assert_eq!(converted.get_item("negative").unwrap().extract::<i64>().unwrap(), -3);
+            assert_eq!(converted.get_item("half").unwrap().extract::<f64>().unwrap(), 0.5);
+            let list = converted.get_item("list").unwrap();
+            assert!(list.is_instance_of::<PyList>());
+            assert_eq!((list.get_item(0).unwrap().extract::<String>().unwrap(), list.get_item(1).unwrap().extract::<u32>().unwrap()), ("a".to_string(), 1));
+        });
+    }
+
+    #[test]
+    fn bad_arguments_are_value_errors() {
+        Python::initialize();
+        let err = PyViewer::new(64, 64, "directx", true).err().unwrap();
+        Python::attach(|py| assert!(err.is_instance_of::<PyValueError>(py)));
+
+        let mut viewer = software_viewer();
+        let errors = [
+            viewer.resize(0, 64).unwrap_err(),
+            viewer.set_mode("sketch").unwrap_err(),
+            viewer.set_directional_light([0.0; 3], [1.0; 3], 1.0).unwrap_err(),
+            //nothing loaded, so there's nothing to frame
+            viewer.frame_model(45.0, 25.0).unwrap_err(),
+        ];
+        Python::attach(|py| {
+            for err in errors {
+                assert!(err.is_instance_of::<PyValueError>(py), "{}", err);
+            }
+            assert!(viewer.stats(py).unwrap().is_none());
+            let err = viewer.load_model(PathBuf::from("missing.obj")).unwrap_err();
+            assert!(err.is_instance_of::<PyRuntimeError>(py));
+        });
+
+        viewer.load_model(cube()).unwrap();
+        viewer.frame_model(45.0, 25.0).unwrap();
+        assert!(viewer.bounds().is_some());
+    }
+}
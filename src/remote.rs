@@ -0,0 +1,519 @@
+// opt-in remote control. with KANIRENDERER_REMOTE_PORT set the viewer listens on 127.0.0.1 for
+// json-rpc 2.0, one request per line and one response line back. connections run on their own
+// threads and hand every request to the event loop as a user event, so commands run between
+// frames on the thread that owns State. every request carries the token from
+// KANIRENDERER_REMOTE_TOKEN (or the file KANIRENDERER_REMOTE_TOKEN_FILE), without one the
+// server doesn't start
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc;
+use anyhow::{anyhow, Context};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use winit::event_loop::EventLoopProxy;
+use crate::{live_link, Attachment, RenderOutputMode, State, Transform};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// the command was understood but failed, loading, rendering, writing a file...
+const COMMAND_FAILED: i64 = -32000;
+
+pub(crate) struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl std::fmt::Display) -> Self {
+        Self { code, message: format!("{:#}", message) }
+    }
+}
+
+// a request waiting for the event loop, the answer goes back to the connection over reply
+pub(crate) struct RemoteRequest {
+    method: String,
+    params: Value,
+    reply: mpsc::Sender<Result<Value, RpcError>>,
+}
+
+pub(crate) fn port_from_env() -> anyhow::Result<Option<u16>> {
+    match std::env::var("KANIRENDERER_REMOTE_PORT") {
+        Ok(value) => value
+            .parse::<u16>()
+            .map(Some)
+            .map_err(|_| anyhow!("KANIRENDERER_REMOTE_PORT must be a port number, got {:?}", value)),
+        Err(_) => Ok(None),
+    }
+}
+
+pub(crate) fn token_from_env() -> anyhow::Result<String> {
+    let token = if let Ok(token) = std::env::var("KANIRENDERER_REMOTE_TOKEN") {
+        token
+    } else if let Some(path) = std::env::var_os("KANIRENDERER_REMOTE_TOKEN_FILE") {
+        std::fs::read_to_string(&path).with_context(|| format!("could not read the remote token from {:?}", path))?
+    } else {
+        return Err(anyhow!("remote control needs a token, set KANIRENDERER_REMOTE_TOKEN or KANIRENDERER_REMOTE_TOKEN_FILE"));
+    };
+    let token = token.trim().to_string();
+    if token.is_empty() {
+        return Err(anyhow!("the remote control token is empty"));
+    }
+    Ok(token)
+}
+
+// screenshots only go below this folder, KANIRENDERER_REMOTE_OUTPUT_DIR or ./screenshots
+fn output_dir() -> PathBuf {
+    std::env::var_os("KANIRENDERER_REMOTE_OUTPUT_DIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("screenshots"))
+}
+
+// a relative path without .. so a client can't write anywhere else
+fn output_path(dir: &Path, path: &Path) -> Result<PathBuf, RpcError> {
+    if path.as_os_str().is_empty() || !path.components().all(|component| matches!(component, Component::Normal(_))) {
+        return Err(RpcError::new(INVALID_PARAMS, format!("{:?} must be a relative path inside the output folder", path)));
+    }
+    Ok(dir.join(path))
+}
+
+pub(crate) fn start(port: u16, token: String, proxy: EventLoopProxy<RemoteRequest>) -> anyhow::Result<()> {
+    let token = Arc::new(token);
+    let listener = TcpListener::bind(("127.0.0.1", port)).with_context(|| format!("could not listen on 127.0.0.1:{}", port))?;
+    println!("remote control listening on 127.0.0.1:{}, screenshots go to {:?}", port, output_dir());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let proxy = proxy.clone();
+                    let token = token.clone();
+                    std::thread::spawn(move || serve(stream, &token, proxy));
+                }
+                Err(err) => println!("remote control connection failed : {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn serve(stream: TcpStream, token: &str, proxy: EventLoopProxy<RemoteRequest>) {
+    let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+    println!("remote control client {} connected", peer);
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    let mut writer = stream;
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        //anything that isn't json-rpc with the right token, a browser sending an http request
+        //for example, gets one error and the connection is closed
+        let request = match check_request(&line, token) {
+            Ok(request) => request,
+            Err((id, err)) => {
+                println!("remote control client {} closed : {}", peer, err.message);
+                let _ = writeln!(writer, "{}", error_response(id, err));
+                break;
+            }
+        };
+        if let Some(response) = respond(request, &proxy) {
+            if writeln!(writer, "{}", response).is_err() {
+                break;
+            }
+        }
+    }
+    println!("remote control client {} disconnected", peer);
+}
+
+fn check_request(line: &str, token: &str) -> Result<Value, (Value, RpcError)> {
+    let request = serde_json::from_str::<Value>(line).map_err(|err| (Value::Null, RpcError::new(PARSE_ERROR, err)))?;
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    if request.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return Err((id, RpcError::new(INVALID_REQUEST, "not a json-rpc 2.0 request")));
+    }
+    if !request.get("method").map_or(false, Value::is_string) {
+        return Err((id, RpcError::new(INVALID_REQUEST, "the request needs a method")));
+    }
+    let sent = request.get("token").and_then(Value::as_str).unwrap_or("");
+    if !same_token(sent, token) {
+        return Err((id, RpcError::new(INVALID_REQUEST, "wrong or missing token")));
+    }
+    Ok(request)
+}
+
+// compares every byte so the time doesn't tell how much of the token was right
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// None for notifications, requests without an id get no answer
+fn respond(request: Value, proxy: &EventLoopProxy<RemoteRequest>) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let method = request.get("method").and_then(Value::as_str).unwrap_or_default();
+
+    let (reply, answer) = mpsc::channel();
+    let request = RemoteRequest {
+        method: method.to_string(),
+        params: request.get("params").cloned().unwrap_or(Value::Null),
+        reply,
+    };
+    let result = match proxy.send_event(request) {
+        Ok(()) => answer.recv().unwrap_or_else(|_| Err(RpcError::new(COMMAND_FAILED, "the viewer dropped the request"))),
+        Err(_) => Err(RpcError::new(COMMAND_FAILED, "the viewer is closing")),
+    };
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => error_response(id, err),
+    })
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": err.code, "message": err.message } })
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    //methods without arguments accept a missing params as well as {}
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err))
+}
+
+fn failed(err: anyhow::Error) -> RpcError {
+    RpcError::new(COMMAND_FAILED, err)
+}
+
+#[derive(Deserialize)]
+struct LoadModelParams {
+    path: PathBuf,
+    //keep the models that are already loaded
+    #[serde(default)]
+    add: bool,
+}
+
+#[derive(Deserialize)]
+struct CameraParams {
+    position: [f32; 3],
+    target: [f32; 3],
+}
+
+#[derive(Deserialize)]
+struct FrameModelParams {
+    #[serde(default = "default_azimuth")]
+    azimuth: f32,
+    #[serde(default = "default_elevation")]
+    elevation: f32,
+}
+
+fn default_azimuth() -> f32 {
+    45.0
+}
+
+fn default_elevation() -> f32 {
+    25.0
+}
+
+// missing fields keep their current value
+#[derive(Deserialize)]
+struct DirectionalLightParams {
+    direction: Option<[f32; 3]>,
+    color: Option<[f32; 3]>,
+    intensity: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct PointLightParams {
+    position: [f32; 3],
+    color: [f32; 3],
+    range: f32,
+}
+
+#[derive(Deserialize)]
+struct ModeParams {
+    mode: String,
+}
+
+#[derive(Deserialize)]
+struct ScreenshotParams {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct PickParams {
+    x: f32,
+    y: f32,
+}
+
+// rotation in degrees around x, y and z. fields that are left out keep their value
+#[derive(Deserialize)]
+struct EntityParams {
+    id: Option<u32>,
+    parent: Option<u32>,
+    model: Option<usize>,
+    mesh: Option<usize>,
+    #[serde(default)]
+    light: bool,
+    name: Option<String>,
+    visible: Option<bool>,
+    position: Option<[f32; 3]>,
+    rotation: Option<[f32; 3]>,
+    scale: Option<[f32; 3]>,
+}
+
+impl EntityParams {
+    fn apply(&self, transform: Transform) -> Transform {
+        Transform {
+            position: self.position.unwrap_or(transform.position),
+            rotation: self.rotation.unwrap_or(transform.rotation),
+            scale: self.scale.unwrap_or(transform.scale),
+        }
+    }
+
+    fn attachment(&self) -> Result<Attachment, RpcError> {
+        Ok(match (self.model, self.mesh, self.light) {
+            (Some(model), Some(mesh), false) => Attachment::Mesh { model, mesh },
+            (Some(model), None, false) => Attachment::Model { model },
+            (None, None, true) => Attachment::PointLight,
+            (None, None, false) => Attachment::None,
+            (None, Some(_), _) => return Err(RpcError::new(INVALID_PARAMS, "a mesh needs the model it belongs to")),
+            (Some(_), _, true) => return Err(RpcError::new(INVALID_PARAMS, "an entity holds either a model or the light")),
+        })
+    }
+
+    fn id(&self, method: &str) -> Result<u32, RpcError> {
+        self.id.ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("{} needs an entity id", method)))
+    }
+}
+
+#[derive(Deserialize)]
+struct RemoveMeshParams {
+    name: String,
+}
+
+impl State {
+    pub(crate) fn handle_remote(&mut self, request: RemoteRequest) {
+        let result = self.remote_command(&request.method, request.params);
+        if let Err(err) = &result {
+            println!("remote {} failed : {}", request.method, err.message);
+        }
+        let _ = request.reply.send(result);
+    }
+
+    fn remote_command(&mut self, method: &str, params_value: Value) -> Result<Value, RpcError> {
+        match method {
+            "load_model" => {
+                let params: LoadModelParams = params(params_value)?;
+                if !params.path.is_file() {
+                    return Err(RpcError::new(INVALID_PARAMS, format!("{:?} is not a file", params.path)));
+                }
+                if params.add {
+                    if self.model_loader.is_some() {
+                        return Err(RpcError::new(COMMAND_FAILED, "still loading, wait until status says loading is false"));
+                    }
+                    self.model_load_error = None;
+                    self.open_model(&params.path, true);
+                } else {
+                    self.start_loading(&params.path);
+                }
+                Ok(json!({ "loading": true }))
+            }
+            "unload_models" => {
+                self.unload_models();
+                Ok(Value::Null)
+            }
+            "status" => Ok(self.remote_status()),
+            "get_camera" => {
+                let position = self.camera.position;
+                let target = position + self.camera.direction();
+                Ok(json!({ "position": [position.x, position.y, position.z], "target": [target.x, target.y, target.z] }))
+            }
+            "set_camera" => {
+                let params: CameraParams = params(params_value)?;
+                self.look_at(params.position, params.target);
+                Ok(Value::Null)
+            }
+            "frame_model" => {
+                let params: FrameModelParams = params(params_value)?;
+                let (position, target) = self.orbit_pose(params.azimuth, params.elevation).map_err(failed)?;
+                self.look_at(position, target);
+                Ok(json!({ "position": position, "target": target }))
+            }
+            "set_directional_light" => {
+                let params: DirectionalLightParams = params(params_value)?;
+                if params.direction == Some([0.0; 3]) {
+                    return Err(RpcError::new(INVALID_PARAMS, "light direction must not be zero"));
+                }
+                let light = &self.directional_light;
+                let direction = params.direction.unwrap_or(light.light_direction);
+                let color = params.color.unwrap_or(light.color);
+                let intensity = params.intensity.unwrap_or(light.intensity);
+                self.set_directional_light(direction, color, intensity);
+                Ok(Value::Null)
+            }
+            "set_point_light" => {
+                let params: PointLightParams = params(params_value)?;
+                self.set_point_light(params.position, params.color, params.range);
+                Ok(Value::Null)
+            }
+            "set_mode" => {
+                let params: ModeParams = params(params_value)?;
+                self.set_mode_by_name(&params.mode).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+                Ok(Value::Null)
+            }
+            "modes" => Ok(json!(RenderOutputMode::ALL.iter().map(|mode| mode.name()).collect::<Vec<_>>())),
+            // the scene without the debug overlays at the window size
+            "screenshot" => {
+                let params: ScreenshotParams = params(params_value)?;
+                let dir = output_dir();
+                let path = output_path(&dir, &params.path)?;
+                let image = self.render_image().map_err(failed)?;
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).with_context(|| format!("could not create {:?}", parent)).map_err(failed)?;
+                }
+                image.save(&path).with_context(|| format!("could not save {:?}", path)).map_err(failed)?;
+                println!("remote screenshot saved to {:?}", path);
+                Ok(json!({ "path": path, "width": image.width(), "height": image.height() }))
+            }
+            "pick" => {
+                let params: PickParams = params(params_value)?;
+                Ok(match self.pick_at(params.x, params.y) {
+                    Some(hit) => json!({
+                        "model": hit.model,
+                        "mesh": hit.mesh,
+                        "mesh_name": self.models[hit.model].meshes[hit.mesh].name,
+                        "entity": self.entity_at(hit.model, hit.mesh, hit.instance),
+                        "distance": hit.distance,
+                    }),
+                    None => Value::Null,
+                })
+            }
+            "entities" => Ok(json!(self.entities.iter().map(|entity| {
+                let transform = entity.transform();
+                json!({
+                    "id": entity.id,
+                    "name": entity.name,
+                    "parent": entity.parent,
+                    "children": entity.children,
+                    "attachment": entity.attachment,
+                    "visible": entity.visible,
+                    "position": transform.position,
+                    "rotation": transform.rotation,
+                    "scale": transform.scale,
+                })
+            }).collect::<Vec<_>>())),
+            "spawn_entity" => {
+                let params: EntityParams = params(params_value)?;
+                let id = self.add_entity(params.parent, params.attachment()?, params.apply(Transform::default())).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+                if let Some(name) = params.name {
+                    self.entity_mut(id).map_err(failed)?.name = name;
+                }
+                if let Some(visible) = params.visible {
+                    self.set_entity_visible(id, visible).map_err(failed)?;
+                }
+                Ok(json!({ "id": id }))
+            }
+            "set_entity" => {
+                let params: EntityParams = params(params_value)?;
+                let id = params.id("set_entity")?;
+                let entity = self.entity(id).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+                let transform = params.apply(entity.transform());
+                self.set_entity_transform(id, transform).map_err(failed)?;
+                if let Some(name) = params.name {
+                    self.entity_mut(id).map_err(failed)?.name = name;
+                }
+                if let Some(visible) = params.visible {
+                    self.set_entity_visible(id, visible).map_err(failed)?;
+                }
+                Ok(Value::Null)
+            }
+            // a missing or null parent moves the entity to the top of the tree
+            "set_parent" => {
+                let params: EntityParams = params(params_value)?;
+                let id = params.id("set_parent")?;
+                self.set_entity_parent(id, params.parent).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+                Ok(Value::Null)
+            }
+            "remove_entity" => {
+                let params: EntityParams = params(params_value)?;
+                let id = params.id("remove_entity")?;
+                self.remove_entity(id).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+                Ok(Value::Null)
+            }
+            "push_mesh" => {
+                let update: live_link::MeshUpdate = params(params_value)?;
+                self.push_mesh(update).map_err(|err| RpcError::new(INVALID_PARAMS, err))
+            }
+            "remove_mesh" => {
+                let params: RemoveMeshParams = params(params_value)?;
+                self.remove_mesh(&params.name).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+                Ok(Value::Null)
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method {:?}", method))),
+        }
+    }
+
+    fn remote_status(&self) -> Value {
+        let mut vertices = 0;
+        let mut triangles = 0;
+        let models = self.models.iter().map(|model| {
+            let meshes = model.meshes.iter().map(|mesh| {
+                vertices += mesh.positions.len();
+                triangles += mesh.indices.len() / 3;
+                json!({
+                    "name": mesh.name,
+                    "vertices": mesh.positions.len(),
+                    "triangles": mesh.indices.len() / 3,
+                    "material": model.materials.get(mesh.material).map(|material| material.name.clone()),
+                })
+            }).collect::<Vec<_>>();
+            json!({ "meshes": meshes, "materials": model.materials.len(), "instances": model.instance_num })
+        }).collect::<Vec<_>>();
+        json!({
+            "loading": self.model_loader.is_some(),
+            "load_error": self.model_load_error,
+            "mode": self.render_output_mode.name(),
+            "size": [self.config.width, self.config.height],
+            "models": models,
+            "vertices": vertices,
+            "triangles": triangles,
+            "bounds": self.scene_bounds(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_json_rpc_with_the_token_gets_through() {
+        let ok = r#"{"jsonrpc": "2.0", "id": 1, "method": "status", "token": "secret"}"#;
+        assert!(check_request(ok, "secret").is_ok());
+        let (_, err) = check_request("POST / HTTP/1.1", "secret").unwrap_err();
+        assert_eq!(err.code, PARSE_ERROR);
+        for line in [
+            r#"{"id": 1, "method": "status", "token": "secret"}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "token": "secret"}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "status"}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "status", "token": "secres"}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "status", "token": "secret2"}"#,
+        ] {
+            let (id, err) = check_request(line, "secret").unwrap_err();
+            assert_eq!((id, err.code), (json!(1), INVALID_REQUEST), "{}", line);
+        }
+    }
+
+    #[test]
+    fn screenshots_stay_in_the_output_folder() {
+        let dir = Path::new("out");
+        assert_eq!(output_path(dir, Path::new("a/cube.png")).ok(), Some(PathBuf::from("out/a/cube.png")));
+        for path in ["", "/tmp/cube.png", "../cube.png", "a/../../cube.png", "./cube.png"] {
+            assert!(output_path(dir, Path::new(path)).is_err(), "{}", path);
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
 use anyhow::Ok;
-use rayon::iter::IntoParallelIterator;
+use std::time::Instant;
+use rayon::prelude::*;
 use wgpu::util::DeviceExt;
-use crate::{model::{self, Instance}, texture};
+use crate::{asset_cache, model::{self, Instance}, texture};
 use cfg_if::cfg_if;
 use cgmath::{num_traits::ToPrimitive, perspective, prelude::*, Quaternion, Vector3};
 
@@ -48,6 +50,176 @@ pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
 }
 
 
+// strip mtl texture options such as "-bm 1.0" in front of the actual file name, everything
+// after the options is the name so names with spaces stay whole
+fn strip_texture_options(file_name: &str) -> &str {
+    let mut rest = file_name.trim();
+    while rest.starts_with('-') {
+        let (option, after) = next_token(rest);
+        rest = after;
+        //-o, -s and -t take up to three numbers, the other options a fixed count
+        let (min_args, max_args) = match option {
+            "-o" | "-s" | "-t" => (1, 3),
+            "-mm" => (2, 2),
+            _ => (1, 1),
+        };
+        for arg in 0..max_args {
+            let (value, after) = next_token(rest);
+            //on/off and channel names are arguments too, only the optional numbers are checked
+            if value.is_empty() || (arg >= min_args && value.parse::<f32>().is_err()) {
+                break;
+            }
+            rest = after;
+        }
+    }
+    rest
+}
+
+// the first whitespace separated token and the trimmed rest after it
+fn next_token(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(end) => (&text[..end], text[end..].trim_start()),
+        None => (text, ""),
+    }
+}
+
+// resolve a file referenced by an obj/mtl relative to the model directory,
+// mtl files exported on windows often contain backslashes or absolute paths
+// from the artist's machine, so fall back to the bare file name next to the model
+pub fn resolve_path(model_dir: &Path, file_name: &str) -> PathBuf {
+    let normalized = strip_texture_options(file_name).replace('\\', "/");
+    let path = Path::new(&normalized);
+
+    if path.is_absolute() && path.exists() {
+        return path.to_path_buf();
+    }
+
+    let is_windows_absolute = normalized.len() > 2
+        && normalized.as_bytes()[1] == b':'
+        && normalized.as_bytes()[0].is_ascii_alphabetic();
+    let relative = if is_windows_absolute || path.is_absolute() {
+        None
+    } else {
+        Some(model_dir.join(path))
+    };
+    if let Some(relative) = &relative {
+        if relative.exists() {
+            return relative.clone();
+        }
+    }
+
+    if let Some(bare_name) = path.file_name() {
+        let next_to_model = model_dir.join(bare_name);
+        if next_to_model.exists() {
+            return next_to_model;
+        }
+    }
+
+    relative.unwrap_or_else(|| path.to_path_buf())
+}
+
+// work out which texture a material slot uses, missing textures share the fallback key
+// and get added to missing
+fn material_texture_key(model_dir: &Path, texture_name: Option<&String>, is_normal_map: bool, opengl: bool, missing: &mut Vec<PathBuf>) -> texture::TextureKey {
+    let texture_kind = if is_normal_map { "normal" } else { "diffuse" };
+    match texture_name {
+        Some(texture_name) => {
+            let path = resolve_path(model_dir, texture_name);
+            if path.exists() {
+                texture::TextureKey { path, is_normal_map, opengl }
+            } else {
+                eprintln!("{} texture {:?} not found, using fallback texture", texture_kind, path);
+                if !missing.contains(&path) {
+                    missing.push(path);
+                }
+                texture::TextureKey::fallback(is_normal_map, opengl)
+            }
+        }
+        None => {
+            eprintln!("no {} textures, using fallback texture", texture_kind);
+            texture::TextureKey::fallback(is_normal_map, opengl)
+        }
+    }
+}
+
+// read and decode a material texture, falling back to the default normal map
+// when the texture cannot be decoded
+pub fn decode_material_texture(key: &texture::TextureKey, features: wgpu::Features) -> texture::DecodedImage {
+    let default_bytes = include_bytes!("../res/default_normal.png");
+    let fallback_label = if key.is_normal_map {
+        "fallback normal texture"
+    } else {
+        "fallback diffuse texture"
+    };
+
+    if key.is_fallback() {
+        return texture::DecodedImage::from_bytes(default_bytes, fallback_label, key.is_normal_map).unwrap();
+    }
+
+    let label = key.path.to_string_lossy().to_string();
+    let data = pollster::block_on(load_binary(&label)).unwrap_or(default_bytes.to_vec());
+    let cache = asset_cache::AssetCache::from_env();
+    let cached = cache.as_ref().and_then(|cache| cache.load_texture(key, &data));
+    let decoded = match cached {
+        Some(cached) => Ok(cached),
+        None => {
+            let decoded = if key.opengl {
+                texture::DecodedImage::from_opengl_bytes(&data, &label, key.is_normal_map)
+            } else {
+                texture::DecodedImage::from_bytes(&data, &label, key.is_normal_map)
+            };
+            //stored before fit_to_features, what the adapter can sample is decided on every launch
+            if let (Some(cache), std::result::Result::Ok(decoded)) = (&cache, &decoded) {
+                cache.store_texture(key, &data, decoded);
+            }
+            decoded
+        }
+    };
+    decoded
+        .and_then(|decoded| decoded.fit_to_features(features))
+        .unwrap_or_else(|err| {
+            println!("{}: {:?}, using fallback texture", label, err);
+            texture::DecodedImage::from_bytes(default_bytes, fallback_label, key.is_normal_map).unwrap()
+        })
+}
+
+// decode and upload every texture in keys that is not cached yet
+fn load_textures_into_cache(
+    keys: &[texture::TextureKey],
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture_cache: &mut texture::TextureCache,
+) -> anyhow::Result<()> {
+    let mut missing_keys: Vec<&texture::TextureKey> = Vec::new();
+    for key in keys {
+        if !texture_cache.contains(key) && !missing_keys.contains(&key) {
+            missing_keys.push(key);
+        }
+    }
+
+    //decode in parallel
+    let features = device.features();
+    let texture_decode_start = Instant::now();
+    let decoded_textures = missing_keys
+        .par_iter()
+        .map(|key| decode_material_texture(key, features))
+        .collect::<Vec<_>>();
+    let texture_decode_duration = texture_decode_start.elapsed();
+
+    //upload all decoded textures once decoding is done
+    let texture_upload_start = Instant::now();
+    for (key, decoded) in missing_keys.into_iter().zip(decoded_textures.iter()) {
+        let texture = texture::Texture::from_decoded(device, queue, decoded)?;
+        texture_cache.insert(key.clone(), texture);
+    }
+    let texture_upload_duration = texture_upload_start.elapsed();
+
+    println!("texture decoding time {:?} ({} textures, {} reused from cache)", texture_decode_duration, decoded_textures.len(), keys.len() - decoded_textures.len());
+    println!("texture upload time {:?}", texture_upload_duration);
+    Ok(())
+}
+
 pub async fn load_texture(file_name: &str, is_normal_map: bool, device: &wgpu::Device, queue: &wgpu::Queue,) -> anyhow::Result<texture::Texture> {
     let default_texture: Vec<u8> = include_bytes!("../res/default_normal.png").to_vec();
     let data = load_binary(file_name).await.unwrap_or(default_texture);
@@ -60,15 +232,28 @@ pub async fn load_opengl_texture(file_name: &str, is_normal_map: bool, device: &
     texture::Texture::from_opengl_bytes(device, queue, &data, file_name, is_normal_map)
 }
 
-pub async fn load_model(
-    mut file_name: &str, 
-    file_type: String,
-    device: &wgpu::Device, 
-    queue: &wgpu::Queue, 
-    layout: &wgpu::BindGroupLayout,
-    instance: u32,
-    spawn_position: Vector3<f32> 
-) -> anyhow::Result<model::Model> {
+// everything read from the obj and mtl files, before any texture is decoded
+pub struct ObjData {
+    pub file_name: String,
+    pub models: Vec<tobj::Model>,
+    pub material_names: Vec<String>,
+    pub mtl_paths: Vec<PathBuf>,
+    //[diffuse, normal] per material
+    pub texture_keys: Vec<texture::TextureKey>,
+    //referenced by an mtl file but not found, those materials use the fallback texture
+    pub missing_textures: Vec<PathBuf>,
+}
+
+// cpu side of a mesh, can be built off the render thread
+#[derive(Clone)]
+pub struct MeshData {
+    pub name: String,
+    pub vertices: Vec<model::ModelVertex>,
+    pub indices: Vec<u32>,
+    pub material: usize,
+}
+
+pub async fn parse_obj(mut file_name: &str, file_type: &str) -> anyhow::Result<ObjData> {
     let obj_text: String;
     if file_name.is_empty(){
         file_name = "default_cube.obj"
@@ -77,8 +262,11 @@ pub async fn load_model(
         "default_cube.obj"   => obj_text = load_string(file_name).await.unwrap_or(include_str!("../res/cube.obj").to_string()),
         _                   => obj_text = load_string(file_name).await?,
     }
+    let model_dir = Path::new(file_name).parent().unwrap_or(Path::new("")).to_path_buf();
+    let obj_parse_start = Instant::now();
     let obj_cursor = Cursor::new(obj_text);
     let mut obj_reader = BufReader::new(obj_cursor);
+    let mtl_paths = std::cell::RefCell::new(Vec::new());
 
 
     let (models, obj_materials) = tobj::load_obj_buf_async(
@@ -88,184 +276,177 @@ pub async fn load_model(
             single_index: true,
             ..Default::default()
         },
-        |p| async move {
-            let mat_text: String;
-            match file_name {
-                "default_cube.obj"   => mat_text = load_string(&p).await.unwrap_or(include_str!("../res/cube.mtl").to_string()),
-                _                   => mat_text = load_string(&p).await.unwrap_or(include_str!("../res/cube.mtl").to_string()),
+        |p| {
+            let mtl_path = resolve_path(&model_dir, &p);
+            mtl_paths.borrow_mut().push(mtl_path.clone());
+            async move {
+                let mat_text: String;
+                match file_name {
+                    "default_cube.obj"   => mat_text = load_string(&mtl_path.to_string_lossy()).await.unwrap_or(include_str!("../res/cube.mtl").to_string()),
+                    _                   => mat_text = load_string(&mtl_path.to_string_lossy()).await.unwrap_or(include_str!("../res/cube.mtl").to_string()),
+                }
+                // = load_string(&p).await.unwrap();
+                tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
             }
-            // = load_string(&p).await.unwrap();
-            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
         },
     )
     .await?;
 
-    let mut materials = Vec::new();
-
-    for m in obj_materials? {
-        let diffuse_texture: texture::Texture;
-        let normal_texture: texture::Texture;
-        let normal_bytes = include_bytes!("../res/default_normal.png");
-        match file_type.as_str() {
-            "default" =>    {   if !m.diffuse_texture.is_none() {
-                                    diffuse_texture = load_texture(&m.diffuse_texture.unwrap().as_str(), false, device, queue).await.unwrap_or(texture::Texture::from_bytes(device, queue, normal_bytes, "fallback diffuse texture", false).unwrap());
-                                } else {
-                                    println!("no diffuse textures, using fallback texture");
-                                    diffuse_texture = texture::Texture::from_bytes(device, queue, normal_bytes, "using a default normal map as fallback diffuse texture", false).unwrap();
-                                }
-                
-                                if !&m.normal_texture.is_none() {
-                                    normal_texture = load_texture(&m.normal_texture.unwrap().as_str(), true, device, queue).await.unwrap_or(texture::Texture::from_bytes(device, queue, normal_bytes, "fallback normal texture", true).unwrap());
-                                } else {
-                                    println!("no normal textures, using fallback texture");
-                                    normal_texture = texture::Texture::from_bytes(device, queue, normal_bytes, "default_normal", true).unwrap();
-                                    //load_texture("default_normal.png", device, queue).await?;
-                                }
-                            },
-            "opengl" =>     {   if !m.diffuse_texture.is_none() {
-                                    diffuse_texture = load_opengl_texture(&m.diffuse_texture.unwrap().as_str(), false, device, queue, ).await.unwrap_or(texture::Texture::from_bytes(device, queue, normal_bytes, "fallback diffuse texture", false).unwrap());
-                                } else {
-                                    println!("no diffuse textures, using fallback texture");
-                                    diffuse_texture = texture::Texture::from_bytes(device, queue, normal_bytes, "using a default normal map as fallback diffuse texture", false).unwrap();
-                                }
-                                if !&m.normal_texture.is_none() {
-                                    normal_texture = load_opengl_texture(&m.normal_texture.unwrap().as_str(), true, device, queue).await.unwrap_or(texture::Texture::from_bytes(device, queue, normal_bytes, "fallback normal texture", true).unwrap());
-                                } else {
-                                    println!("no normal textures, using fallback texture");
-                                    normal_texture = texture::Texture::from_bytes(device, queue, normal_bytes, "default_normal", true).unwrap();
-                                    //load_texture("default_normal.png", device, queue).await?;
-                                }
-                            },
-            _ => panic!("no file type given"),
-        }; 
-        // let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        //     layout,
-        //     entries: &[
-        //         wgpu::BindGroupEntry {
-        //             binding: 0,
-        //             resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-        //         },
-        //         wgpu::BindGroupEntry{
-        //             binding: 1,
-        //             resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-        //         },
-        //     ],
-        //     label: None,
-        // });
-        
-
-        materials.push(model::Material::new(
-            device,
-            &m.name,
-            diffuse_texture,
-            normal_texture,
-            layout,));
-    }
+    eprintln!("obj parsing time {:?}", obj_parse_start.elapsed());
 
-    if materials.len() == 0 {
-        println!("{:?} do not have any materials", file_name);
-        println!("trying to add a default material");
-        let normal_bytes = include_bytes!("../res/default_normal.png");
-        let diffuse_texture = texture::Texture::from_bytes(device, queue, normal_bytes, "using a default normal map as fallback diffuse texture", false).unwrap();
-        let normal_texture = texture::Texture::from_bytes(device, queue, normal_bytes, "default_normal", true).unwrap();
+    let obj_materials = obj_materials?;
+    let opengl_textures = match file_type {
+        "default" => false,
+        "opengl" => true,
+        _ => panic!("no file type given"),
+    };
 
-        materials.push(model::Material::new(
-            device,
-            "default material",
-            diffuse_texture,
-            normal_texture,
-            layout,));
+    let mut missing_textures = Vec::new();
+    let mut texture_keys = Vec::new();
+    for m in &obj_materials {
+        texture_keys.push(material_texture_key(&model_dir, m.diffuse_texture.as_ref(), false, opengl_textures, &mut missing_textures));
+        texture_keys.push(material_texture_key(&model_dir, m.normal_texture.as_ref(), true, opengl_textures, &mut missing_textures));
+    }
+    let mut material_names = obj_materials.iter().map(|m| m.name.clone()).collect::<Vec<_>>();
+
+    if obj_materials.is_empty() {
+        eprintln!("{:?} do not have any materials", file_name);
+        eprintln!("trying to add a default material");
+        material_names.push("default material".to_string());
+        texture_keys.push(texture::TextureKey::fallback(false, opengl_textures));
+        texture_keys.push(texture::TextureKey::fallback(true, opengl_textures));
     }
 
-    let meshes = models
-        .into_iter()
-        .map(|m| {
-            let mut vertices = (0..m.mesh.positions.len() / 3)
-                .map(|i| model::ModelVertex {
-                    position: [
-                        m.mesh.positions[i *3],
-                        m.mesh.positions[i * 3 + 1],
-                        m.mesh.positions[i * 3 + 2],
-                    ],
-                    tex_coords: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
-                    normal: [
-                        m.mesh.normals[i * 3],
-                        m.mesh.normals[i * 3 + 1],
-                        m.mesh.normals[i * 3 + 2],
-                    ],
-                    tangent: [0.0; 3],
-                    bitangent: [0.0; 3],
-                })
-                .collect::<Vec<_>>();
-
-            let indices = &m.mesh.indices;
-            let mut triangles_included = vec![0; vertices.len()];
-
-            for c in indices.chunks(3) {
-                let v0 = vertices[c[0] as usize];
-                let v1 = vertices[c[1] as usize];
-                let v2 = vertices[c[2] as usize];
-
-                let pos0: cgmath::Vector3<_> = v0.position.into();
-                let pos1: cgmath::Vector3<_> = v1.position.into();
-                let pos2: cgmath::Vector3<_> = v2.position.into();
-
-                let uv0: cgmath::Vector2<_> = v0.tex_coords.into();
-                let uv1: cgmath::Vector2<_> = v1.tex_coords.into();
-                let uv2: cgmath::Vector2<_> = v2.tex_coords.into();
-
-                let delta_pos1 = pos1 - pos0;
-                let delta_pos2 = pos2 - pos0;
-
-                let delta_uv1 = uv1 - uv0;
-                let delta_uv2 = uv2 - uv0;
-
-                let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
-                let tangent = (delta_pos1 *  delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
-
-                let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
-
-                vertices[c[0] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[0] as usize].tangent)).into();
-                vertices[c[1] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[1] as usize].tangent)).into();
-                vertices[c[2] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[2] as usize].tangent)).into(); 
-                vertices[c[0] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[0] as usize].bitangent)).into();
-                vertices[c[1] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[1] as usize].bitangent)).into();
-                vertices[c[2] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[2] as usize].bitangent)).into();
-
-                triangles_included[c[0] as usize] += 1;
-                triangles_included[c[1] as usize] += 1;
-                triangles_included[c[2] as usize] += 1;
-            }
-
-            for (i, n) in triangles_included.into_iter().enumerate() {
-                let denom = 1.0/ n as f32;
-                let mut v = &mut vertices[i];
-                v.tangent = (cgmath::Vector3::from(v.tangent) * denom).into();
-                v.bitangent = (cgmath::Vector3::from(v.bitangent) * denom).into();
-            }
+    Ok(ObjData { file_name: file_name.to_string(), models, material_names, mtl_paths: mtl_paths.into_inner(), texture_keys, missing_textures })
+}
 
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Vertex Buffer", file_name)),
-                contents: bytemuck::cast_slice(&vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Index Buffer", file_name)),
-                contents: bytemuck::cast_slice(&m.mesh.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
-            model::Mesh {
-                name: file_name.to_string(),
-                vertex_buffer,
-                index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
-            }
+// read vertices out of a tobj mesh and generate tangents/bitangents for normal mapping
+pub fn build_mesh_data(file_name: &str, m: &tobj::Model) -> MeshData {
+    let mut vertices = (0..m.mesh.positions.len() / 3)
+        .map(|i| model::ModelVertex {
+            position: [
+                m.mesh.positions[i *3],
+                m.mesh.positions[i * 3 + 1],
+                m.mesh.positions[i * 3 + 2],
+            ],
+            tex_coords: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
+            normal: [
+                m.mesh.normals[i * 3],
+                m.mesh.normals[i * 3 + 1],
+                m.mesh.normals[i * 3 + 2],
+            ],
+            tangent: [0.0; 3],
+            bitangent: [0.0; 3],
         })
         .collect::<Vec<_>>();
 
+    generate_tangents(&mut vertices, &m.mesh.indices);
+
+    let name = if m.name.is_empty() { file_name.to_string() } else { m.name.clone() };
+    MeshData {
+        name,
+        vertices,
+        indices: m.mesh.indices.clone(),
+        material: m.mesh.material_id.unwrap_or(0),
+    }
+}
+
+// per vertex tangents/bitangents averaged over the triangles using it
+pub fn generate_tangents(vertices: &mut [model::ModelVertex], indices: &[u32]) {
+    let mut triangles_included = vec![0; vertices.len()];
+
+    for c in indices.chunks(3) {
+        let v0 = vertices[c[0] as usize];
+        let v1 = vertices[c[1] as usize];
+        let v2 = vertices[c[2] as usize];
+
+        let pos0: cgmath::Vector3<_> = v0.position.into();
+        let pos1: cgmath::Vector3<_> = v1.position.into();
+        let pos2: cgmath::Vector3<_> = v2.position.into();
+
+        let uv0: cgmath::Vector2<_> = v0.tex_coords.into();
+        let uv1: cgmath::Vector2<_> = v1.tex_coords.into();
+        let uv2: cgmath::Vector2<_> = v2.tex_coords.into();
+
+        let delta_pos1 = pos1 - pos0;
+        let delta_pos2 = pos2 - pos0;
+
+        let delta_uv1 = uv1 - uv0;
+        let delta_uv2 = uv2 - uv0;
+
+        let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
+        let tangent = (delta_pos1 *  delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
+
+        let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
+
+        vertices[c[0] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[0] as usize].tangent)).into();
+        vertices[c[1] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[1] as usize].tangent)).into();
+        vertices[c[2] as usize].tangent = (tangent + cgmath::Vector3::from(vertices[c[2] as usize].tangent)).into(); 
+        vertices[c[0] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[0] as usize].bitangent)).into();
+        vertices[c[1] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[1] as usize].bitangent)).into();
+        vertices[c[2] as usize].bitangent = (bitangent + cgmath::Vector3::from(vertices[c[2] as usize].bitangent)).into();
+
+        triangles_included[c[0] as usize] += 1;
+        triangles_included[c[1] as usize] += 1;
+        triangles_included[c[2] as usize] += 1;
+    }
+
+    for (i, n) in triangles_included.into_iter().enumerate() {
+        let denom = 1.0/ n as f32;
+        let mut v = &mut vertices[i];
+        v.tangent = (cgmath::Vector3::from(v.tangent) * denom).into();
+        v.bitangent = (cgmath::Vector3::from(v.bitangent) * denom).into();
+    }
+}
+
+pub fn upload_mesh(device: &wgpu::Device, mesh: &MeshData) -> model::Mesh {
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(&format!("{:?} Vertex Buffer", mesh.name)),
+        contents: bytemuck::cast_slice(&mesh.vertices),
+        //live link writes new geometry into the same buffer, the validation view reads it back
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+    });
+
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(&format!("{:?} Index Buffer", mesh.name)),
+        contents: bytemuck::cast_slice(&mesh.indices),
+        usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+    });
+
+    model::Mesh {
+        name: mesh.name.clone(),
+        vertex_buffer,
+        index_buffer,
+        num_elements: mesh.indices.len() as u32,
+        material: mesh.material,
+        positions: mesh.vertices.iter().map(|v| v.position).collect(),
+        indices: mesh.indices.clone(),
+        overlay: None,
+    }
+}
+
+// every texture in texture_keys has to be in the cache already
+pub fn create_materials(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    material_names: &[String],
+    texture_keys: &[texture::TextureKey],
+    texture_cache: &texture::TextureCache,
+) -> Vec<model::Material> {
+    material_names
+        .iter()
+        .zip(texture_keys.chunks(2))
+        .map(|(name, keys)| model::Material::new(
+            device,
+            name,
+            texture_cache.get(&keys[0]).unwrap(),
+            texture_cache.get(&keys[1]).unwrap(),
+            layout,))
+        .collect::<Vec<_>>()
+}
+
+// model without meshes or materials yet, those get filled in while loading
+pub fn create_empty_model(device: &wgpu::Device, instance: u32) -> model::Model {
         const SPACE_BETWEEN: f32 = 3.0;
 
         let instances = (0..instance)
@@ -274,8 +455,8 @@ pub async fn load_model(
                 let i_f_end = i_f*10.0;
                 let pos = rand::random_range(i_f..=i_f_end);
                 let position = Vector3 { x: pos, y: pos, z: pos };
-                let rotation = Quaternion{ v: Vector3 { x: 0.0, y: 0.0, z: 0.0 }, s: 0.0};
-                model::Instance { position, rotation }
+                let rotation = Quaternion::one();
+                model::Instance { position, rotation, scale: Vector3::new(1.0, 1.0, 1.0) }
             })
             .collect::<Vec<_>>();
         let instance_data = instances.iter().map(model::Instance::to_raw).collect::<Vec<_>>();
@@ -290,14 +471,70 @@ pub async fn load_model(
     
     let instance_num = instance as i32;
 
-    Ok(model::Model {meshes, materials,instances, instance_buffer,instance_num })
+    model::Model {meshes: Vec::new(), materials: Vec::new(), instances, instance_buffer, instance_num, mesh_instances: Vec::new(), instance_data: Vec::new() }
+}
+
+pub async fn load_model(
+    file_name: &str, 
+    file_type: String,
+    device: &wgpu::Device, 
+    queue: &wgpu::Queue, 
+    layout: &wgpu::BindGroupLayout,
+    instance: u32,
+    spawn_position: Vector3<f32>,
+    texture_cache: &mut texture::TextureCache,
+) -> anyhow::Result<model::Model> {
+    let cache = asset_cache::AssetCache::from_env();
+    let cached = cache.as_ref().and_then(|cache| cache.load_model(file_name, &file_type));
+    let (obj, cached_meshes) = match cached {
+        Some((obj, meshes)) => (obj, Some(meshes)),
+        None => (parse_obj(file_name, &file_type).await?, None),
+    };
+
+    load_textures_into_cache(&obj.texture_keys, device, queue, texture_cache)?;
+
+    let mut model = create_empty_model(device, instance);
+    model.materials = create_materials(device, layout, &obj.material_names, &obj.texture_keys, texture_cache);
+
+    let mesh_start = Instant::now();
+    let meshes = match cached_meshes {
+        Some(meshes) => meshes,
+        None => {
+            let meshes = obj.models.iter().map(|m| build_mesh_data(&obj.file_name, m)).collect::<Vec<_>>();
+            if let Some(cache) = &cache {
+                cache.store_model(&obj, &file_type, &meshes);
+            }
+            meshes
+        }
+    };
+    model.meshes = meshes.iter().map(|mesh| upload_mesh(device, mesh)).collect::<Vec<_>>();
+    println!("mesh processing time {:?}", mesh_start.elapsed());
+
+    Ok(model)
 }
 
 pub async fn load_default_cube(
 device: &wgpu::Device, 
 queue: &wgpu::Queue, 
 layout: &wgpu::BindGroupLayout, 
+texture_cache: &mut texture::TextureCache,
 ) -> anyhow::Result<model::Model> {
-    let default_cube = load_model("default_cube.obj", "opengl".to_string(), device, queue, layout,1, Vector3 { x: 0.0, y: 0.0, z: 0.0 }).await?;
+    let default_cube = load_model("default_cube.obj", "opengl".to_string(), device, queue, layout,1, Vector3 { x: 0.0, y: 0.0, z: 0.0 }, texture_cache).await?;
     Ok(default_cube)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_options_are_stripped() {
+        assert_eq!(strip_texture_options("diffuse.png"), "diffuse.png");
+        assert_eq!(strip_texture_options("-bm 1.0 normal.png"), "normal.png");
+        assert_eq!(strip_texture_options("-o 0.5 0.5 -s 2 2 1 -clamp on albedo.png"), "albedo.png");
+        assert_eq!(strip_texture_options("-s 2 -imfchan r rough.png"), "rough.png");
+        assert_eq!(strip_texture_options("-mm 0 1 my diffuse texture.png"), "my diffuse texture.png");
+        assert_eq!(strip_texture_options("  wood planks 2.png "), "wood planks 2.png");
+        assert_eq!(strip_texture_options("-bm 0.3 C:\\textures\\old wood.png"), "C:\\textures\\old wood.png");
+    }
+}
@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, bail, Context};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame};
+use serde::Deserialize;
+use crate::headless::HeadlessRenderer;
+
+// one camera pose on a path, time in seconds
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Keyframe {
+    pub time: f32,
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+}
+
+pub enum CameraMotion {
+    // frames evenly spaced on a circle around the bounding box centre, elevation in degrees
+    Turntable { frames: u32, elevation: f32 },
+    // sampled at the sequence fps from the first keyframe to the last, linear in between
+    Path(Vec<Keyframe>),
+}
+
+pub struct SequenceOptions {
+    pub motion: CameraMotion,
+    pub out_dir: PathBuf,
+    pub fps: u32,
+    pub gif: bool,
+    pub apng: bool,
+}
+
+// a json array of keyframes, [{"time": 0.0, "position": [0, 1, 5], "target": [0, 0, 0]}, ...]
+pub fn load_camera_path(path: &Path) -> anyhow::Result<Vec<Keyframe>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("could not read {:?}", path))?;
+    let mut keyframes: Vec<Keyframe> = serde_json::from_str(&text).with_context(|| format!("could not parse {:?}", path))?;
+    if keyframes.is_empty() {
+        bail!("{:?} has no keyframes", path);
+    }
+    keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Ok(keyframes)
+}
+
+// writes frame_0000.png, frame_0001.png, ... into out_dir, plus sequence.gif / sequence.apng
+// when asked for. returns the frame paths
+pub fn render_sequence(renderer: &mut HeadlessRenderer, options: &SequenceOptions) -> anyhow::Result<Vec<PathBuf>> {
+    if options.fps == 0 {
+        bail!("fps must be at least 1");
+    }
+    let poses = camera_poses(renderer, &options.motion, options.fps)?;
+    std::fs::create_dir_all(&options.out_dir).with_context(|| format!("could not create {:?}", options.out_dir))?;
+
+    let (width, height) = renderer.size();
+    let mut gif = match options.gif {
+        true => {
+            let file = File::create(options.out_dir.join("sequence.gif"))?;
+            let mut encoder = GifEncoder::new(BufWriter::new(file));
+            encoder.set_repeat(Repeat::Infinite)?;
+            Some(encoder)
+        }
+        false => None,
+    };
+    let mut apng = match options.apng {
+        true => {
+            let file = File::create(options.out_dir.join("sequence.apng"))?;
+            let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            //0 plays forever
+            encoder.set_animated(poses.len() as u32, 0)?;
+            encoder.set_frame_delay(1, u16::try_from(options.fps).map_err(|_| anyhow!("fps is too high for apng"))?)?;
+            Some(encoder.write_header()?)
+        }
+        false => None,
+    };
+
+    let digits = poses.len().to_string().len().max(4);
+    let mut frames = Vec::with_capacity(poses.len());
+    for (index, (position, target)) in poses.iter().enumerate() {
+        renderer.look_at(*position, *target);
+        let image = renderer.render()?;
+
+        let path = options.out_dir.join(format!("frame_{:0width$}.png", index, width = digits));
+        image.save(&path).with_context(|| format!("could not save {:?}", path))?;
+        if let Some(apng) = &mut apng {
+            apng.write_image_data(&image)?;
+        }
+        if let Some(gif) = &mut gif {
+            gif.encode_frame(Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(1000, options.fps)))?;
+        }
+        println!("frame {}/{} : {:?}", index + 1, poses.len(), path);
+        frames.push(path);
+    }
+
+    if let Some(apng) = apng {
+        apng.finish()?;
+    }
+    Ok(frames)
+}
+
+// camera position and look at target for every frame
+fn camera_poses(renderer: &HeadlessRenderer, motion: &CameraMotion, fps: u32) -> anyhow::Result<Vec<([f32; 3], [f32; 3])>> {
+    match motion {
+        CameraMotion::Turntable { frames, elevation } => {
+            if *frames == 0 {
+                bail!("a turntable needs at least 1 frame");
+            }
+            (0..*frames)
+                .map(|frame| renderer.orbit_pose(frame as f32 / *frames as f32 * 360.0, *elevation))
+                .collect()
+        }
+        CameraMotion::Path(keyframes) => {
+            let (first, last) = match (keyframes.first(), keyframes.last()) {
+                (Some(first), Some(last)) => (first, last),
+                _ => bail!("the camera path has no keyframes"),
+            };
+            let frames = ((last.time - first.time) * fps as f32).round() as u32 + 1;
+            Ok((0..frames)
+                .map(|frame| sample_path(keyframes, first.time + frame as f32 / fps as f32))
+                .collect())
+        }
+    }
+}
+
+// keyframes are sorted by time, times outside the path hold the first or last pose
+fn sample_path(keyframes: &[Keyframe], time: f32) -> ([f32; 3], [f32; 3]) {
+    let next = keyframes.iter().position(|key| key.time > time).unwrap_or(keyframes.len());
+    if next == 0 {
+        return (keyframes[0].position, keyframes[0].target);
+    }
+    if next == keyframes.len() {
+        let last = &keyframes[keyframes.len() - 1];
+        return (last.position, last.target);
+    }
+    let (a, b) = (&keyframes[next - 1], &keyframes[next]);
+    let t = (time - a.time) / (b.time - a.time);
+    let lerp = |from: [f32; 3], to: [f32; 3]| [
+        from[0] + (to[0] - from[0]) * t,
+        from[1] + (to[1] - from[1]) * t,
+        from[2] + (to[2] - from[2]) * t,
+    ];
+    (lerp(a.position, b.position), lerp(a.target, b.target))
+}
@@ -158,10 +158,7 @@ fn sample_shadow_pcf(uv: vec2<f32>, depth: f32) -> f32 {
         return shadow;
 }
 
-@fragment
-
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    var object_color: vec3<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords).rgb;
+fn shade(in: VertexOutput, object_color: vec3<f32>) -> vec3<f32> {
     let object_normal: vec3<f32> = textureSample(t_normal, s_normal, in.tex_coords).xyz;
     let light_distance = length(light.position - in.world_position);
 
@@ -251,10 +248,34 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         result += newresult;
 
     }
-    let aces_out = aces_tone_map(result);
+    return result;
+}
+
+@fragment
+
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var object_color: vec3<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords).rgb;
+    let aces_out = aces_tone_map(shade(in, object_color));
     return vec4<f32>(aces_out,1.0);
 }
 
+//white albedo leaves only the light reaching the surface, used by the lighting debug view
+@fragment
+fn fs_lighting(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(aces_tone_map(shade(in, vec3<f32>(1.0))), 1.0);
+}
+
+//which shadow cascade covers the pixel, there is a single shadow map so far which counts as cascade 0
+@fragment
+fn fs_shadow_cascade(in: VertexOutput) -> @location(0) vec4<f32> {
+    let inside = all(in.shadow_coord.xy >= vec2<f32>(0.0)) && all(in.shadow_coord.xy <= vec2<f32>(1.0))
+        && in.shadow_coord.z >= 0.0 && in.shadow_coord.z <= 1.0;
+    if inside {
+        return vec4<f32>(0.9, 0.2, 0.2, 1.0);
+    }
+    return vec4<f32>(0.15, 0.15, 0.15, 1.0);
+}
+
 fn aces_tone_map(color: vec3<f32>) -> vec3<f32> {
     let a = 2.51;
     let b = 0.03;
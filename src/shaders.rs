@@ -0,0 +1,57 @@
+use std::path::{Path, PathBuf};
+
+// wgsl sources are embedded in the binary, with KANIRENDERER_DEV set they are
+// read from the source tree instead so edits get picked up by hot reload
+pub const SHADER_FILES: [&str; 13] = [
+    "shader.wgsl",
+    "shader_hdr.wgsl",
+    "unlit_shader.wgsl",
+    "lit_shader.wgsl",
+    "shader_wireframe.wgsl",
+    "shadow.wgsl",
+    "debug_depth.wgsl",
+    "frametime.wgsl",
+    "loading.wgsl",
+    "validation.wgsl",
+    "vectors.wgsl",
+    "debug_shading.wgsl",
+    "debug_view.wgsl",
+];
+
+pub fn dev_mode() -> bool {
+    std::env::var_os("KANIRENDERER_DEV").is_some()
+}
+
+pub fn shader_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src")
+}
+
+fn embedded_shader(name: &str) -> &'static str {
+    match name {
+        "shader.wgsl" => include_str!("shader.wgsl"),
+        "shader_hdr.wgsl" => include_str!("shader_hdr.wgsl"),
+        "unlit_shader.wgsl" => include_str!("unlit_shader.wgsl"),
+        "lit_shader.wgsl" => include_str!("lit_shader.wgsl"),
+        "shader_wireframe.wgsl" => include_str!("shader_wireframe.wgsl"),
+        "shadow.wgsl" => include_str!("shadow.wgsl"),
+        "debug_depth.wgsl" => include_str!("debug_depth.wgsl"),
+        "frametime.wgsl" => include_str!("frametime.wgsl"),
+        "loading.wgsl" => include_str!("loading.wgsl"),
+        "validation.wgsl" => include_str!("validation.wgsl"),
+        "vectors.wgsl" => include_str!("vectors.wgsl"),
+        "debug_shading.wgsl" => include_str!("debug_shading.wgsl"),
+        "debug_view.wgsl" => include_str!("debug_view.wgsl"),
+        _ => panic!("unknown shader {}", name),
+    }
+}
+
+pub fn load_shader(name: &str) -> String {
+    if dev_mode() {
+        let path = shader_dir().join(name);
+        match std::fs::read_to_string(&path) {
+            Ok(source) => return source,
+            Err(err) => println!("could not read {:?} ({}), using embedded shader", path, err),
+        }
+    }
+    embedded_shader(name).to_string()
+}
@@ -1,6 +1,10 @@
 use image::{DynamicImage, GenericImageView};
 use anyhow::*;
 use std::result::Result::Ok;
+use std::{collections::{HashMap, HashSet}, path::PathBuf, sync::Arc};
+use crate::compressed_texture::{self, CompressedImage};
+use crate::asset_cache::CachedTexture;
+use crate::basis::BasisImage;
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
@@ -57,6 +61,225 @@ fn invert_green_channel(image: &mut DynamicImage) {
     }
 }
 
+// gpu format a regular image gets uploaded as, color is srgb and normal maps are linear
+pub fn image_format(img: &DynamicImage, is_normal_map: bool) -> wgpu::TextureFormat {
+    color_format(img.color(), is_normal_map)
+}
+
+pub fn color_format(color: image::ColorType, is_normal_map: bool) -> wgpu::TextureFormat {
+    if is_normal_map{
+        match color{
+            image::ColorType::L8 => {wgpu::TextureFormat::Rgba8Unorm},
+            image::ColorType::La8 => {wgpu::TextureFormat::Rgba8Unorm},
+            image::ColorType::Rgb8=> {wgpu::TextureFormat::Rgba8Unorm},
+            image::ColorType::Rgba8=> {wgpu::TextureFormat::Rgba8Unorm},
+            image::ColorType::L16=> {wgpu::TextureFormat::Rgba16Unorm},
+            image::ColorType::La16=> {wgpu::TextureFormat::Rgba16Unorm},
+            image::ColorType::Rgb16=> {wgpu::TextureFormat::Rgba16Unorm},
+            image::ColorType::Rgba16=> {wgpu::TextureFormat::Rgba16Unorm},
+            image::ColorType::Rgb32F=> {wgpu::TextureFormat::Rgba32Float},
+            image::ColorType::Rgba32F=> {wgpu::TextureFormat::Rgba32Float},
+            _ => {wgpu::TextureFormat::Rgba8Unorm},
+        }
+    } else {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    }
+}
+
+// every mip level down to 1x1, each pixel the average of the 2x2 pixels above it
+// (edge pixels repeat for odd sizes). levels[0] is the image itself
+pub fn mip_chain(rgba: &image::RgbaImage) -> Vec<Vec<u8>> {
+    let mut levels = vec![rgba.as_raw().clone()];
+    let (mut width, mut height) = rgba.dimensions();
+    while width > 1 || height > 1 {
+        let previous = levels.last().unwrap();
+        let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+        let mut next = Vec::with_capacity((next_width * next_height * 4) as usize);
+        for y in 0..next_height {
+            for x in 0..next_width {
+                let rows = [(y * 2).min(height - 1), (y * 2 + 1).min(height - 1)];
+                let columns = [(x * 2).min(width - 1), (x * 2 + 1).min(width - 1)];
+                for channel in 0..4 {
+                    let mut sum = 0;
+                    for row in rows {
+                        for column in columns {
+                            sum += previous[((row * width + column) * 4 + channel) as usize] as u32;
+                        }
+                    }
+                    next.push(((sum + 2) / 4) as u8);
+                }
+            }
+        }
+        levels.push(next);
+        (width, height) = (next_width, next_height);
+    }
+    levels
+}
+
+pub enum ImageData {
+    Image(DynamicImage),
+    Compressed(CompressedImage),
+    Cached(CachedTexture),
+    //basis universal ktx2, transcoded once the adapter's features are known
+    Basis(BasisImage),
+}
+
+// decoded on a worker thread, uploaded to the gpu later
+pub struct DecodedImage {
+    pub label: String,
+    pub data: ImageData,
+    pub is_normal_map: bool,
+}
+
+impl DecodedImage {
+    pub fn from_bytes(bytes: &[u8], label: &str, is_normal_map: bool) -> Result<Self> {
+        if compressed_texture::is_compressed_container(bytes) {
+            match BasisImage::from_bytes(bytes) {
+                Ok(Some(basis)) => return Ok(Self { label: label.to_string(), data: ImageData::Basis(basis), is_normal_map }),
+                Ok(None) => {}
+                Err(err) => {eprintln!("{}: {:?}", label, err); return Err(err);}
+            }
+            let mut compressed = match CompressedImage::from_bytes(bytes) {
+                Ok(c) => c,
+                Err(err) => {eprintln!("{}: {:?}", label, err); return Err(err);}
+            };
+            compressed.format = color_space(compressed.format, is_normal_map);
+            return Ok(Self { label: label.to_string(), data: ImageData::Compressed(compressed), is_normal_map });
+        }
+
+        let img_res = image::load_from_memory(bytes);
+        let img;
+        match img_res {
+            Ok(i) => {img = i;}
+            Err(err) => {eprintln!("{:?}",err); return Err(err.into());}
+        }
+        Ok(Self { label: label.to_string(), data: ImageData::Image(img), is_normal_map })
+    }
+
+    // compressed textures are expected to be authored for wgpu already,
+    // so the opengl flip and green channel fix only apply to regular images
+    pub fn from_opengl_bytes(bytes: &[u8], label: &str, is_normal_map: bool) -> Result<Self> {
+        let mut decoded = Self::from_bytes(bytes, label, is_normal_map)?;
+        if let ImageData::Image(image) = &mut decoded.data {
+            *image = image.flipv();
+            if is_normal_map{
+                invert_green_channel(image);
+            }
+        }
+        Ok(decoded)
+    }
+
+    // decompress block compressed data the adapter can't sample, transcode basis textures
+    pub fn fit_to_features(self, features: wgpu::Features) -> Result<Self> {
+        match &self.data {
+            ImageData::Basis(basis) => {
+                let transcoded = transcode_basis(basis, features, self.is_normal_map)?;
+                println!("{}: basis {:?} transcoded to {:?}", self.label, basis.codec, transcoded.format);
+                Ok(Self { data: ImageData::Compressed(transcoded), ..self })
+            }
+            ImageData::Compressed(compressed) if !compressed.is_supported(features) => {
+                println!("{}: {:?} not supported by the adapter, decoding on the cpu", self.label, compressed.format);
+                let decompressed = compressed.decompress()?;
+                Ok(Self { data: ImageData::Compressed(decompressed), ..self })
+            }
+            ImageData::Cached(cached) if !compressed_texture::is_format_supported(cached.format, cached.width, cached.height, features) => {
+                println!("{}: {:?} not supported by the adapter, decoding on the cpu", self.label, cached.format);
+                let decompressed = cached.to_compressed().decompress()?;
+                Ok(Self { data: ImageData::Compressed(decompressed), ..self })
+            }
+            _ => Ok(self),
+        }
+    }
+}
+
+// same rule as for png/jpg, color is srgb and normal maps are linear
+fn color_space(format: wgpu::TextureFormat, is_normal_map: bool) -> wgpu::TextureFormat {
+    if is_normal_map {
+        format.remove_srgb_suffix()
+    } else {
+        format.add_srgb_suffix()
+    }
+}
+
+fn transcode_basis(basis: &BasisImage, features: wgpu::Features, is_normal_map: bool) -> Result<CompressedImage> {
+    let mut transcoded = basis.transcode(features)?;
+    transcoded.format = color_space(transcoded.format, is_normal_map);
+    Ok(transcoded)
+}
+
+// used as cache path for the built in default_normal.png fallback
+pub const FALLBACK_TEXTURE_PATH: &str = "<default_normal>";
+
+// same file can be uploaded as srgb color or as linear normal map,
+// and flipped for opengl meshes, so those are part of the key
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureKey {
+    pub path: PathBuf,
+    pub is_normal_map: bool,
+    pub opengl: bool,
+}
+
+impl TextureKey {
+    pub fn fallback(is_normal_map: bool, opengl: bool) -> Self {
+        Self {
+            path: PathBuf::from(FALLBACK_TEXTURE_PATH),
+            is_normal_map,
+            opengl,
+        }
+    }
+
+    pub fn is_fallback(&self) -> bool {
+        self.path == PathBuf::from(FALLBACK_TEXTURE_PATH)
+    }
+}
+
+// shares uploaded textures between materials and between model loads
+#[derive(Default)]
+pub struct TextureCache {
+    textures: HashMap<TextureKey, Arc<Texture>>,
+}
+
+impl TextureCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &TextureKey) -> Option<Arc<Texture>> {
+        self.textures.get(key).cloned()
+    }
+
+    pub fn keys(&self) -> HashSet<TextureKey> {
+        self.textures.keys().cloned().collect()
+    }
+
+    pub fn contains(&self, key: &TextureKey) -> bool {
+        self.textures.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: TextureKey, texture: Texture) -> Arc<Texture> {
+        let texture = Arc::new(texture);
+        self.textures.insert(key, texture.clone());
+        texture
+    }
+
+    pub fn remove(&mut self, key: &TextureKey) -> Option<Arc<Texture>> {
+        self.textures.remove(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    // drop textures no material references anymore
+    pub fn remove_unused(&mut self) {
+        self.textures.retain(|_, texture| Arc::strong_count(texture) > 1);
+    }
+}
+
 impl Texture {
     pub fn from_bytes(
         device: &wgpu::Device,
@@ -65,13 +288,8 @@ impl Texture {
         label:&str,
         is_normal_map: bool,
     ) -> Result<Self> {
-        let img_res = image::load_from_memory(bytes);
-        let mut img;
-        match img_res {
-            Ok(i) => {img = i;}
-            Err(err) => {println!("{:?}",err); return Err(err.into());}
-        }
-        Self::from_image(device, queue, &img, Some(label), is_normal_map)
+        let decoded = DecodedImage::from_bytes(bytes, label, is_normal_map)?;
+        Self::from_decoded(device, queue, &decoded)
     }
 
     pub fn from_opengl_bytes(
@@ -81,17 +299,105 @@ impl Texture {
         label:&str,
         is_normal_map: bool,
     ) -> Result<Self> {
-        let img_res = image::load_from_memory(bytes);
-        let mut img;
-        match img_res {
-            Ok(i) => {img = i;}
-            Err(err) => {println!("{:?}",err); return Err(err.into());}
+        let decoded = DecodedImage::from_opengl_bytes(bytes, label, is_normal_map)?;
+        Self::from_decoded(device, queue, &decoded)
+    }
+
+    pub fn from_decoded(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        decoded: &DecodedImage,
+    ) -> Result<Self> {
+        match &decoded.data {
+            ImageData::Image(image) => Self::from_image(device, queue, image, Some(&decoded.label), decoded.is_normal_map),
+            ImageData::Compressed(compressed) => Self::from_compressed(device, queue, compressed, Some(&decoded.label)),
+            ImageData::Cached(cached) => Self::from_levels(device, queue, cached.format, cached.width, cached.height, &cached.levels(), Some(&decoded.label)),
+            ImageData::Basis(basis) => {
+                let transcoded = transcode_basis(basis, device.features(), decoded.is_normal_map)?;
+                Self::from_compressed(device, queue, &transcoded, Some(&decoded.label))
+            }
         }
-        let mut flippedimg = img.flipv();
-        if is_normal_map{
-            invert_green_channel(&mut flippedimg);
+    }
+
+    // upload every mip level stored in the file as is
+    pub fn from_compressed(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        compressed: &CompressedImage,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let levels = compressed.levels.iter().map(|level| level.as_slice()).collect::<Vec<_>>();
+        Self::from_levels(device, queue, compressed.format, compressed.width, compressed.height, &levels, label)
+    }
+
+    // levels[0] is the full size image, each level is tightly packed rows of pixels or blocks
+    pub fn from_levels(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        levels: &[&[u8]],
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let mip_extent = |level: u32| wgpu::Extent3d {
+            width: (width >> level).max(1),
+            height: (height >> level).max(1),
+            depth_or_array_layers: 1,
+        };
+        let size = mip_extent(0);
+
+        let texture = device.create_texture(
+            &wgpu::TextureDescriptor {
+                label,
+                size,
+                mip_level_count: levels.len() as u32,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            }
+        );
+
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format.block_size(None).unwrap_or(4);
+        for (level, data) in levels.iter().enumerate() {
+            let level_size = mip_extent(level as u32);
+            let physical_size = level_size.physical_size(format);
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(physical_size.width / block_width * block_size),
+                    rows_per_image: Some(physical_size.height / block_height),
+                },
+                physical_size,
+            );
         }
-        Self::from_image(device, queue, &flippedimg, Some(label), is_normal_map)
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::Repeat,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                border_color: None,
+                ..Default::default()
+            }
+        );
+
+        Ok(Self { texture, view, sampler })
     }
 
     pub fn from_image(
@@ -110,29 +416,14 @@ impl Texture {
             depth_or_array_layers: 1,
         };
 
-        let format = if is_normal_map{
-            match img.color(){
-                image::ColorType::L8 => {wgpu::TextureFormat::Rgba8Unorm},
-                image::ColorType::La8 => {wgpu::TextureFormat::Rgba8Unorm},
-                image::ColorType::Rgb8=> {wgpu::TextureFormat::Rgba8Unorm},
-                image::ColorType::Rgba8=> {wgpu::TextureFormat::Rgba8Unorm},
-                image::ColorType::L16=> {wgpu::TextureFormat::Rgba16Unorm},
-                image::ColorType::La16=> {wgpu::TextureFormat::Rgba16Unorm},
-                image::ColorType::Rgb16=> {wgpu::TextureFormat::Rgba16Unorm},
-                image::ColorType::Rgba16=> {wgpu::TextureFormat::Rgba16Unorm},
-                image::ColorType::Rgb32F=> {wgpu::TextureFormat::Rgba32Float},
-                image::ColorType::Rgba32F=> {wgpu::TextureFormat::Rgba32Float},
-                _ => {wgpu::TextureFormat::Rgba8Unorm},
-                            }
-        } else {
-            wgpu::TextureFormat::Rgba8UnormSrgb
-        };
+        let format = image_format(img, is_normal_map);
+        let levels = mip_chain(&rgba);
 
         let texture = device.create_texture(
             &wgpu::TextureDescriptor {
                 label,
                 size,
-                mip_level_count: 1,
+                mip_level_count: levels.len() as u32,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format: format,
@@ -141,21 +432,28 @@ impl Texture {
             }
         );
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                aspect: wgpu::TextureAspect::All,
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            &rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            size,
-        );
+        for (level, data) in levels.iter().enumerate() {
+            let level_size = wgpu::Extent3d {
+                width: (dimensions.0 >> level).max(1),
+                height: (dimensions.1 >> level).max(1),
+                depth_or_array_layers: 1,
+            };
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * level_size.width),
+                    rows_per_image: Some(level_size.height),
+                },
+                level_size,
+            );
+        }
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
@@ -0,0 +1,163 @@
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+use crate::headless::HeadlessRenderer;
+
+// three-quarter view from the front right and a bit above, lit from the front left
+const CAMERA_AZIMUTH: f32 = 45.0;
+const CAMERA_ELEVATION: f32 = 25.0;
+const LIGHT_DIRECTION: [f32; 3] = [0.35, -1.0, -0.7];
+
+pub struct ThumbnailOptions {
+    pub out_dir: PathBuf,
+    pub size: u32,
+    pub file_type: String,
+    pub mode: Option<String>,
+}
+
+pub struct ThumbnailFailure {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+#[derive(Default)]
+pub struct ThumbnailReport {
+    pub written: Vec<PathBuf>,
+    pub failures: Vec<ThumbnailFailure>,
+}
+
+// every .obj below dir, sorted so runs are reproducible, and the folders and entries below dir that
+// couldn't be read. only an unreadable dir itself is an error. symlinked folders aren't followed so a
+// link back up the tree can't loop forever
+pub fn find_models(dir: &Path) -> anyhow::Result<(Vec<PathBuf>, Vec<ThumbnailFailure>)> {
+    let mut models = Vec::new();
+    let mut failures = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = match std::fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(err) if current == dir => return Err(err).with_context(|| format!("could not read {:?}", dir)),
+            Err(err) => {
+                failures.push(ThumbnailFailure { path: current, error: format!("could not read the folder: {}", err) });
+                continue;
+            }
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    failures.push(ThumbnailFailure { path: current.clone(), error: format!("could not read an entry: {}", err) });
+                    continue;
+                }
+            };
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => pending.push(path),
+                Ok(_) if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("obj")) => models.push(path),
+                Ok(_) => {}
+                Err(err) => failures.push(ThumbnailFailure { path, error: format!("could not read the file type: {}", err) }),
+            }
+        }
+    }
+    models.sort();
+    failures.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((models, failures))
+}
+
+// one size x size png per model in out_dir, mirroring the folders below dir.
+// a model that fails is recorded and skipped, the device is reused for all of them
+pub fn render_thumbnails(dir: &Path, options: &ThumbnailOptions) -> anyhow::Result<ThumbnailReport> {
+    let (models, failures) = find_models(dir)?;
+    let mut renderer = HeadlessRenderer::new(&options.file_type, options.size, options.size)?;
+    if let Some(mode) = &options.mode {
+        renderer.set_mode(mode)?;
+    }
+    renderer.set_light_direction(LIGHT_DIRECTION);
+
+    for failure in &failures {
+        eprintln!("{:?} skipped : {}", failure.path, failure.error);
+    }
+    let mut report = ThumbnailReport { failures, ..Default::default() };
+    for (index, model) in models.iter().enumerate() {
+        let relative = model.strip_prefix(dir).unwrap_or(model);
+        let path = options.out_dir.join(relative).with_extension("png");
+        //a panic inside the loader or wgpu only costs this file
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| render_thumbnail(&mut renderer, model, &path)))
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("panicked while rendering")));
+        match result {
+            Ok(()) => {
+                println!("[{}/{}] {:?}", index + 1, models.len(), path);
+                report.written.push(path);
+            }
+            Err(err) => {
+                eprintln!("[{}/{}] {:?} failed : {:#}", index + 1, models.len(), model, err);
+                report.failures.push(ThumbnailFailure {
+                    path: model.clone(),
+                    error: format!("{:#}", err),
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn render_thumbnail(renderer: &mut HeadlessRenderer, model: &Path, path: &Path) -> anyhow::Result<()> {
+    renderer.load_model(model)?;
+    let (position, target) = renderer.orbit_pose(CAMERA_AZIMUTH, CAMERA_ELEVATION)?;
+    renderer.look_at(position, target);
+    let image = renderer.render()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("could not create {:?}", parent))?;
+    }
+    image.save(path).with_context(|| format!("could not save {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_are_not_followed() {
+        let dir = std::env::temp_dir().join("kanirenderer_find_models");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("props")).unwrap();
+        std::fs::write(dir.join("cube.obj"), "").unwrap();
+        std::fs::write(dir.join("props").join("crate.OBJ"), "").unwrap();
+        std::fs::write(dir.join("props").join("notes.txt"), "").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("props").join("loop")).unwrap();
+        let (models, failures) = find_models(&dir).unwrap();
+        assert_eq!(models, vec![dir.join("cube.obj"), dir.join("props").join("crate.OBJ")]);
+        assert!(failures.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_folders_are_reported_and_skipped() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join("kanirenderer_find_models_unreadable");
+        let locked = dir.join("locked");
+        if locked.exists() {
+            std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&locked).unwrap();
+        std::fs::create_dir_all(dir.join("open")).unwrap();
+        std::fs::write(dir.join("cube.obj"), "").unwrap();
+        std::fs::write(locked.join("hidden.obj"), "").unwrap();
+        std::fs::write(dir.join("open").join("crate.obj"), "").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        //root reads it anyway, there's nothing to check then
+        let readable = std::fs::read_dir(&locked).is_ok();
+        let found = find_models(&dir);
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        if readable {
+            return;
+        }
+        let (models, failures) = found.unwrap();
+        assert_eq!(models, vec![dir.join("cube.obj"), dir.join("open").join("crate.obj")]);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].path, locked);
+        assert!(find_models(&dir.join("missing")).is_err());
+    }
+}
@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc;
+use cgmath::{InnerSpace, Vector2, Vector3};
+use rayon::prelude::*;
+use serde::Serialize;
+use wgpu::util::DeviceExt;
+use crate::{model, resources, shaders, State};
+use crate::model::Vertex;
+
+// triangles thinner than this (twice the area over the longest edge squared) count as degenerate
+const DEGENERATE_RATIO: f32 = 1e-6;
+// uv triangles have to overlap by more than this to count, touching edges don't
+const UV_OVERLAP_EPSILON: f32 = 1e-6;
+// stacked or mirrored uv layouts can put thousands of triangles in one spot, stop testing pairs after this many
+const UV_PAIR_BUDGET: usize = 20_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Issue {
+    Degenerate,
+    FlippedNormals,
+    ZeroLengthNormal,
+    OverlappingUv,
+    BoundaryEdge,
+    NonManifoldEdge,
+    InconsistentWinding,
+}
+
+impl Issue {
+    pub fn color(&self) -> [f32; 4] {
+        match self {
+            Issue::Degenerate => [1.0, 0.0, 1.0, 1.0],
+            Issue::FlippedNormals => [1.0, 0.45, 0.0, 0.6],
+            Issue::ZeroLengthNormal => [0.0, 1.0, 1.0, 0.6],
+            Issue::OverlappingUv => [1.0, 1.0, 0.0, 0.35],
+            Issue::BoundaryEdge => [0.1, 1.0, 0.1, 1.0],
+            Issue::NonManifoldEdge => [1.0, 0.0, 0.0, 1.0],
+            Issue::InconsistentWinding => [0.2, 0.4, 1.0, 1.0],
+        }
+    }
+}
+
+// mesh space geometry to draw in the validation render mode
+#[derive(Debug, Clone)]
+pub enum Highlight {
+    Triangle([[f32; 3]; 3], Issue),
+    Edge([[f32; 3]; 2], Issue),
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MeshReport {
+    pub name: String,
+    pub triangle_count: usize,
+    pub out_of_range_indices: usize,
+    pub nan_positions: usize,
+    pub degenerate_triangles: usize,
+    pub boundary_edges: usize,
+    pub non_manifold_edges: usize,
+    pub inconsistent_winding_edges: usize,
+    pub zero_length_normals: usize,
+    pub flipped_normal_triangles: usize,
+    pub overlapping_uv_triangles: usize,
+    //false when the uv overlap test ran out of budget, the count is a lower bound then
+    pub uv_overlap_check_complete: bool,
+    #[serde(skip)]
+    pub highlights: Vec<Highlight>,
+}
+
+impl MeshReport {
+    // things that render wrong, boundary edges and overlapping uvs are often on purpose
+    pub fn error_count(&self) -> usize {
+        self.out_of_range_indices
+            + self.nan_positions
+            + self.degenerate_triangles
+            + self.non_manifold_edges
+            + self.inconsistent_winding_edges
+            + self.zero_length_normals
+            + self.flipped_normal_triangles
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.boundary_edges + self.overlapping_uv_triangles
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    pub file_name: String,
+    pub error_count: usize,
+    pub warning_count: usize,
+    pub meshes: Vec<MeshReport>,
+}
+
+impl ValidationReport {
+    pub fn new(file_name: &str, meshes: Vec<MeshReport>) -> Self {
+        Self {
+            file_name: file_name.to_string(),
+            error_count: meshes.iter().map(|mesh| mesh.error_count()).sum(),
+            warning_count: meshes.iter().map(|mesh| mesh.warning_count()).sum(),
+            meshes,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "validation of {}: {} errors, {} warnings in {} meshes", self.file_name, self.error_count, self.warning_count, self.meshes.len())?;
+        for mesh in self.meshes.iter().filter(|mesh| mesh.error_count() + mesh.warning_count() > 0) {
+            writeln!(f, "  {} ({} triangles)", mesh.name, mesh.triangle_count)?;
+            let counts = [
+                ("triangles with out of range indices", mesh.out_of_range_indices),
+                ("nan positions", mesh.nan_positions),
+                ("degenerate triangles", mesh.degenerate_triangles),
+                ("non-manifold edges", mesh.non_manifold_edges),
+                ("inconsistent winding edges", mesh.inconsistent_winding_edges),
+                ("zero length normals", mesh.zero_length_normals),
+                ("flipped normal triangles", mesh.flipped_normal_triangles),
+                ("boundary edges (warning)", mesh.boundary_edges),
+                ("overlapping uv triangles (warning)", mesh.overlapping_uv_triangles),
+            ];
+            for (label, count) in counts.iter().filter(|(_, count)| *count > 0) {
+                writeln!(f, "    {}: {}", label, count)?;
+            }
+            if !mesh.uv_overlap_check_complete {
+                writeln!(f, "    uv overlap check stopped early, too many stacked uv triangles")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// parse an obj and validate every mesh the same way the viewer builds them
+pub fn validate_model(file_name: &str, file_type: &str) -> anyhow::Result<ValidationReport> {
+    let obj = pollster::block_on(resources::parse_obj(file_name, file_type))?;
+    let meshes = obj.models
+        .par_iter()
+        .map(|m| {
+            let mesh = resources::build_mesh_data(&obj.file_name, m);
+            validate_mesh(&mesh.name, &mesh.vertices, &mesh.indices)
+        })
+        .collect::<Vec<_>>();
+    Ok(ValidationReport::new(&obj.file_name, meshes))
+}
+
+#[derive(Default, Clone, Copy)]
+struct EdgeUse {
+    count: u32,
+    //how many triangles walk the edge from the lower to the higher vertex id
+    forward: u32,
+}
+
+pub fn validate_mesh(name: &str, vertices: &[model::ModelVertex], indices: &[u32]) -> MeshReport {
+    let mut report = MeshReport {
+        name: name.to_string(),
+        triangle_count: indices.len() / 3,
+        uv_overlap_check_complete: true,
+        ..Default::default()
+    };
+
+    let finite = |v: &[f32]| v.iter().all(|x| x.is_finite());
+    report.nan_positions = vertices.iter().filter(|v| !finite(&v.position)).count();
+    let bad_normal = vertices
+        .iter()
+        .map(|v| !finite(&v.normal) || Vector3::from(v.normal).magnitude2() < 1e-12)
+        .collect::<Vec<_>>();
+    report.zero_length_normals = bad_normal.iter().filter(|bad| **bad).count();
+
+    //vertices get split at uv and normal seams, weld by position for the topology checks
+    let mut welded_ids: HashMap<[u32; 3], u32> = HashMap::new();
+    let welded = vertices
+        .iter()
+        .map(|v| {
+            let key = v.position.map(|x| if x == 0.0 { 0 } else { x.to_bits() });
+            let next_id = welded_ids.len() as u32;
+            *welded_ids.entry(key).or_insert(next_id)
+        })
+        .collect::<Vec<_>>();
+
+    let mut edges: HashMap<(u32, u32), EdgeUse> = HashMap::new();
+    let mut edge_positions: HashMap<(u32, u32), [[f32; 3]; 2]> = HashMap::new();
+    let mut uv_candidates = Vec::new();
+
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        if corners.iter().any(|&i| i as usize >= vertices.len()) {
+            report.out_of_range_indices += 1;
+            continue;
+        }
+        let v = [vertices[corners[0] as usize], vertices[corners[1] as usize], vertices[corners[2] as usize]];
+        if !v.iter().all(|v| finite(&v.position)) {
+            continue;
+        }
+        let p = v.map(|v| Vector3::from(v.position));
+        let face = (p[1] - p[0]).cross(p[2] - p[0]);
+        let longest = (p[1] - p[0]).magnitude2().max((p[2] - p[1]).magnitude2()).max((p[0] - p[2]).magnitude2());
+        let w = [welded[corners[0] as usize], welded[corners[1] as usize], welded[corners[2] as usize]];
+        if w[0] == w[1] || w[1] == w[2] || w[2] == w[0] || face.magnitude() <= DEGENERATE_RATIO * longest {
+            report.degenerate_triangles += 1;
+            for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+                report.highlights.push(Highlight::Edge([v[a].position, v[b].position], Issue::Degenerate));
+            }
+            continue;
+        }
+
+        let positions = v.map(|v| v.position);
+        if corners.iter().any(|&i| bad_normal[i as usize]) {
+            report.highlights.push(Highlight::Triangle(positions, Issue::ZeroLengthNormal));
+        } else {
+            let normal_sum = v.iter().map(|v| Vector3::from(v.normal).normalize()).fold(Vector3::new(0.0, 0.0, 0.0), |a, b| a + b);
+            if face.dot(normal_sum) < 0.0 {
+                report.flipped_normal_triangles += 1;
+                report.highlights.push(Highlight::Triangle(positions, Issue::FlippedNormals));
+            }
+        }
+
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            let key = (w[a].min(w[b]), w[a].max(w[b]));
+            let edge = edges.entry(key).or_default();
+            edge.count += 1;
+            if w[a] < w[b] {
+                edge.forward += 1;
+            }
+            edge_positions.entry(key).or_insert([positions[a], positions[b]]);
+        }
+
+        if v.iter().all(|v| finite(&v.tex_coords)) {
+            uv_candidates.push(triangle as u32);
+        }
+    }
+
+    for (key, edge) in &edges {
+        let issue = match edge.count {
+            1 => Issue::BoundaryEdge,
+            2 if edge.forward != 1 => Issue::InconsistentWinding,
+            2 => continue,
+            _ => Issue::NonManifoldEdge,
+        };
+        match issue {
+            Issue::BoundaryEdge => report.boundary_edges += 1,
+            Issue::InconsistentWinding => report.inconsistent_winding_edges += 1,
+            _ => report.non_manifold_edges += 1,
+        }
+        report.highlights.push(Highlight::Edge(edge_positions[key], issue));
+    }
+
+    let (overlapping, complete) = overlapping_uv_triangles(vertices, indices, &uv_candidates);
+    report.overlapping_uv_triangles = overlapping.len();
+    report.uv_overlap_check_complete = complete;
+    for triangle in overlapping {
+        let corners = &indices[triangle as usize * 3..triangle as usize * 3 + 3];
+        let positions = [0, 1, 2].map(|i| vertices[corners[i] as usize].position);
+        report.highlights.push(Highlight::Triangle(positions, Issue::OverlappingUv));
+    }
+
+    report
+}
+
+// broad phase on a grid over the uv bounds, then a separating axis test per pair.
+// each pair is only tested in the first grid cell both triangles cover
+fn overlapping_uv_triangles(vertices: &[model::ModelVertex], indices: &[u32], candidates: &[u32]) -> (Vec<u32>, bool) {
+    let uv_triangle = |triangle: u32| {
+        let corners = &indices[triangle as usize * 3..triangle as usize * 3 + 3];
+        [0, 1, 2].map(|i| Vector2::from(vertices[corners[i] as usize].tex_coords))
+    };
+    let triangles = candidates
+        .iter()
+        .map(|&triangle| (triangle, uv_triangle(triangle)))
+        .filter(|(_, uv)| (uv[1] - uv[0]).perp_dot(uv[2] - uv[0]).abs() > UV_OVERLAP_EPSILON * UV_OVERLAP_EPSILON)
+        .collect::<Vec<_>>();
+    if triangles.len() < 2 {
+        return (Vec::new(), true);
+    }
+
+    let mut min = Vector2::new(f32::MAX, f32::MAX);
+    let mut max = Vector2::new(f32::MIN, f32::MIN);
+    for (_, uv) in &triangles {
+        for p in uv {
+            min = Vector2::new(min.x.min(p.x), min.y.min(p.y));
+            max = Vector2::new(max.x.max(p.x), max.y.max(p.y));
+        }
+    }
+    let grid_size = ((triangles.len() as f32).sqrt().ceil() as u32).clamp(1, 1024);
+    let cell = |p: f32, low: f32, high: f32| {
+        let t = if high > low { (p - low) / (high - low) } else { 0.0 };
+        ((t * grid_size as f32) as u32).min(grid_size - 1)
+    };
+    let cell_range = |uv: &[Vector2<f32>; 3]| {
+        let (x0, x1) = (uv[0].x.min(uv[1].x).min(uv[2].x), uv[0].x.max(uv[1].x).max(uv[2].x));
+        let (y0, y1) = (uv[0].y.min(uv[1].y).min(uv[2].y), uv[0].y.max(uv[1].y).max(uv[2].y));
+        (cell(x0, min.x, max.x), cell(x1, min.x, max.x), cell(y0, min.y, max.y), cell(y1, min.y, max.y))
+    };
+
+    let ranges = triangles.iter().map(|(_, uv)| cell_range(uv)).collect::<Vec<_>>();
+    let mut cells = Vec::new();
+    for (i, &(x0, x1, y0, y1)) in ranges.iter().enumerate() {
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                cells.push((y * grid_size + x, i as u32));
+            }
+        }
+    }
+    cells.sort_unstable();
+
+    let mut overlapping = vec![false; triangles.len()];
+    let mut tests = 0;
+    let mut complete = true;
+    'cells: for group in cells.chunk_by(|a, b| a.0 == b.0) {
+        let cell_index = group[0].0;
+        for (n, &(_, a)) in group.iter().enumerate() {
+            for &(_, b) in &group[n + 1..] {
+                let (ra, rb) = (ranges[a as usize], ranges[b as usize]);
+                if ra.2.max(rb.2) * grid_size + ra.0.max(rb.0) != cell_index {
+                    continue;
+                }
+                tests += 1;
+                if tests > UV_PAIR_BUDGET {
+                    complete = false;
+                    break 'cells;
+                }
+                if uv_triangles_overlap(&triangles[a as usize].1, &triangles[b as usize].1) {
+                    overlapping[a as usize] = true;
+                    overlapping[b as usize] = true;
+                }
+            }
+        }
+    }
+
+    let result = triangles
+        .iter()
+        .zip(overlapping)
+        .filter(|(_, overlaps)| *overlaps)
+        .map(|((triangle, _), _)| *triangle)
+        .collect();
+    (result, complete)
+}
+
+// separating axis test on the edge normals of both triangles, touching counts as separated
+fn uv_triangles_overlap(a: &[Vector2<f32>; 3], b: &[Vector2<f32>; 3]) -> bool {
+    for triangle in [a, b] {
+        for i in 0..3 {
+            let edge = triangle[(i + 1) % 3] - triangle[i];
+            let axis = Vector2::new(-edge.y, edge.x);
+            let project = |t: &[Vector2<f32>; 3]| {
+                let d = t.map(|p| p.dot(axis));
+                (d[0].min(d[1]).min(d[2]), d[0].max(d[1]).max(d[2]))
+            };
+            let (a_min, a_max) = project(a);
+            let (b_min, b_max) = project(b);
+            if a_max.min(b_max) - a_min.max(b_min) <= UV_OVERLAP_EPSILON * axis.magnitude() {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OverlayVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+// the vertices of a mesh copied back from its vertex buffer, meshes only keep their positions on the cpu
+fn read_vertices(device: &wgpu::Device, queue: &wgpu::Queue, mesh: &model::Mesh) -> anyhow::Result<Vec<model::ModelVertex>> {
+    let size = mesh.vertex_buffer.size();
+    if size == 0 {
+        return Ok(Vec::new());
+    }
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Validation Readback Buffer"),
+        size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Validation Readback Encoder") });
+    encoder.copy_buffer_to_buffer(&mesh.vertex_buffer, 0, &buffer, 0, size);
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv()??;
+    let vertices = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+    Ok(vertices)
+}
+
+impl State {
+    // the checks run the first time a mesh is drawn in the validation view instead of on every load,
+    // stacked uvs can take a while. live link updates drop the overlay so they're checked again
+    pub(crate) fn validate_meshes(&mut self) {
+        if self.render_output_mode != crate::RenderOutputMode::Validation {
+            return;
+        }
+        let device = &self.device;
+        let queue = &self.queue;
+        for (model_index, model) in self.models.iter_mut().enumerate() {
+            let mut reports = Vec::new();
+            for mesh in model.meshes.iter_mut().filter(|mesh| mesh.overlay.is_none()) {
+                let vertices = match read_vertices(device, queue, mesh) {
+                    Ok(vertices) => vertices,
+                    Err(err) => {
+                        println!("could not read back {:?} for validation : {:#}", mesh.name, err);
+                        Vec::new()
+                    }
+                };
+                let mut report = validate_mesh(&mesh.name, &vertices, &mesh.indices);
+                mesh.overlay = Some(MeshOverlay::new(device, &report));
+                //the overlay has them on the gpu now
+                report.highlights = Vec::new();
+                reports.push(report);
+            }
+            if !reports.is_empty() {
+                print!("{}", ValidationReport::new(&format!("model {}", model_index), reports));
+            }
+        }
+    }
+}
+
+// gpu buffers with the highlighted triangles and edges of one mesh, empty for clean meshes
+pub struct MeshOverlay {
+    triangles: Option<(wgpu::Buffer, u32)>,
+    lines: Option<(wgpu::Buffer, u32)>,
+}
+
+impl MeshOverlay {
+    pub fn new(device: &wgpu::Device, report: &MeshReport) -> Self {
+        let mut triangles = Vec::new();
+        let mut lines = Vec::new();
+        for highlight in &report.highlights {
+            match highlight {
+                Highlight::Triangle(positions, issue) => {
+                    triangles.extend(positions.iter().map(|&position| OverlayVertex { position, color: issue.color() }));
+                }
+                Highlight::Edge(positions, issue) => {
+                    lines.extend(positions.iter().map(|&position| OverlayVertex { position, color: issue.color() }));
+                }
+            }
+        }
+        let buffer = |vertices: &[OverlayVertex], label: &str| {
+            if vertices.is_empty() {
+                return None;
+            }
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+            Some((buffer, vertices.len() as u32))
+        };
+        Self {
+            triangles: buffer(&triangles, "Validation Triangle Buffer"),
+            lines: buffer(&lines, "Validation Line Buffer"),
+        }
+    }
+}
+
+// draws the highlights of every mesh on top of the shaded scene
+pub struct OverlayRenderer {
+    triangle_pipeline: wgpu::RenderPipeline,
+    line_pipeline: wgpu::RenderPipeline,
+}
+
+impl OverlayRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Validation Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::load_shader("validation.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Validation Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = |topology: wgpu::PrimitiveTopology, label: &str| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+                            step_mode: wgpu::VertexStepMode::Vertex,
+                            attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+                        },
+                        model::InstanceRaw::desc(),
+                    ],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                //pulled towards the camera in the shader, depth bias doesn't work for lines
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        Self {
+            triangle_pipeline: pipeline(wgpu::PrimitiveTopology::TriangleList, "Validation Triangle Pipeline"),
+            line_pipeline: pipeline(wgpu::PrimitiveTopology::LineList, "Validation Line Pipeline"),
+        }
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, models: &'a [model::Model], camera_bind_group: &'a wgpu::BindGroup) {
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        for (pipeline, lines) in [(&self.triangle_pipeline, false), (&self.line_pipeline, true)] {
+            render_pass.set_pipeline(pipeline);
+            for model in models {
+                render_pass.set_vertex_buffer(1, model.instance_buffer.slice(..));
+                for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+                    let Some(overlay) = &mesh.overlay else {
+                        continue;
+                    };
+                    let overlay = if lines { &overlay.lines } else { &overlay.triangles };
+                    if let Some((buffer, vertex_count)) = overlay {
+                        render_pass.set_vertex_buffer(0, buffer.slice(..));
+                        render_pass.draw(0..*vertex_count, model.instance_range(mesh_index));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3], normal: [f32; 3]) -> model::ModelVertex {
+        model::ModelVertex { position, tex_coords: [0.0; 2], normal, tangent: [0.0; 3], bitangent: [0.0; 3] }
+    }
+
+    // a unit cube with its own 4 vertices per face like an obj with face normals, wound counter clockwise from outside
+    fn cube() -> (Vec<model::ModelVertex>, Vec<u32>) {
+        let x = Vector3::unit_x();
+        let y = Vector3::unit_y();
+        let z = Vector3::unit_z();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (u, v) in [(y, z), (z, y), (z, x), (x, z), (x, y), (y, x)] {
+            let normal = u.cross(v);
+            let first = vertices.len() as u32;
+            for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                vertices.push(vertex(((normal + u * a + v * b) * 0.5).into(), normal.into()));
+            }
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+        (vertices, indices)
+    }
+
+    #[test]
+    fn a_single_triangle_has_three_boundary_edges() {
+        let normal = [0.0, 0.0, 1.0];
+        let vertices = [vertex([0.0, 0.0, 0.0], normal), vertex([1.0, 0.0, 0.0], normal), vertex([0.0, 1.0, 0.0], normal)];
+        let report = validate_mesh("triangle", &vertices, &[0, 1, 2]);
+        assert_eq!(report.boundary_edges, 3);
+        assert_eq!(report.error_count(), 0);
+    }
+
+    #[test]
+    fn a_closed_cube_is_clean() {
+        let (vertices, indices) = cube();
+        let report = validate_mesh("cube", &vertices, &indices);
+        assert_eq!(report.triangle_count, 12);
+        assert_eq!(report.boundary_edges, 0);
+        assert_eq!(report.error_count(), 0);
+        assert!(report.highlights.is_empty());
+    }
+
+    #[test]
+    fn degenerate_triangles_are_reported() {
+        let normal = [0.0, 0.0, 1.0];
+        //all three corners on one line
+        let vertices = [vertex([0.0, 0.0, 0.0], normal), vertex([1.0, 0.0, 0.0], normal), vertex([2.0, 0.0, 0.0], normal)];
+        let report = validate_mesh("line", &vertices, &[0, 1, 2]);
+        assert_eq!(report.degenerate_triangles, 1);
+        assert!(report.highlights.iter().all(|highlight| matches!(highlight, Highlight::Edge(_, Issue::Degenerate))));
+    }
+
+    #[test]
+    fn flipped_triangles_are_reported() {
+        //wound towards +z with normals pointing to -z
+        let normal = [0.0, 0.0, -1.0];
+        let vertices = [vertex([0.0, 0.0, 0.0], normal), vertex([1.0, 0.0, 0.0], normal), vertex([0.0, 1.0, 0.0], normal)];
+        let report = validate_mesh("flipped", &vertices, &[0, 1, 2]);
+        assert_eq!(report.flipped_normal_triangles, 1);
+        assert_eq!(report.degenerate_triangles, 0);
+
+        //the same in a cube, one face turned around
+        let (vertices, mut indices) = cube();
+        indices.swap(1, 2);
+        let report = validate_mesh("cube", &vertices, &indices);
+        assert_eq!(report.flipped_normal_triangles, 1);
+        assert!(report.inconsistent_winding_edges > 0);
+    }
+}
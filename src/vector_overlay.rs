@@ -0,0 +1,181 @@
+use wgpu::util::DeviceExt;
+use crate::{model, shaders};
+use crate::model::Vertex;
+
+// fraction of the scene bounds diagonal used as the line length before it's been adjusted
+const DEFAULT_LENGTH_RATIO: f32 = 0.01;
+
+// draws the normal, tangent and bitangent of every vertex straight from the mesh vertex buffers,
+// nothing gets copied, each ModelVertex is fed in as an instance and expanded to 3 lines
+pub struct VectorOverlay {
+    pub enabled: bool,
+    pub pipeline: wgpu::RenderPipeline,
+    length: Option<f32>,
+    layout: wgpu::PipelineLayout,
+    params_buffer: wgpu::Buffer,
+    params_bind_group: wgpu::BindGroup,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct VectorParams {
+    length: f32,
+    _padding: [f32; 3],
+}
+
+impl VectorOverlay {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        let params_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("vector_params_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vector Params Buffer"),
+            contents: bytemuck::cast_slice(&[VectorParams { length: 0.0, _padding: [0.0; 3] }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Vector Params Bind Group"),
+            layout: &params_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Vector Overlay Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, &params_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            enabled: false,
+            pipeline: Self::create_pipeline(device, &layout, format),
+            length: None,
+            layout,
+            params_buffer,
+            params_bind_group,
+        }
+    }
+
+    pub fn create_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Vector Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(shaders::load_shader("vectors.wgsl").into()),
+        });
+        let vertex_layout = model::ModelVertex::desc();
+        let instance_layout = model::InstanceRaw::desc();
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Vector Overlay Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    //one ModelVertex per instance
+                    wgpu::VertexBufferLayout {
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        ..vertex_layout
+                    },
+                    //a single model instance bound as a slice, stride 0 repeats it for every vertex
+                    wgpu::VertexBufferLayout {
+                        array_stride: 0,
+                        ..instance_layout
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    pub fn reload(&self, device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        Self::create_pipeline(device, &self.layout, format)
+    }
+
+    pub fn toggle(&mut self, queue: &wgpu::Queue, models: &[model::Model]) {
+        self.enabled = !self.enabled;
+        if self.enabled && self.length.is_none() {
+            self.set_length(queue, default_length(models));
+        }
+        println!("vector overlay {}", if self.enabled { "on" } else { "off" });
+    }
+
+    pub fn scale_length(&mut self, queue: &wgpu::Queue, models: &[model::Model], factor: f32) {
+        let length = self.length.unwrap_or_else(|| default_length(models)) * factor;
+        self.set_length(queue, length);
+        println!("vector length : {}", length);
+    }
+
+    fn set_length(&mut self, queue: &wgpu::Queue, length: f32) {
+        self.length = Some(length);
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[VectorParams { length, _padding: [0.0; 3] }]));
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, models: &'a [model::Model], camera_bind_group: &'a wgpu::BindGroup) {
+        let instance_size = std::mem::size_of::<model::InstanceRaw>() as wgpu::BufferAddress;
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.params_bind_group, &[]);
+        for model in models {
+            for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                for instance in model.instance_range(mesh_index) {
+                    let instance = instance as wgpu::BufferAddress;
+                    render_pass.set_vertex_buffer(1, model.instance_buffer.slice(instance * instance_size..(instance + 1) * instance_size));
+                    render_pass.draw(0..6, 0..mesh.positions.len() as u32);
+                }
+            }
+        }
+    }
+}
+
+// a small fraction of the scene size so the lines are visible without covering the model
+fn default_length(models: &[model::Model]) -> f32 {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for mesh in models.iter().flat_map(|model| &model.meshes) {
+        for point in &mesh.positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(point[axis]);
+                max[axis] = max[axis].max(point[axis]);
+            }
+        }
+    }
+    if min[0] > max[0] {
+        return 1.0;
+    }
+    let diagonal = (0..3).map(|axis| (max[axis] - min[axis]).powi(2)).sum::<f32>().sqrt();
+    if diagonal > 0.0 { diagonal * DEFAULT_LENGTH_RATIO } else { 1.0 }
+}
@@ -0,0 +1,95 @@
+// per vertex normal (blue), tangent (red) and bitangent (green) line segments.
+// every ModelVertex is one instance, vertex_index picks the vector and which end of it
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Params {
+    length: f32,
+};
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+};
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+//zero length vectors are still drawn as a point instead of producing nan
+fn safe_normalize(v: vec3<f32>) -> vec3<f32> {
+    let len = length(v);
+    if len > 0.0 {
+        return v / len;
+    }
+    return vec3<f32>(0.0);
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+
+    let line = index / 2u;
+    let tip = f32(index % 2u);
+
+    var direction: vec3<f32>;
+    var color: vec3<f32>;
+    if line == 0u {
+        direction = normal_matrix * model.normal;
+        color = vec3<f32>(0.2, 0.4, 1.0);
+    } else if line == 1u {
+        direction = normal_matrix * model.tangent;
+        color = vec3<f32>(1.0, 0.15, 0.15);
+    } else {
+        direction = normal_matrix * model.bitangent;
+        color = vec3<f32>(0.15, 1.0, 0.15);
+    }
+
+    let world_position = (model_matrix * vec4<f32>(model.position, 1.0)).xyz
+        + safe_normalize(direction) * params.length * tip;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
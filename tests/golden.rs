@@ -0,0 +1,144 @@
+// renders the bundled cubes in each render mode on the software adapter and compares them with
+// the reference images in tests/golden. run with KANIRENDERER_UPDATE_GOLDEN=1 to write new references
+// after an intended change, failures leave the render and a diff image in target/golden-diff.
+// the references come from mesa's llvmpipe through gl, so that's the backend unless WGPU_BACKEND says
+// otherwise. wireframe isn't checked, llvmpipe can't draw polygons as lines and would just fill them
+
+use std::path::{Path, PathBuf};
+use image::{Rgba, RgbaImage};
+use kanirenderer_viewer::HeadlessRenderer;
+
+const SIZE: u32 = 128;
+const MODES: [&str; 4] = ["unlit", "lit", "shadow", "debug"];
+
+// CIE76 delta E a pixel may be off by before it counts, ~2.3 is just noticeable.
+// llvmpipe versions round lighting a little differently, so this is looser than that
+const PIXEL_TOLERANCE: f32 = 6.0;
+// rasterization rules differ on some edges, a few pixels along silhouettes may flip
+const MAX_FAILED_FRACTION: f32 = 0.005;
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+// checks every mode and panics with all of the failures at once
+fn check_model(model: &str) {
+    if std::env::var_os("WGPU_BACKEND").is_none() {
+        std::env::set_var("WGPU_BACKEND", "gl");
+    }
+    //a missing adapter is a failure, a skipped comparison would pass without checking anything
+    let mut renderer = HeadlessRenderer::with_adapter("default", SIZE, SIZE, true)
+        .unwrap_or_else(|err| panic!("no software adapter for the golden images of {} : {:#}, install mesa (llvmpipe)", model, err));
+    renderer.load_model(&manifest_dir().join("res").join(model)).unwrap();
+    let (position, target) = renderer.orbit_pose(45.0, 25.0).unwrap();
+    renderer.look_at(position, target);
+
+    let update = std::env::var("KANIRENDERER_UPDATE_GOLDEN").is_ok_and(|value| value == "1");
+    let stem = Path::new(model).file_stem().unwrap().to_string_lossy().to_string();
+    let mut failures = Vec::new();
+    for mode in MODES {
+        renderer.set_mode(mode).unwrap();
+        let actual = renderer.render().unwrap();
+        let name = format!("{}_{}", stem, mode);
+        let reference_path = manifest_dir().join("tests").join("golden").join(format!("{}.png", name));
+
+        if update {
+            std::fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+            actual.save(&reference_path).unwrap();
+            println!("updated {:?}", reference_path);
+            continue;
+        }
+        let expected = match image::open(&reference_path) {
+            Ok(expected) => expected.to_rgba8(),
+            Err(err) => {
+                let actual_path = save_output(&name, "actual", &actual);
+                failures.push(format!("{} : no reference image {:?} ({}), render saved to {:?}", name, reference_path, err, actual_path));
+                continue;
+            }
+        };
+        if let Err(message) = compare(&name, &expected, &actual) {
+            failures.push(message);
+        }
+    }
+    if !failures.is_empty() {
+        panic!("{} golden image(s) differ, run with KANIRENDERER_UPDATE_GOLDEN=1 if the change is intended\n{}", failures.len(), failures.join("\n"));
+    }
+}
+
+fn compare(name: &str, expected: &RgbaImage, actual: &RgbaImage) -> Result<(), String> {
+    if expected.dimensions() != actual.dimensions() {
+        let actual_path = save_output(name, "actual", actual);
+        return Err(format!("{} : reference is {:?}, render is {:?} ({:?})", name, expected.dimensions(), actual.dimensions(), actual_path));
+    }
+
+    //expected in dim grey with the pixels that are off in red, brighter the further off they are
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut failed = 0;
+    let mut worst: f32 = 0.0;
+    for (x, y, expected_pixel) in expected.enumerate_pixels() {
+        let delta = delta_e(expected_pixel, actual.get_pixel(x, y));
+        worst = worst.max(delta);
+        let grey = (luminance(expected_pixel) * 80.0) as u8;
+        let pixel = if delta > PIXEL_TOLERANCE {
+            failed += 1;
+            Rgba([(128.0 + delta.min(50.0) / 50.0 * 127.0) as u8, 0, 0, 255])
+        } else {
+            Rgba([grey, grey, grey, 255])
+        };
+        diff.put_pixel(x, y, pixel);
+    }
+
+    let failed_fraction = failed as f32 / (actual.width() * actual.height()) as f32;
+    if failed_fraction <= MAX_FAILED_FRACTION {
+        return Ok(());
+    }
+    let actual_path = save_output(name, "actual", actual);
+    let diff_path = save_output(name, "diff", &diff);
+    Err(format!(
+        "{} : {} pixels ({:.2}%) off by more than delta E {}, worst {:.1}. render {:?}, diff {:?}",
+        name, failed, failed_fraction * 100.0, PIXEL_TOLERANCE, worst, actual_path, diff_path,
+    ))
+}
+
+fn save_output(name: &str, kind: &str, image: &RgbaImage) -> PathBuf {
+    let dir = manifest_dir().join("target").join("golden-diff");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{}_{}.png", name, kind));
+    image.save(&path).unwrap();
+    path
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+fn luminance(pixel: &Rgba<u8>) -> f32 {
+    0.2126 * srgb_to_linear(pixel[0]) + 0.7152 * srgb_to_linear(pixel[1]) + 0.0722 * srgb_to_linear(pixel[2])
+}
+
+// srgb to CIELAB with a D65 white point, alpha is ignored since the frames are opaque
+fn lab(pixel: &Rgba<u8>) -> [f32; 3] {
+    let (r, g, b) = (srgb_to_linear(pixel[0]), srgb_to_linear(pixel[1]), srgb_to_linear(pixel[2]));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f32| if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn delta_e(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    let (a, b) = (lab(a), lab(b));
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+#[test]
+fn cube_render_modes() {
+    check_model("cube.obj");
+}
+
+#[test]
+fn smol_cube_render_modes() {
+    check_model("smol_cube.obj");
+}
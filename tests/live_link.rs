@@ -0,0 +1,32 @@
+// pushing meshes through HeadlessRenderer on the software adapter (llvmpipe through gl unless
+// WGPU_BACKEND says otherwise, like the golden images)
+
+use kanirenderer_viewer::{HeadlessRenderer, MeshUpdate};
+
+fn renderer() -> HeadlessRenderer {
+    if std::env::var_os("WGPU_BACKEND").is_none() {
+        std::env::set_var("WGPU_BACKEND", "gl");
+    }
+    HeadlessRenderer::with_adapter("default", 64, 64, true).unwrap_or_else(|err| panic!("no software adapter : {:#}, install mesa (llvmpipe)", err))
+}
+
+#[test]
+fn rejected_updates_keep_the_pushed_state() {
+    let mut renderer = renderer();
+    renderer.push_mesh(MeshUpdate {
+        name: "live".to_string(),
+        positions: Some(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]),
+        indices: Some(vec![0, 1, 2]),
+        ..Default::default()
+    }).unwrap();
+
+    let bad_index = MeshUpdate { name: "live".to_string(), indices: Some(vec![0, 1, 5]), ..Default::default() };
+    assert!(renderer.push_mesh(bad_index).is_err());
+    let unknown_material = MeshUpdate { name: "live".to_string(), uvs: Some(vec![0.0; 6]), material: Some("nope".to_string()), ..Default::default() };
+    assert!(renderer.push_mesh(unknown_material).is_err());
+
+    //still knows the positions and indices from the first push
+    let uvs_only = MeshUpdate { name: "live".to_string(), uvs: Some(vec![0.5; 6]), ..Default::default() };
+    renderer.push_mesh(uvs_only).unwrap();
+    renderer.render().unwrap();
+}
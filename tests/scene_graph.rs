@@ -0,0 +1,208 @@
+// the entity tree through HeadlessRenderer on the software adapter (llvmpipe through gl unless
+// WGPU_BACKEND says otherwise, like the golden images): world matrices reaching the instance data,
+// hidden subtrees, reparenting and mesh entities following the meshes of a model
+
+use std::path::PathBuf;
+use kanirenderer_viewer::{Attachment, HeadlessRenderer, MeshUpdate, Transform};
+
+fn renderer() -> HeadlessRenderer {
+    if std::env::var_os("WGPU_BACKEND").is_none() {
+        std::env::set_var("WGPU_BACKEND", "gl");
+    }
+    HeadlessRenderer::with_adapter("default", 64, 64, true).unwrap_or_else(|err| panic!("no software adapter : {:#}, install mesa (llvmpipe)", err))
+}
+
+// an obj with one triangle per object, in its own folder so the tests don't share files
+fn write_obj(test: &str, groups: &[&str]) -> PathBuf {
+    let dir = std::env::temp_dir().join("kanirenderer_scene_graph").join(test);
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut obj = String::from("vt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n");
+    for (index, group) in groups.iter().enumerate() {
+        let first = index * 3 + 1;
+        obj += &format!("o {}\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf {}/1/1 {}/2/1 {}/3/1\n", group, first, first + 1, first + 2);
+    }
+    let path = dir.join("groups.obj");
+    std::fs::write(&path, obj).unwrap();
+    path
+}
+
+fn entity_named(renderer: &HeadlessRenderer, name: &str) -> u32 {
+    renderer.entities().iter().find(|entity| entity.name == name).unwrap_or_else(|| panic!("no entity named {}", name)).id
+}
+
+fn model_entity(renderer: &HeadlessRenderer) -> u32 {
+    renderer.entities().iter().find(|entity| entity.attachment == Attachment::Model { model: 0 }).unwrap().id
+}
+
+fn translation(position: [f32; 3]) -> Transform {
+    Transform { position, ..Default::default() }
+}
+
+// x of the translation of every instance of a mesh
+fn instance_x(renderer: &HeadlessRenderer, mesh: usize) -> Vec<f32> {
+    renderer.mesh_transforms(0, mesh).iter().map(|matrix| matrix[3][0]).collect()
+}
+
+#[test]
+fn obj_groups_become_mesh_entities() {
+    let mut renderer = renderer();
+    renderer.load_model(&write_obj("groups", &["a", "b", "c"])).unwrap();
+    let model = model_entity(&renderer);
+    let children = renderer.entities().iter().find(|entity| entity.id == model).unwrap().children.clone();
+    let meshes = children
+        .iter()
+        .map(|id| {
+            let entity = renderer.entities().iter().find(|entity| entity.id == *id).unwrap();
+            (entity.name.clone(), entity.attachment)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(meshes, vec![
+        ("a".to_string(), Attachment::Mesh { model: 0, mesh: 0 }),
+        ("b".to_string(), Attachment::Mesh { model: 0, mesh: 1 }),
+        ("c".to_string(), Attachment::Mesh { model: 0, mesh: 2 }),
+    ]);
+}
+
+#[test]
+fn meshes_are_drawn_while_the_model_is_still_loading() {
+    let mut renderer = renderer();
+    renderer.start_loading(&write_obj("partial", &["a", "b", "c", "d"]));
+    //one event per poll, so the meshes come in one after the other
+    let mut partial_polls = 0;
+    while renderer.poll_loading().unwrap() {
+        let meshes = renderer.mesh_count(0);
+        if meshes > 1 && meshes < 4 {
+            partial_polls += 1;
+        }
+        //moving the model rewrites the instances of every mesh it has by now
+        if let Some(model) = renderer.entities().iter().find(|entity| entity.attachment == Attachment::Model { model: 0 }) {
+            renderer.set_entity_transform(model.id, translation([1.0, 0.0, 0.0])).unwrap();
+        }
+        for mesh in 0..meshes {
+            assert_eq!(renderer.mesh_transforms(0, mesh).len(), 1, "mesh {} of {} has no instance", mesh, meshes);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(partial_polls > 0);
+    assert_eq!(renderer.entities().iter().filter(|entity| matches!(entity.attachment, Attachment::Mesh { .. })).count(), 4);
+}
+
+#[test]
+fn parent_transforms_reach_the_instances() {
+    let mut renderer = renderer();
+    renderer.load_model(&write_obj("propagation", &["a", "b"])).unwrap();
+    let model = model_entity(&renderer);
+    renderer.set_entity_transform(model, Transform { position: [1.0, 0.0, 0.0], rotation: [0.0; 3], scale: [2.0; 3] }).unwrap();
+    renderer.set_entity_transform(entity_named(&renderer, "b"), translation([1.0, 0.0, 0.0])).unwrap();
+    assert_eq!(instance_x(&renderer, 0), vec![1.0]);
+    //the child's offset is scaled by the parent
+    assert_eq!(instance_x(&renderer, 1), vec![3.0]);
+
+    //a group above the model moves everything below it
+    let group = renderer.add_entity(None, Attachment::None, translation([10.0, 0.0, 0.0])).unwrap();
+    renderer.set_entity_parent(model, Some(group)).unwrap();
+    assert_eq!(instance_x(&renderer, 0), vec![11.0]);
+    assert_eq!(instance_x(&renderer, 1), vec![13.0]);
+
+    //a second placement of the model is a second instance of every mesh
+    renderer.spawn_entity(0, translation([-5.0, 0.0, 0.0])).unwrap();
+    assert_eq!(instance_x(&renderer, 0), vec![11.0, -5.0]);
+    assert_eq!(instance_x(&renderer, 1), vec![13.0, -5.0]);
+    renderer.render().unwrap();
+}
+
+#[test]
+fn hidden_subtrees_are_not_drawn() {
+    let mut renderer = renderer();
+    renderer.load_model(&write_obj("hidden", &["a", "b"])).unwrap();
+    let model = model_entity(&renderer);
+    renderer.set_entity_visible(model, false).unwrap();
+    assert!(instance_x(&renderer, 0).is_empty());
+    assert!(instance_x(&renderer, 1).is_empty());
+    renderer.render().unwrap();
+
+    renderer.set_entity_visible(model, true).unwrap();
+    renderer.set_entity_visible(entity_named(&renderer, "a"), false).unwrap();
+    assert!(instance_x(&renderer, 0).is_empty());
+    assert_eq!(instance_x(&renderer, 1).len(), 1);
+}
+
+#[test]
+fn reparenting_rejects_cycles() {
+    let mut renderer = renderer();
+    renderer.load_model(&write_obj("cycles", &["a"])).unwrap();
+    let model = model_entity(&renderer);
+    let mesh = entity_named(&renderer, "a");
+    let group = renderer.add_entity(Some(mesh), Attachment::None, Transform::default()).unwrap();
+    assert!(renderer.set_entity_parent(model, Some(model)).is_err());
+    assert!(renderer.set_entity_parent(model, Some(mesh)).is_err());
+    assert!(renderer.set_entity_parent(model, Some(group)).is_err());
+    //nothing moved
+    assert_eq!(renderer.entities().iter().find(|entity| entity.id == model).unwrap().parent, None);
+
+    renderer.set_entity_parent(mesh, None).unwrap();
+    renderer.set_entity_parent(model, Some(group)).unwrap();
+    assert_eq!(renderer.entities().iter().find(|entity| entity.id == model).unwrap().parent, Some(group));
+}
+
+#[test]
+fn reload_with_fewer_groups_drops_their_entities() {
+    let mut renderer = renderer();
+    let path = write_obj("reload", &["a", "b", "c"]);
+    renderer.load_model(&path).unwrap();
+    renderer.set_entity_transform(entity_named(&renderer, "b"), translation([4.0, 0.0, 0.0])).unwrap();
+
+    write_obj("reload", &["a", "b"]);
+    renderer.reload_model(0, &path).unwrap();
+    let meshes = renderer
+        .entities()
+        .iter()
+        .filter_map(|entity| match entity.attachment {
+            Attachment::Mesh { mesh, .. } => Some((entity.name.clone(), mesh)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(meshes, vec![("a".to_string(), 0), ("b".to_string(), 1)]);
+    //the transforms of the groups that are still there stay
+    assert_eq!(instance_x(&renderer, 1), vec![4.0]);
+    renderer.render().unwrap();
+}
+
+#[test]
+fn removed_meshes_take_their_entities_along() {
+    let mut renderer = renderer();
+    renderer.load_model(&write_obj("remove_mesh", &["a", "b", "c"])).unwrap();
+    let c = entity_named(&renderer, "c");
+    renderer.set_entity_transform(c, translation([7.0, 0.0, 0.0])).unwrap();
+    renderer.set_entity_visible(entity_named(&renderer, "a"), false).unwrap();
+
+    renderer.remove_mesh("b").unwrap();
+    assert!(renderer.entities().iter().all(|entity| entity.name != "b"));
+    let c_entity = renderer.entities().iter().find(|entity| entity.id == c).unwrap();
+    assert_eq!(c_entity.attachment, Attachment::Mesh { model: 0, mesh: 1 });
+    //c keeps its own transform and a stays hidden
+    assert!(instance_x(&renderer, 0).is_empty());
+    assert_eq!(instance_x(&renderer, 1), vec![7.0]);
+    renderer.render().unwrap();
+
+    //a pushed mesh with a new name is a model of its own with its own entities
+    renderer.push_mesh(MeshUpdate {
+        name: "live".to_string(),
+        positions: Some(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]),
+        indices: Some(vec![0, 1, 2]),
+        ..Default::default()
+    }).unwrap();
+    assert!(renderer.entities().iter().any(|entity| entity.attachment == Attachment::Mesh { model: 1, mesh: 0 }));
+}
+
+#[test]
+fn failed_loads_report_the_cause_without_a_backtrace() {
+    //the viewer's main turns backtraces on, they shouldn't end up in the load error
+    std::env::set_var("RUST_BACKTRACE", "1");
+    let mut renderer = renderer();
+    let missing = std::env::temp_dir().join("kanirenderer_scene_graph").join("missing.obj");
+    let err = renderer.load_model(&missing).unwrap_err().to_string();
+    assert!(err.contains("missing.obj"), "{}", err);
+    assert!(!err.contains("backtrace"), "{}", err);
+    assert!(renderer.entities().is_empty());
+}